@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use webhook::event;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(config) = std::str::from_utf8(data) {
+        event::fuzz::parse_event(config);
+    }
+});
@@ -0,0 +1,155 @@
+use thiserror::Error;
+
+use crate::event::{default_queue_capacity, Event};
+use crate::event::process::operation;
+use crate::event::{sender, trigger};
+
+#[derive(Error, Debug)]
+pub enum BuildError {
+    #[error("missing field: {0}")]
+    MissingField(String),
+}
+
+pub struct EventBuilder {
+    name: String,
+    trigger: Vec<trigger::Trigger>,
+    process: Vec<operation::Op>,
+    target: Vec<sender::SenderConfig>,
+    queue_capacity: Option<usize>,
+}
+
+impl Default for EventBuilder {
+    fn default() -> Self {
+        EventBuilder {
+            name: String::new(),
+            trigger: vec![],
+            process: vec![],
+            target: vec![],
+            queue_capacity: default_queue_capacity(),
+        }
+    }
+}
+
+impl EventBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn add_trigger(mut self, trigger: trigger::Trigger) -> Self {
+        self.trigger.push(trigger);
+        self
+    }
+
+    pub fn add_op(mut self, op: operation::Op) -> Self {
+        self.process.push(op);
+        self
+    }
+
+    pub fn add_target(mut self, target: sender::SenderConfig) -> Self {
+        self.target.push(target);
+        self
+    }
+
+    pub fn queue_capacity(mut self, queue_capacity: Option<usize>) -> Self {
+        self.queue_capacity = queue_capacity;
+        self
+    }
+
+    pub fn build(self) -> Result<Event, BuildError> {
+        if self.name.is_empty() {
+            return Err(BuildError::MissingField("name".into()));
+        }
+        if self.trigger.is_empty() {
+            return Err(BuildError::MissingField("trigger".into()));
+        }
+        if self.target.is_empty() {
+            return Err(BuildError::MissingField("target".into()));
+        }
+
+        Ok(Event {
+            name: self.name,
+            trigger: self.trigger,
+            process: if self.process.is_empty() { None } else { Some(self.process) },
+            target: self.target,
+            timeout_ms: None,
+            timeout_behavior: None,
+            labels: None,
+            max_state_keys: None,
+            queue_capacity: self.queue_capacity,
+            enabled: None,
+            tags: None,
+            source: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger() -> trigger::Trigger {
+        serde_yaml::from_str("type: mock\nconfig: { messages: [hello] }").unwrap()
+    }
+
+    fn target() -> sender::SenderConfig {
+        serde_yaml::from_str("mock: { name: builder_tests }").unwrap()
+    }
+
+    #[test]
+    fn test_build_ok() {
+        let event = EventBuilder::new()
+            .name("builder")
+            .add_trigger(trigger())
+            .add_op(serde_yaml::from_str("sleep: { duration_ms: 1 }").unwrap())
+            .add_target(target())
+            .queue_capacity(Some(4))
+            .build()
+            .unwrap();
+
+        assert_eq!(event.name, "builder");
+        assert_eq!(event.trigger.len(), 1);
+        assert_eq!(event.process.as_ref().map(Vec::len), Some(1));
+        assert_eq!(event.target.len(), 1);
+        assert_eq!(event.queue_capacity, Some(4));
+        assert!(event.validate().is_ok());
+    }
+
+    #[test]
+    fn test_build_defaults_ok() {
+        let event = EventBuilder::new()
+            .name("builder")
+            .add_trigger(trigger())
+            .add_target(target())
+            .build()
+            .unwrap();
+
+        assert!(event.process.is_none());
+        assert_eq!(event.queue_capacity, Some(0));
+    }
+
+    #[test]
+    fn test_build_missing_field_err() {
+        let err = EventBuilder::new()
+            .add_trigger(trigger())
+            .add_target(target())
+            .build();
+        assert!(matches!(err, Err(BuildError::MissingField(f)) if f == "name"));
+
+        let err = EventBuilder::new()
+            .name("builder")
+            .add_target(target())
+            .build();
+        assert!(matches!(err, Err(BuildError::MissingField(f)) if f == "trigger"));
+
+        let err = EventBuilder::new()
+            .name("builder")
+            .add_trigger(trigger())
+            .build();
+        assert!(matches!(err, Err(BuildError::MissingField(f)) if f == "target"));
+    }
+}
@@ -0,0 +1,22 @@
+use crate::event::Event;
+use crate::event::process::{Identifier, Item, State, Value};
+
+pub fn parse_event(config: &str) {
+    if let Ok(event) = serde_yaml::from_str::<Event>(config) {
+        let _ = event.validate();
+    }
+}
+
+pub fn set_and_get_state(identifier: &str) {
+    let key = Identifier::from(identifier);
+    let _ = key.split();
+
+    let mut state = State::new();
+    let _ = state.set("list".into(), Item::Vec(vec!(Item::Map(Default::default()))));
+    let _ = state.set(key.clone(), Item::Value(Value::IntValue(1)));
+    let _ = state.get(&key);
+
+    let nested = Identifier::from(format!("list.{}", identifier));
+    let _ = state.set(nested.clone(), Item::Value(Value::None));
+    let _ = state.get(&nested);
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use prometheus::proto::MetricFamily;
+
+const EVENT_NAME_LABEL: &str = "event_name";
+const SENDER_LABEL: &str = "sender";
+// Prefixes every metric name, so the series do not collide with other jobs on a shared Prometheus.
+const NAMESPACE: &str = "webhook";
+
+/// Metrics of every pipeline. Each pipeline has its own registry so that its series carry the
+/// event labels, which differ between events.
+#[derive(Default)]
+pub struct PipelineMetrics {
+    pipelines: RwLock<BTreeMap<String, Arc<EventMetrics>>>,
+}
+
+struct EventMetrics {
+    registry: Registry,
+    events_received_total: IntCounter,
+    events_processed_total: IntCounter,
+    events_failed_total: IntCounter,
+    processing_duration_seconds: Histogram,
+    queue_depth: IntGauge,
+    sender_duration_seconds: HistogramVec,
+    sender_failed_total: IntCounterVec,
+}
+
+impl EventMetrics {
+    /// Fails when a label name is not a valid Prometheus label name.
+    fn new(event_name: &str, labels: HashMap<String, String>) -> prometheus::Result<Self> {
+        // The registry attaches its labels without checking them, which would break the exposition.
+        if let Some(label) = labels.keys().find(|k| !super::is_valid_label_name(k)) {
+            return Err(prometheus::Error::Msg(format!("invalid label name \"{}\"", label)));
+        }
+
+        let mut labels = labels;
+        labels.insert(EVENT_NAME_LABEL.to_string(), event_name.to_string());
+        let registry = Registry::new_custom(None, Some(labels))?;
+
+        fn register<T: prometheus::core::Collector + Clone + 'static>(registry: &Registry, collector: T) -> prometheus::Result<T> {
+            registry.register(Box::new(collector.clone()))?;
+            Ok(collector)
+        }
+
+        let counter = |name: &str, help: &str| register(&registry, IntCounter::with_opts(Opts::new(name, help).namespace(NAMESPACE))?);
+        let events_received_total = counter("events_received_total", "Events received from triggers")?;
+        let events_processed_total = counter("events_processed_total", "Events dispatched to every target")?;
+        let events_failed_total = counter("events_failed_total", "Events that failed processing or sending")?;
+
+        let processing_duration_seconds = register(&registry, Histogram::with_opts(
+            HistogramOpts::new("processing_duration_seconds", "Time spent processing and sending an event").namespace(NAMESPACE),
+        )?)?;
+
+        let queue_depth = register(&registry, IntGauge::with_opts(
+            Opts::new("queue_depth", "Events waiting in the pipeline queue").namespace(NAMESPACE),
+        )?)?;
+
+        let sender_duration_seconds = register(&registry, HistogramVec::new(
+            HistogramOpts::new("sender_duration_seconds", "Time spent sending an event, per target").namespace(NAMESPACE),
+            &[SENDER_LABEL],
+        )?)?;
+        let sender_failed_total = register(&registry, IntCounterVec::new(
+            Opts::new("sender_failed_total", "Sends that failed, per target").namespace(NAMESPACE),
+            &[SENDER_LABEL],
+        )?)?;
+
+        Ok(EventMetrics {
+            registry,
+            events_received_total,
+            events_processed_total,
+            events_failed_total,
+            processing_duration_seconds,
+            queue_depth,
+            sender_duration_seconds,
+            sender_failed_total,
+        })
+    }
+}
+
+impl PipelineMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attaches `labels` to every series of the pipeline, replacing the series recorded so far.
+    pub fn register(&self, event_name: &str, labels: HashMap<String, String>) -> prometheus::Result<()> {
+        let metrics = EventMetrics::new(event_name, labels)?;
+        self.pipelines.write().expect("metrics lock poisoned")
+            .insert(event_name.to_string(), Arc::new(metrics));
+        Ok(())
+    }
+
+    fn pipeline(&self, event_name: &str) -> Arc<EventMetrics> {
+        if let Some(metrics) = self.pipelines.read().expect("metrics lock poisoned").get(event_name) {
+            return metrics.clone();
+        }
+
+        self.pipelines.write().expect("metrics lock poisoned")
+            .entry(event_name.to_string())
+            .or_insert_with(|| Arc::new(
+                EventMetrics::new(event_name, HashMap::new()).expect("metrics without event labels are always valid"),
+            ))
+            .clone()
+    }
+
+    pub fn event_received(&self, event_name: &str) {
+        self.pipeline(event_name).events_received_total.inc();
+    }
+
+    pub fn event_processed(&self, event_name: &str, duration: std::time::Duration) {
+        let metrics = self.pipeline(event_name);
+        metrics.events_processed_total.inc();
+        metrics.processing_duration_seconds.observe(duration.as_secs_f64());
+    }
+
+    pub fn event_failed(&self, event_name: &str, duration: std::time::Duration) {
+        let metrics = self.pipeline(event_name);
+        metrics.events_failed_total.inc();
+        metrics.processing_duration_seconds.observe(duration.as_secs_f64());
+    }
+
+    pub fn sender_finished(&self, event_name: &str, sender: &str, duration: std::time::Duration, success: bool) {
+        let metrics = self.pipeline(event_name);
+        if !success {
+            metrics.sender_failed_total.with_label_values(&[sender]).inc();
+        }
+        metrics.sender_duration_seconds.with_label_values(&[sender]).observe(duration.as_secs_f64());
+    }
+
+    pub fn queue_depth(&self, event_name: &str) -> IntGauge {
+        self.pipeline(event_name).queue_depth.clone()
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut families: BTreeMap<String, MetricFamily> = BTreeMap::new();
+        for metrics in self.pipelines.read().expect("metrics lock poisoned").values() {
+            for mut family in metrics.registry.gather() {
+                // The registry appends the event labels after the metric's own labels.
+                for metric in family.mut_metric().iter_mut() {
+                    let mut labels = metric.take_label();
+                    labels.sort_by(|a, b| a.name().cmp(b.name()));
+                    metric.set_label(labels);
+                }
+                match families.get_mut(family.name()) {
+                    Some(merged) => merged.mut_metric().extend(family.take_metric()),
+                    None => { families.insert(family.name().to_string(), family); }
+                }
+            }
+        }
+
+        let mut buffer = vec!();
+        let families = families.into_values().collect::<Vec<_>>();
+        if let Err(e) = TextEncoder::new().encode(&families, &mut buffer) {
+            log::error!("unable to encode metrics: {}", e);
+        }
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_ok() {
+        let metrics = PipelineMetrics::new();
+        metrics.event_received("orders");
+        metrics.event_received("orders");
+        metrics.event_processed("orders", std::time::Duration::from_millis(20));
+        metrics.event_failed("payments", std::time::Duration::from_millis(5));
+        metrics.queue_depth("orders").set(3);
+        metrics.sender_finished("orders", "http[0]", std::time::Duration::from_millis(10), true);
+        metrics.sender_finished("orders", "http[0]", std::time::Duration::from_millis(10), false);
+
+        let text = metrics.encode();
+        assert!(text.contains("webhook_events_received_total{event_name=\"orders\"} 2"));
+        assert!(text.contains("webhook_events_processed_total{event_name=\"orders\"} 1"));
+        assert!(text.contains("webhook_events_failed_total{event_name=\"payments\"} 1"));
+        assert!(text.contains("webhook_processing_duration_seconds_count{event_name=\"orders\"} 1"));
+        assert!(text.contains("webhook_processing_duration_seconds_count{event_name=\"payments\"} 1"));
+        assert!(text.contains("webhook_queue_depth{event_name=\"orders\"} 3"));
+        assert!(text.contains("webhook_sender_duration_seconds_count{event_name=\"orders\",sender=\"http[0]\"} 2"));
+        assert!(text.contains("webhook_sender_failed_total{event_name=\"orders\",sender=\"http[0]\"} 1"));
+        assert_eq!(text.matches("# TYPE webhook_events_received_total counter").count(), 1);
+    }
+
+    #[test]
+    fn test_encode_event_labels() {
+        let metrics = PipelineMetrics::new();
+        metrics.register("orders", HashMap::from([("team".to_string(), "payments".to_string())])).unwrap();
+        metrics.register("audit", HashMap::new()).unwrap();
+        metrics.event_received("orders");
+        metrics.event_received("audit");
+
+        let text = metrics.encode();
+        assert!(text.contains("webhook_events_received_total{event_name=\"orders\",team=\"payments\"} 1"));
+        assert!(text.contains("webhook_events_received_total{event_name=\"audit\"} 1"));
+    }
+
+    #[test]
+    fn test_register_invalid_label_err() {
+        let metrics = PipelineMetrics::new();
+        assert!(metrics.register("orders", HashMap::from([("team-name".to_string(), "payments".to_string())])).is_err());
+        assert!(metrics.encode().is_empty());
+    }
+}
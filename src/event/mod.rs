@@ -1,17 +1,31 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::mpsc;
 use serde::Deserialize;
 use thiserror::Error;
 
 use process::operation;
+pub use builder::{BuildError, EventBuilder};
+pub use metrics::PipelineMetrics;
+pub use utils::logger::{format_json, PipelineLogFilter};
+pub use utils::startup::Startup;
 pub use utils::sync::GracefulSignalInvoker;
 
 use crate::event::trigger::SourceEvent;
-use crate::event::utils::sync::{combine, GracefulSignal, new_graceful_signal};
+use crate::event::utils::sync::{combine, CombinedGracefulSignalInvoker, GracefulSignal, new_graceful_signal, SingleGracefulSignalInvoker};
 
-mod trigger;
+mod builder;
+mod metrics;
+pub mod trigger;
 mod utils;
 mod queue;
-mod sender;
-mod process;
+pub mod sender;
+pub mod process;
+#[cfg(fuzzing)]
+pub mod fuzz;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Event {
@@ -19,91 +33,572 @@ pub struct Event {
     trigger: Vec<trigger::Trigger>,
     process: Option<Vec<operation::Op>>,
     target: Vec<sender::SenderConfig>,
+    timeout_ms: Option<u64>,
+    timeout_behavior: Option<TimeoutBehavior>,
+    labels: Option<HashMap<String, String>>,
+    max_state_keys: Option<usize>,
+    /// Messages buffered between the triggers and the processor. `0` (the default) makes every
+    /// trigger wait for the processor, a bounded capacity absorbs bursts, and `null` is unbounded,
+    /// which can run out of memory when the senders cannot keep up.
+    #[serde(default = "default_queue_capacity")]
+    queue_capacity: Option<usize>,
+    enabled: Option<bool>,
+    tags: Option<Vec<String>>,
+    #[serde(skip)]
+    source: Option<serde_yaml::Value>,
+}
+
+fn default_queue_capacity() -> Option<usize> {
+    Some(0)
+}
+
+/// What happens to a message that is not processed within `timeout_ms`. Delivery is at least once:
+/// a target that did not finish sending before the timeout gets the message again on the next
+/// attempt, and a message returned to the broker is sent to every target again on redelivery.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutBehavior {
+    Drop,
+    /// Processes the message again up to `max_attempts` times, skipping the targets that already
+    /// got it, then returns it to the broker.
+    Retry { max_attempts: usize },
+    DeadLetter { target: Vec<sender::SenderConfig> },
+}
+
+impl Event {
+    pub fn builder() -> EventBuilder {
+        EventBuilder::new()
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        self.tags.iter().flatten().any(|t| tags.contains(t))
+    }
+
+    pub fn validate(&self) -> std::result::Result<(), ExecutorError> {
+        let invalid = |reason: String| ExecutorError::InvalidEvent { name: self.name.clone(), reason };
+
+        if self.name.is_empty() {
+            return Err(invalid("event name must not be empty".into()));
+        }
+        if self.trigger.is_empty() {
+            return Err(invalid("event must have at least one trigger".into()));
+        }
+
+        if let Some(label) = self.labels.iter().flatten().map(|(k, _)| k).find(|k| !is_valid_label_name(k)) {
+            return Err(invalid(format!(
+                "invalid label name \"{}\": must match [a-zA-Z_][a-zA-Z0-9_]* and must not be \"pipeline\", \"event_name\" or \"sender\" or start with \"__\"",
+                label,
+            )));
+        }
+
+        self.trigger.iter()
+            .try_for_each(|t| t.validate())
+            .map_err(|e| invalid(e.to_string()))
+    }
+}
+
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let valid_start = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_');
+
+    valid_start
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !["pipeline", "event_name", "sender"].contains(&name)
+        && !name.starts_with("__")
+}
+
+#[derive(Error, Debug)]
+pub enum LoadError {
+    #[error("unable to read {0}: {1}")]
+    ReadError(String, std::io::Error),
+
+    #[error("unable to parse {0}: {1}")]
+    ParseError(String, String),
+}
+
+/// Loads every event file under `dir` whose extension is one of `extensions`. Files that cannot
+/// be read or parsed are skipped with a warning.
+pub fn load_events(dir: &String, extensions: &[String]) -> Vec<Event> {
+    event_files(dir, extensions).into_iter()
+        .flat_map(|f| f.and_then(|f| load_event_file(f.as_str())).unwrap_or_else(|e| {
+            log::warn!("skipping event file: {}", e);
+            vec!()
+        }))
+        .collect()
+}
+
+/// Like [`load_events`], but fails on the first file that cannot be read or parsed.
+pub fn try_load_events(dir: &String, extensions: &[String]) -> std::result::Result<Vec<Event>, LoadError> {
+    event_files(dir, extensions).into_iter()
+        .map(|f| f.and_then(|f| load_event_file(f.as_str())))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map(|events| events.into_iter().flatten().collect())
 }
 
-pub fn load_events(dir: &String) -> Vec<Event> {
+fn event_files(dir: &String, extensions: &[String]) -> Vec<std::result::Result<String, LoadError>> {
     walkdir::WalkDir::new(dir)
         .into_iter()
-        .filter(|f| {
-            match f {
-                Ok(_) => {}
-                Err(e) => { log::warn!("unable to read file/directory: {}", e) }
-            };
-
-            f.is_ok()
+        .filter(|f| match f {
+            Ok(f) => {
+                let extension = f.path().extension().map(|e| e.to_string_lossy());
+                f.path().is_file()
+                    && !f.file_name().to_string_lossy().starts_with('.')
+                    && matches!(extension, Some(e) if extensions.iter().any(|x| x.eq_ignore_ascii_case(&e)))
+            }
+            Err(_) => true,
         })
-        .map(|f| f.unwrap())
-        .filter(|f| f.path().is_file())
-        .map(|f| f.path().to_str().unwrap().to_string())
-        .map(|f| {
-            log::trace!("reading {}", f);
-            // todo: handle error
-            std::fs::read_to_string(f).expect("unable to read file")
+        .map(|f| match f {
+            Ok(f) => Ok(f.path().to_string_lossy().to_string()),
+            Err(e) => {
+                let path = e.path().map(|p| p.to_string_lossy().to_string()).unwrap_or_else(|| dir.clone());
+                Err(LoadError::ReadError(path, e.into()))
+            }
         })
-        // todo: handle yaml error
-        .map(|f| serde_yaml::from_str(f.as_str()).expect("unable to parse config"))
         .collect()
 }
 
-pub struct Executor {}
+pub fn load_event_file(path: &str) -> std::result::Result<Vec<Event>, LoadError> {
+    log::trace!("reading {}", path);
+    let content = std::fs::read_to_string(path).map_err(|e| LoadError::ReadError(path.to_string(), e))?;
+
+    parse_event_str(content.as_str(), ConfigFormat::from_path(std::path::Path::new(path)))
+        .map_err(|e| LoadError::ParseError(path.to_string(), e.to_string()))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Files without a `.toml` or `.json` extension are read as YAML.
+    pub fn from_path(path: &std::path::Path) -> Self {
+        match path.extension().map(|e| e.to_string_lossy().to_lowercase()).as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct ParseError(String);
+
+/// A YAML or JSON document is either a single event or a list of events. A TOML document is
+/// either a single event or an `[[events]]` array.
+pub fn parse_event_str(content: &str, format: ConfigFormat) -> std::result::Result<Vec<Event>, ParseError> {
+    let parse_error = |e: &dyn std::fmt::Display| ParseError(e.to_string());
+
+    match format {
+        ConfigFormat::Yaml => load_events_from_str(content),
+        ConfigFormat::Json => {
+            let source: serde_yaml::Value = serde_json::from_str(content).map_err(|e| parse_error(&e))?;
+            from_document(source)
+        }
+        ConfigFormat::Toml => {
+            let source: serde_yaml::Value = toml::from_str(content).map_err(|e| parse_error(&e))?;
+            match source {
+                serde_yaml::Value::Mapping(m) if m.len() == 1 && m.contains_key(&"events".into()) => {
+                    from_document(m.get(&"events".into()).cloned().unwrap_or_default())
+                }
+                source => from_document(source),
+            }
+        }
+    }
+}
+
+/// Each `---` separated document is either a single event or a list of events.
+pub fn load_events_from_str(content: &str) -> std::result::Result<Vec<Event>, ParseError> {
+    use serde::Deserialize as _;
+
+    let mut events = vec!();
+    for doc in serde_yaml::Deserializer::from_str(content) {
+        let source = serde_yaml::Value::deserialize(doc).map_err(|e| ParseError(e.to_string()))?;
+        events.append(&mut from_document(source)?);
+    }
+    Ok(events)
+}
+
+fn from_document(source: serde_yaml::Value) -> std::result::Result<Vec<Event>, ParseError> {
+    match source {
+        serde_yaml::Value::Sequence(sources) => sources.into_iter().map(from_source).collect(),
+        source => Ok(vec!(from_source(source)?)),
+    }
+}
+
+fn from_source(source: serde_yaml::Value) -> std::result::Result<Event, ParseError> {
+    let event: Event = serde_yaml::from_value(source.clone()).map_err(|e| ParseError(e.to_string()))?;
+    Ok(Event { source: Some(source), ..event })
+}
+
+#[cfg(test)]
+fn parse_event(content: &str) -> Event {
+    let source: serde_yaml::Value = serde_yaml::from_str(content).expect("unable to parse config");
+    from_source(source).expect("unable to parse config")
+}
+
+#[cfg(feature = "compiled")]
+static COMPILED_EVENTS: include_dir::Dir = include_dir::include_dir!("$WEBHOOK_COMPILED_EVENTS_DIR");
+
+#[cfg(feature = "compiled")]
+pub fn load_compiled_events() -> Vec<Event> {
+    fn files<'a>(dir: &'a include_dir::Dir<'a>) -> Vec<&'a include_dir::File<'a>> {
+        dir.files().chain(dir.dirs().flat_map(files)).collect()
+    }
+
+    files(&COMPILED_EVENTS).into_iter()
+        .flat_map(|f| {
+            log::trace!("reading compiled event {}", f.path().display());
+            let content = f.contents_utf8().expect("compiled event is not valid utf-8");
+            parse_event_str(content, ConfigFormat::from_path(f.path())).expect("unable to parse compiled event")
+        })
+        .collect()
+}
+
+#[derive(Error, Debug)]
+pub enum ExecutorError {
+    #[error("pipeline {0} not found")]
+    PipelineNotFound(String),
+
+    #[error("pipeline {0} is already running")]
+    DuplicateName(String),
+
+    #[error("event name {0} is used by more than one event")]
+    DuplicateEventName(String),
+
+    #[error("invalid event {name}: {reason}")]
+    InvalidEvent { name: String, reason: String },
+}
+
+type PipelineDone = Shared<BoxFuture<'static, ()>>;
+
+#[derive(Clone)]
+struct PipelineHandle {
+    event: Event,
+    invoker: SingleGracefulSignalInvoker,
+    done: PipelineDone,
+}
+
+pub struct Executor {
+    pipelines: Arc<RwLock<HashMap<String, PipelineHandle>>>,
+    invoker: Arc<Mutex<CombinedGracefulSignalInvoker>>,
+    new_pipelines: mpsc::UnboundedSender<PipelineDone>,
+    new_pipelines_receiver: Option<mpsc::UnboundedReceiver<PipelineDone>>,
+    startup: Arc<Startup>,
+    metrics: Arc<PipelineMetrics>,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Executor {
     pub fn new() -> Self {
-        Executor {}
+        let (s, r) = mpsc::unbounded_channel();
+        Executor {
+            pipelines: Arc::new(RwLock::new(HashMap::new())),
+            invoker: Arc::new(Mutex::new(combine(vec!()))),
+            new_pipelines: s,
+            new_pipelines_receiver: Some(r),
+            startup: Arc::new(Startup::new()),
+            metrics: Arc::new(PipelineMetrics::new()),
+        }
+    }
+
+    pub fn startup(&self) -> Arc<Startup> {
+        self.startup.clone()
+    }
+
+    pub fn metrics(&self) -> Arc<PipelineMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Whether each pipeline task is still running.
+    pub fn liveness(&self) -> HashMap<String, bool> {
+        self.read_pipelines().iter()
+            .map(|(name, handle)| (name.clone(), handle.done.peek().is_none()))
+            .collect()
+    }
+
+    fn read_pipelines(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, PipelineHandle>> {
+        self.pipelines.read().expect("executor pipelines lock poisoned")
+    }
+
+    fn write_pipelines(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, PipelineHandle>> {
+        self.pipelines.write().expect("executor pipelines lock poisoned")
+    }
+
+    pub fn validate_no_duplicates(events: &[Event]) -> std::result::Result<(), ExecutorError> {
+        let mut names = std::collections::HashSet::new();
+        match events.iter().find(|e| !names.insert(e.name.as_str())) {
+            Some(e) => Err(ExecutorError::DuplicateEventName(e.name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn start(
+        &mut self,
+        mut events: Vec<Event>,
+    ) -> std::result::Result<(impl std::future::Future, Box<dyn GracefulSignalInvoker>), ExecutorError> {
+        Self::validate_no_duplicates(&events)?;
+        events.iter().try_for_each(|e| e.validate())?;
+        let receiver = self.new_pipelines_receiver.take().expect("executor is already started");
+
+        events.drain(0..)
+            .filter(|e| {
+                if !e.is_enabled() {
+                    log::info!("event {} is disabled, skipping", e.name);
+                }
+                e.is_enabled()
+            })
+            .for_each(|e| self.spawn_pipeline(e));
+        self.startup.executor_started();
+
+        let (shutdown_invoker, shutdown) = new_graceful_signal();
+        Ok((
+            Self::wait_pipelines(receiver, shutdown),
+            Box::new(combine(vec!(Box::new(self.invoker.clone()), Box::new(shutdown_invoker)))),
+        ))
+    }
+
+    /// Runs until the executor is shut down and every pipeline has finished. Pipelines can be
+    /// added at any time before that, even when none is running.
+    async fn wait_pipelines(mut receiver: mpsc::UnboundedReceiver<PipelineDone>, shutdown: GracefulSignal) {
+        let mut pipelines = FuturesUnordered::new();
+        let mut shutdown = shutdown.into_future();
+        let mut stopping = false;
+
+        loop {
+            while let Ok(done) = receiver.try_recv() {
+                pipelines.push(done);
+            }
+
+            if stopping && pipelines.is_empty() {
+                break;
+            }
+
+            tokio::select! {
+                _ = &mut shutdown, if !stopping => { stopping = true; },
+                Some(done) = receiver.recv() => { pipelines.push(done); },
+                Some(_) = pipelines.next() => {},
+                else => break,
+            }
+        }
+    }
+
+    fn spawn_pipeline(&self, event: Event) {
+        let name = event.name.clone();
+        self.startup.register(name.as_str());
+        let (p, i) = Pipeline::new(event.clone(), self.startup.clone(), self.metrics.clone()).start();
+        let task = tokio::spawn(p);
+        let done = async move {
+            if let Err(e) = task.await {
+                log::error!("error joining pipeline task: {}", e);
+            }
+        }.boxed().shared();
+
+        self.invoker.lock().expect("executor invoker lock poisoned").push(Box::new(i.clone()));
+        if self.new_pipelines.send(done.clone()).is_err() {
+            log::error!("pipeline {} is started after the executor stopped", name);
+        }
+        self.write_pipelines().insert(name, PipelineHandle { event, invoker: i, done });
+    }
+
+    pub fn add_pipeline(&self, event: Event) -> std::result::Result<(), ExecutorError> {
+        if self.read_pipelines().contains_key(&event.name) {
+            return Err(ExecutorError::DuplicateName(event.name));
+        }
+        event.validate()?;
+        if !event.is_enabled() {
+            log::info!("event {} is disabled, skipping", event.name);
+            return Ok(());
+        }
+
+        log::info!("adding pipeline {}", event.name);
+        self.spawn_pipeline(event);
+        Ok(())
+    }
+
+    /// Restarts the pipelines whose config changed, stops the ones missing from `events` and starts
+    /// the new ones, unless any of `events` is invalid. Pipelines with an identical config keep running.
+    pub async fn reload(&self, events: Vec<Event>) -> std::result::Result<(), Vec<ExecutorError>> {
+        let errors = events.iter()
+            .filter_map(|e| e.validate().err())
+            .chain(Self::validate_no_duplicates(&events).err())
+            .collect::<Vec<_>>();
+
+        if !errors.is_empty() {
+            errors.iter().for_each(|e| log::error!("{}", e));
+            log::error!("hot reload aborted due to config errors");
+            return Err(errors);
+        }
+
+        let current = self.read_pipelines().values().map(|h| h.event.clone()).collect::<Vec<_>>();
+        let diff = diff_events(&current, &events);
+        log::info!("reload adds {} and removes {} pipelines", diff.added.len(), diff.removed.len());
+        self.apply_diff(diff).await;
+        Ok(())
+    }
+
+    pub async fn stop_pipeline(&self, name: &str) -> std::result::Result<(), ExecutorError> {
+        let handle = self.read_pipelines().get(name)
+            .cloned()
+            .ok_or_else(|| ExecutorError::PipelineNotFound(name.to_string()))?;
+
+        log::info!("stopping pipeline {}", name);
+        handle.invoker.call();
+        handle.done.await;
+
+        self.write_pipelines().remove(name);
+        self.startup.unregister(name);
+        Ok(())
     }
 
-    pub fn start(&self, mut events: Vec<Event>) -> (impl std::future::Future, Box<dyn GracefulSignalInvoker>) {
-        let (promises, invokers): (Vec<_>, Vec<_>) = events
-            .drain(0..)
-            .map(|e| Pipeline::new(e))
-            .map(|p| p.start())
-            .unzip();
+    /// Stops the removed pipelines, waits for them to finish, then starts the added ones. The added
+    /// events must already be validated.
+    async fn apply_diff(&self, diff: DiffResult) {
+        let removed = {
+            let mut pipelines = self.write_pipelines();
+            diff.removed.iter()
+                .filter_map(|name| pipelines.remove(name).map(|h| (name, h)))
+                .collect::<Vec<_>>()
+        };
+        for (name, handle) in removed {
+            log::info!("stopping pipeline {} on reload", name);
+            handle.invoker.call();
+            handle.done.await;
+            self.startup.unregister(name.as_str());
+        }
+
+        for event in diff.added {
+            if !event.is_enabled() {
+                log::info!("event {} is disabled, skipping", event.name);
+                continue;
+            }
+            log::info!("starting pipeline {} on reload", event.name);
+            self.spawn_pipeline(event);
+        }
+
+        *self.invoker.lock().expect("executor invoker lock poisoned") = combine(
+            self.read_pipelines().values()
+                .map(|h| Box::new(h.invoker.clone()) as Box<dyn GracefulSignalInvoker>)
+                .collect(),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct DiffResult {
+    pub added: Vec<Event>,
+    pub removed: Vec<String>,
+}
+
+/// Events whose config changed are both removed and added, so they restart with the new config.
+pub fn diff_events(old: &[Event], new: &[Event]) -> DiffResult {
+    let unchanged = |e: &Event, others: &[Event]| others.iter()
+        .any(|o| o.name == e.name && o.source.is_some() && o.source == e.source);
 
-        (
-            futures::future::join_all(promises),
-            Box::new(combine(invokers)),
-        )
+    DiffResult {
+        added: new.iter().filter(|e| !unchanged(e, old)).cloned().collect(),
+        removed: old.iter().filter(|e| !unchanged(e, new)).map(|e| e.name.clone()).collect(),
     }
 }
 
 pub struct Pipeline {
     event: Event,
+    startup: Arc<Startup>,
+    metrics: Arc<PipelineMetrics>,
 }
 
 impl Pipeline {
-    pub fn new(event: Event) -> Self {
+    pub fn new(event: Event, startup: Arc<Startup>, metrics: Arc<PipelineMetrics>) -> Self {
         Pipeline {
             event,
+            startup,
+            metrics,
         }
     }
 
-    pub fn start(&self) -> (impl std::future::Future, Box<dyn GracefulSignalInvoker>) {
+    pub fn start(&self) -> (impl std::future::Future<Output=()>, SingleGracefulSignalInvoker) {
         log::info!("starting pipeline for {}", self.event.name);
         let (i, s) = new_graceful_signal();
 
-        (Self::start_loop(self.event.clone(), s), Box::new(i))
+        let name = self.event.name.clone();
+        (utils::logger::with_pipeline(name, Self::start_loop(
+            self.event.clone(),
+            self.startup.clone(),
+            self.metrics.clone(),
+            s,
+        )), i)
     }
 
-    async fn start_loop(event: Event, graceful_signal: GracefulSignal) {
-        let graceful_stop = graceful_signal.called();
-        tokio::pin!(graceful_stop);
+    async fn start_loop(
+        event: Event,
+        startup: Arc<Startup>,
+        metrics: Arc<PipelineMetrics>,
+        graceful_signal: GracefulSignal,
+    ) {
+        let mut graceful_stop = graceful_signal.clone().into_future();
 
-        let (queue_sender, queue_receiver) = queue::new_queue(Some(0));
+        if let Err(e) = metrics.register(event.name.as_str(), event.labels.clone().unwrap_or_default()) {
+            log::error!("pipeline {} stopped, unable to register metrics: {}", event.name, e);
+            return;
+        }
+        let (queue_sender, queue_receiver) = queue::new_queue(event.queue_capacity, Some(metrics.queue_depth(&event.name)));
 
-        let triggers = event.trigger.iter()
-            .map(|t| trigger::new_source_event_receiver(t).expect("unable to initialize event receiver"))
+        let receivers = futures::future::join_all(event.trigger.iter().map(trigger::new_source_event_receiver)).await;
+        let receivers = match receivers.into_iter().collect::<std::result::Result<Vec<_>, _>>() {
+            Ok(receivers) => receivers,
+            Err(e) => {
+                log::error!("pipeline {} stopped, unable to initialize event receiver: {}", event.name, e);
+                return;
+            }
+        };
+        let triggers = receivers.into_iter()
             .map(|r| (r, queue_sender.clone()))
             .map(|(r, s)| {
-                tokio::spawn(async move {
+                let name = event.name.clone();
+                let metrics = metrics.clone();
+                utils::logger::spawn(async move {
+                    let mut backoff = pull_backoff();
                     loop {
-                        let event = r.get_one().await.expect("unable to retrieve event");
+                        let event = match r.get_one().await {
+                            Ok(event) => {
+                                backoff = pull_backoff();
+                                event
+                            }
+                            Err(e) => {
+                                log::warn!("unable to retrieve event, retrying: {}", e);
+                                backoff.wait().await;
+                                continue;
+                            }
+                        };
+                        metrics.event_received(name.as_str());
                         let s = s.clone();
-                        let res = tokio::task::spawn(async move {
+                        let res = utils::logger::spawn(async move {
                             s.send(event)
                         }).await;
 
-                        if let Err(e) = res {
-                            log::error!("event sender thread join error: {}", e);
+                        match res {
+                            Ok(Ok(())) => {}
+                            Ok(Err(e)) => {
+                                log::warn!("stopping trigger: {}", e);
+                                break;
+                            }
+                            Err(e) => log::error!("event sender thread join error: {}", e),
                         }
                     }
                 })
@@ -115,14 +610,27 @@ impl Pipeline {
             .map(|t| sender::new_sender(t).expect("unable to create sender"))
             .collect::<Vec<_>>();
 
+        let dead_letter_senders = match &event.timeout_behavior {
+            Some(TimeoutBehavior::DeadLetter { target }) => target.iter()
+                // todo: handle error
+                .map(|t| sender::new_sender(t).expect("unable to create dead letter sender"))
+                .collect::<Vec<_>>(),
+            _ => vec!(),
+        };
+
         let ops = match &event.process {
             None => { vec!() }
             Some(ops) => { ops.clone() }
         };
 
+        // Receivers connect when they are built, while `get_one` blocks until a message arrives,
+        // so the pipeline is ready here rather than after its first pull: waiting for a message
+        // would keep an idle pipeline, such as an http trigger nobody calls yet, unready forever.
+        startup.pipeline_started(event.name.as_str());
+
         loop {
             let queue_receiver = queue_receiver.clone();
-            let new_message = tokio::task::spawn(async move {
+            let new_message = utils::logger::spawn(async move {
                 queue_receiver.recv()
             });
 
@@ -130,66 +638,1284 @@ impl Pipeline {
             tokio::select! {
                 _ = &mut graceful_stop => { log::debug!("pipeline {} receive stop signal", event.name); break},
                 msg = new_message => {
-                    let msg = msg.unwrap();
+                    let msg = match msg {
+                        Ok(Ok(msg)) => msg,
+                        Ok(Err(e)) => {
+                            log::warn!("pipeline {} stopping: {}", event.name, e);
+                            break;
+                        }
+                        Err(e) => {
+                            log::error!("pipeline {} queue receiver join error: {}", event.name, e);
+                            continue;
+                        }
+                    };
                     log::debug!("new message {:?}", String::from_utf8(msg.bytes().clone()));
 
-                    let res = dispatch_webhook(&event, &senders, &msg, &ops).await;
-                    if let Err(e) = res {
-                        log::error!("error dispatching webhook: {}", e)
-                    }
-                    msg.done().await;
+                    handle_message(&event, &senders, &dead_letter_senders, msg.as_ref(), &ops, &metrics, &graceful_signal).await;
                 },
             }
             ;
             log::trace!("pipeline {} done waiting for new message or stop signal", event.name);
         }
 
+        for sender in senders.iter().chain(dead_letter_senders.iter()) {
+            if let Err(e) = sender.flush().await {
+                log::error!("error flushing sender: {}", e);
+            }
+        }
+
         for trigger in triggers {
+            trigger.abort();
             let res = trigger.await;
             if let Err(e) = res {
-                log::error!("error joining trigger thread: {}", e);
+                if !e.is_cancelled() {
+                    log::error!("error joining trigger thread: {}", e);
+                }
             }
         }
         log::info!("pipeline {} stopped", event.name);
     }
 }
 
+/// Waits between failed pulls of a trigger, which keeps retrying for as long as the pipeline runs.
+fn pull_backoff() -> utils::backoff::Backoff {
+    utils::backoff::Backoff::new(
+        std::time::Duration::from_millis(100),
+        2.0,
+        std::time::Duration::from_secs(30),
+        usize::MAX,
+    )
+}
+
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 enum Error {
     #[error("error during process execution: {0}")]
-    ExecutionError(String)
+    ExecutionError(String),
+
+    #[error("error sending message: {0}")]
+    SendError(#[from] sender::Error),
+
+    #[error("error sending message to {} targets: {}", .0.len(), .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))]
+    MultipleSendError(Vec<sender::Error>),
+
+    #[error("interrupted by shutdown")]
+    Interrupted,
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
 impl From<process::Error> for Error {
     fn from(e: process::Error) -> Self {
-        Error::ExecutionError(format!("{}", e))
+        match e {
+            process::Error::Interrupted => Error::Interrupted,
+            e => Error::ExecutionError(format!("{}", e)),
+        }
+    }
+}
+
+async fn settle(event: &Event, msg: &dyn SourceEvent, res: Result<()>) {
+    match res {
+        // The message was not fully processed, return it to the broker instead of losing it.
+        Err(Error::Interrupted) => {
+            log::info!("pipeline {} interrupted, returning message for redelivery", event.name);
+            msg.nack().await;
+        }
+        Err(e) => {
+            log::error!("error dispatching webhook: {}", e);
+            msg.done().await;
+        }
+        Ok(()) => msg.done().await,
+    }
+}
+
+async fn handle_message(
+    event: &Event,
+    senders: &[Box<dyn sender::Sender>],
+    dead_letter_senders: &[Box<dyn sender::Sender>],
+    msg: &dyn SourceEvent,
+    ops: &[operation::Op],
+    metrics: &PipelineMetrics,
+    graceful: &GracefulSignal,
+) {
+    // Every attempt at the same message logs under the same id.
+    let correlation_id = uuid::Uuid::new_v4().to_string();
+    utils::logger::with_correlation_id(
+        correlation_id,
+        process_message(event, senders, dead_letter_senders, msg, ops, metrics, graceful),
+    ).await
+}
+
+async fn process_message(
+    event: &Event,
+    senders: &[Box<dyn sender::Sender>],
+    dead_letter_senders: &[Box<dyn sender::Sender>],
+    msg: &dyn SourceEvent,
+    ops: &[operation::Op],
+    metrics: &PipelineMetrics,
+    graceful: &GracefulSignal,
+) {
+    let timeout = match event.timeout_ms {
+        None => {
+            let res = dispatch_webhook(event, senders, msg, ops, metrics, graceful, &Delivered::default()).await;
+            settle(event, msg, res).await;
+            return;
+        }
+        Some(ms) => tokio::time::Duration::from_millis(ms),
+    };
+
+    let behavior = event.timeout_behavior.clone().unwrap_or(TimeoutBehavior::Drop);
+    let attempts = match behavior {
+        TimeoutBehavior::Retry { max_attempts } => max_attempts.max(1),
+        _ => 1,
+    };
+
+    let start = std::time::Instant::now();
+    let delivered = Delivered::default();
+    for attempt in 1..=attempts {
+        match tokio::time::timeout(timeout, dispatch_webhook(event, senders, msg, ops, metrics, graceful, &delivered)).await {
+            Ok(res) => {
+                settle(event, msg, res).await;
+                return;
+            }
+            Err(_) => {
+                log::warn!("pipeline {} timed out dispatching message (attempt {}/{})", event.name, attempt, attempts);
+            }
+        }
+    }
+    metrics.event_failed(event.name.as_str(), start.elapsed());
+
+    match behavior {
+        TimeoutBehavior::Drop => {
+            log::warn!("pipeline {} dropping timed out message", event.name);
+            msg.done().await;
+        }
+        TimeoutBehavior::Retry { .. } => {
+            log::warn!("pipeline {} returning timed out message for redelivery", event.name);
+            msg.nack().await;
+        }
+        TimeoutBehavior::DeadLetter { .. } => {
+            log::warn!("pipeline {} routing timed out message to dead letter", event.name);
+            let payload = sender::Payload::new(msg.bytes().clone());
+            let mut state = process::State::new();
+            for s in dead_letter_senders.iter() {
+                if let Err(e) = s.send(payload.clone(), &mut state).await {
+                    log::error!("error sending message to dead letter: {}", e);
+                }
+            }
+            msg.done().await;
+        }
     }
 }
 
 async fn dispatch_webhook(
-    event: &Event, senders: &Vec<Box<dyn sender::Sender>>,
-    msg: &Box<dyn SourceEvent>,
-    ops: &Vec<operation::Op>,
+    event: &Event, senders: &[Box<dyn sender::Sender>],
+    msg: &dyn SourceEvent,
+    ops: &[operation::Op],
+    metrics: &PipelineMetrics,
+    graceful: &GracefulSignal,
+    delivered: &Delivered,
 ) -> Result<()> {
-    let (payload, state) = ops.iter()
-        .fold(Ok((sender::Payload { content: msg.bytes().clone() }, process::State::new())), |r: Result<_>, op| {
-            let (payload, state) = r?;
-            let (payload, new_state) = op.execute(payload, state)?;
-            log::trace!("pipeline \"{}\" new state: {:?}", event.name, new_state);
-            Ok((payload, new_state))
-        })?;
-
-    let ps = senders.iter()
-        .map(|s| {
-            s.send(payload.clone(), &state)
-        });
-
-    let ps = futures::future::join_all(ps).await;
-    // todo: handle error
-    ps.iter().for_each(|p| {
-        p.as_ref().expect("failed to send message");
-    });
-    Ok(())
-}
\ No newline at end of file
+    let start = std::time::Instant::now();
+    let res = async {
+        let payload = sender::Payload::new(msg.bytes().clone());
+        let ctx = operation::Context {
+            pipeline: event.name.as_str(),
+            max_state_keys: event.max_state_keys,
+            graceful: graceful.clone(),
+        };
+        let results = operation::execute_ops(ops, payload, process::State::new(), &ctx).await?;
+
+        let mut errors = vec!();
+        for (part, (payload, mut state)) in results.into_iter().enumerate() {
+            errors.extend(send_all(event, senders, part, &payload, &mut state, metrics, delivered).await);
+        }
+
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(Error::SendError(errors.remove(0))),
+            _ => Err(Error::MultipleSendError(errors)),
+        }
+    }.await;
+
+    match &res {
+        Ok(_) => metrics.event_processed(event.name.as_str(), start.elapsed()),
+        Err(Error::Interrupted) => {}
+        Err(_) => metrics.event_failed(event.name.as_str(), start.elapsed()),
+    }
+    res
+}
+
+/// Sends to every sender and returns the errors of the failed ones. Senders run concurrently, except
+/// that the ones after a sender capturing its response wait for it to see the captured state.
+async fn send_all(
+    event: &Event,
+    senders: &[Box<dyn sender::Sender>],
+    part: usize,
+    payload: &sender::Payload,
+    state: &mut process::State,
+    metrics: &PipelineMetrics,
+    delivered: &Delivered,
+) -> Vec<sender::Error> {
+    let mut errors = vec!();
+    let mut start = 0;
+    while start < senders.len() {
+        let end = senders[start..].iter().position(|s| s.captures_response()).map_or(senders.len(), |i| start + i + 1);
+        let last = end - 1;
+
+        let snapshot = state.clone();
+        let concurrent = (start..last)
+            .filter(|&idx| delivered.get(part, idx).is_none())
+            .map(|idx| {
+                let (payload, mut state) = (payload.clone(), snapshot.clone());
+                async move {
+                    let res = send_one(event, idx, senders[idx].as_ref(), payload, &mut state, metrics).await;
+                    if res.is_ok() {
+                        delivered.insert(part, idx, None);
+                    }
+                    res
+                }
+            });
+        let sequential = async {
+            if let Some(captured) = delivered.get(part, last) {
+                if let Some(captured) = captured {
+                    *state = captured;
+                }
+                return Ok(());
+            }
+
+            let res = send_one(event, last, senders[last].as_ref(), payload.clone(), state, metrics).await;
+            if res.is_ok() {
+                delivered.insert(part, last, senders[last].captures_response().then(|| state.clone()));
+            }
+            res
+        };
+        let (results, last_result) = futures::future::join(futures::future::join_all(concurrent), sequential).await;
+
+        errors.extend(results.into_iter().chain(std::iter::once(last_result)).filter_map(|r| r.err()));
+        start = end;
+    }
+    errors
+}
+
+/// Targets that already got a message, so that a retry after a timeout only sends to the others.
+#[derive(Default)]
+struct Delivered {
+    // Keyed by the part produced by the ops and the target index, with the state captured by the
+    // target so that a retry can restore it without sending again.
+    sent: Mutex<HashMap<(usize, usize), Option<process::State>>>,
+}
+
+impl Delivered {
+    fn get(&self, part: usize, target: usize) -> Option<Option<process::State>> {
+        self.lock().get(&(part, target)).cloned()
+    }
+
+    fn insert(&self, part: usize, target: usize, captured: Option<process::State>) {
+        self.lock().insert((part, target), captured);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(usize, usize), Option<process::State>>> {
+        self.sent.lock().expect("delivered lock poisoned")
+    }
+}
+
+async fn send_one(
+    event: &Event,
+    idx: usize,
+    sender: &dyn sender::Sender,
+    payload: sender::Payload,
+    state: &mut process::State,
+    metrics: &PipelineMetrics,
+) -> std::result::Result<(), sender::Error> {
+    let label = format!("{}[{}]", event.target.get(idx).map_or("unknown", |t| t.kind()), idx);
+    let start = std::time::Instant::now();
+    let res = sender.send(payload, state).await;
+    metrics.sender_finished(event.name.as_str(), label.as_str(), start.elapsed(), res.is_ok());
+    res
+}
+
+#[cfg(test)]
+mod load_tests {
+    use super::*;
+
+    const EVENT: &str = "name: {}\ntrigger: [{type: mock}]\ntarget: []\n";
+
+    fn event(name: &str) -> String {
+        EVENT.replace("{}", name)
+    }
+
+    #[test]
+    fn test_load_events_filters_and_skips_invalid_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        std::fs::write(dir.path().join("a.yaml"), event("a")).unwrap();
+        std::fs::write(dir.path().join("nested/b.YML"), event("b")).unwrap();
+        std::fs::write(dir.path().join("multi.yaml"), format!("{}---\n{}", event("c"), event("d"))).unwrap();
+        std::fs::write(dir.path().join("README.md"), "# events").unwrap();
+        std::fs::write(dir.path().join(".hidden.yaml"), event("hidden")).unwrap();
+        std::fs::write(dir.path().join("broken.yaml"), "name: [").unwrap();
+        std::fs::write(dir.path().join("binary.yml"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let events = load_events(&dir.path().to_string_lossy().to_string(), &["yaml".into(), "yml".into()]);
+        let mut names = events.into_iter().map(|e| e.name).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!("a", "b", "c", "d"));
+    }
+
+    #[test]
+    fn test_try_load_events_fails_on_invalid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.yaml"), event("a")).unwrap();
+        std::fs::write(dir.path().join("README.md"), "# events").unwrap();
+        let extensions = ["yaml".to_string()];
+
+        let events = try_load_events(&dir.path().to_string_lossy().to_string(), &extensions).unwrap();
+        assert_eq!(events.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!("a"));
+
+        std::fs::write(dir.path().join("broken.yaml"), "name: [").unwrap();
+        let res = try_load_events(&dir.path().to_string_lossy().to_string(), &extensions);
+        assert!(matches!(res, Err(LoadError::ParseError(p, _)) if p.ends_with("broken.yaml")));
+
+        let res = try_load_events(&dir.path().join("missing").to_string_lossy().to_string(), &extensions);
+        assert!(matches!(res, Err(LoadError::ReadError(_, _))));
+    }
+
+    #[test]
+    fn test_load_events_from_str_single() {
+        let events = load_events_from_str(event("a").as_str()).unwrap();
+        assert_eq!(events.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!("a"));
+    }
+
+    #[test]
+    fn test_load_events_from_str_list() {
+        let content = r#"
+- name: a
+  trigger: [{type: mock}]
+  target: []
+- name: b
+  trigger: [{type: mock}]
+  target: []
+"#;
+        let events = load_events_from_str(content).unwrap();
+        assert_eq!(events.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!("a", "b"));
+        assert!(events.iter().all(|e| e.source.is_some()));
+    }
+
+    #[test]
+    fn test_load_events_from_str_multi_document() {
+        let content = format!("---\n{}---\n- {}", event("a"), event("b").replace('\n', "\n  "));
+        let events = load_events_from_str(content.as_str()).unwrap();
+        assert_eq!(events.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!("a", "b"));
+    }
+
+    #[test]
+    fn test_load_events_from_str_err() {
+        assert!(load_events_from_str("- name: a\n- 1").is_err());
+        assert!(load_events_from_str(format!("{}---\nname: [", event("a")).as_str()).is_err());
+    }
+
+    #[test]
+    fn test_parse_event_str_json() {
+        let content = r#"[
+            {"name": "a", "trigger": [{"type": "mock"}], "target": []},
+            {"name": "b", "trigger": [{"type": "mock"}], "target": [], "tags": ["eu"]}
+        ]"#;
+        let events = parse_event_str(content, ConfigFormat::Json).unwrap();
+        assert_eq!(events.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!("a", "b"));
+        assert!(events[1].has_any_tag(&["eu".into()]));
+    }
+
+    #[test]
+    fn test_parse_event_str_err() {
+        assert!(parse_event_str("{\"name\": \"a\"", ConfigFormat::Json).is_err());
+        assert!(parse_event_str("name = ", ConfigFormat::Toml).is_err());
+        assert!(parse_event_str("name = \"a\"", ConfigFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn test_load_toml_event_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("single.toml"), r#"
+name = "a"
+timeout_ms = 100
+target = []
+
+[[trigger]]
+type = "mock"
+"#).unwrap();
+        std::fs::write(dir.path().join("list.toml"), r#"
+[[events]]
+name = "b"
+target = []
+trigger = [{ type = "mock" }]
+
+[[events]]
+name = "c"
+target = []
+trigger = [{ type = "mock" }]
+
+[[events.process]]
+set_env = { target = "a", value = 1 }
+"#).unwrap();
+        std::fs::write(dir.path().join("d.yaml"), event("d")).unwrap();
+
+        let events = load_events(&dir.path().to_string_lossy().to_string(), &["toml".into(), "yaml".into()]);
+        let mut events = events.into_iter().map(|e| (e.name.clone(), e)).collect::<Vec<_>>();
+        events.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(events.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>(), vec!("a", "b", "c", "d"));
+        assert_eq!(events[0].1.timeout_ms, Some(100));
+        assert_eq!(events[2].1.process.as_ref().map(|p| p.len()), Some(1));
+    }
+
+    #[test]
+    fn test_load_event_file_err() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.yaml");
+        std::fs::write(&path, "name: a\ntrigger: 1").unwrap();
+
+        let res = load_event_file(path.to_str().unwrap());
+        assert!(matches!(res, Err(LoadError::ParseError(p, _)) if p.ends_with("broken.yaml")));
+
+        let res = load_event_file(dir.path().join("missing.yaml").to_str().unwrap());
+        assert!(matches!(res, Err(LoadError::ReadError(p, _)) if p.ends_with("missing.yaml")));
+    }
+}
+
+#[cfg(all(test, feature = "compiled"))]
+mod compiled_tests {
+    use super::*;
+
+    #[test]
+    fn test_load_compiled_events() {
+        let names = |events: Vec<Event>| {
+            let mut names = events.into_iter().map(|e| e.name).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let compiled = load_compiled_events();
+        let from_dir = load_events(&env!("WEBHOOK_COMPILED_EVENTS_DIR").to_string(), &["yaml".into(), "yml".into()]);
+
+        assert!(!compiled.is_empty());
+        assert_eq!(names(compiled), names(from_dir));
+    }
+}
+
+#[cfg(test)]
+mod executor_tests {
+    use super::*;
+
+    fn mock_event(name: &str) -> Event {
+        serde_yaml::from_str(format!(r#"
+name: {}
+trigger:
+  - type: mock
+target: []
+"#, name).as_str()).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_stop_pipeline_ok() {
+        let mut executor = Executor::new();
+        let (p, _) = executor.start(vec!(mock_event("a"))).unwrap();
+
+        let res = executor.stop_pipeline("a").await;
+        assert!(res.is_ok());
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_start_without_pipelines_waits_for_shutdown() {
+        let mut executor = Executor::new();
+        let (p, g) = executor.start(vec!()).unwrap();
+        tokio::pin!(p);
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_millis(100), &mut p).await;
+        assert!(res.is_err());
+
+        executor.add_pipeline(mock_event("a")).unwrap();
+        executor.stop_pipeline("a").await.unwrap();
+        let res = tokio::time::timeout(tokio::time::Duration::from_millis(100), &mut p).await;
+        assert!(res.is_err());
+
+        g.call();
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_stop_pipeline_then_add_again() {
+        let mut executor = Executor::new();
+        let (_, g) = executor.start(vec!(mock_event("a"), mock_event("b"))).unwrap();
+
+        executor.stop_pipeline("a").await.unwrap();
+        assert_eq!(executor.liveness().into_keys().collect::<Vec<_>>(), vec!("b"));
+
+        executor.add_pipeline(mock_event("a")).unwrap();
+        let mut names = executor.liveness().into_keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!("a", "b"));
+
+        g.call();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_start_skips_disabled_events() {
+        let mut executor = Executor::new();
+        let disabled: Event = serde_yaml::from_str("name: b\ntrigger: [{type: mock}]\ntarget: []\nenabled: false").unwrap();
+        let (p, _) = executor.start(vec!(mock_event("a"), disabled)).unwrap();
+
+        assert_eq!(executor.liveness().into_keys().collect::<Vec<_>>(), vec!("a"));
+
+        let _ = executor.stop_pipeline("a").await;
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_validate_no_duplicates() {
+        let res = Executor::validate_no_duplicates(&[mock_event("a"), mock_event("b")]);
+        assert!(res.is_ok());
+
+        let res = Executor::validate_no_duplicates(&[mock_event("a"), mock_event("b"), mock_event("a")]);
+        assert!(matches!(res, Err(ExecutorError::DuplicateEventName(name)) if name == "a"));
+    }
+
+    #[test]
+    fn test_start_duplicate_event_name() {
+        let mut executor = Executor::new();
+        let res = executor.start(vec!(mock_event("a"), mock_event("a")));
+        assert!(matches!(res, Err(ExecutorError::DuplicateEventName(name)) if name == "a"));
+    }
+
+    #[test]
+    fn test_start_invalid_event() {
+        let mut executor = Executor::new();
+        let event: Event = serde_yaml::from_str("name: a\ntrigger: [{type: mock}]\ntarget: []\nlabels: {team-name: payments}").unwrap();
+        let res = executor.start(vec!(event));
+        assert!(matches!(res, Err(ExecutorError::InvalidEvent { name, .. }) if name == "a"));
+    }
+
+    #[test]
+    fn test_has_any_tag() {
+        let event: Event = serde_yaml::from_str("name: a\ntrigger: []\ntarget: []\ntags: [billing, eu]").unwrap();
+
+        assert!(event.has_any_tag(&["us".into(), "eu".into()]));
+        assert!(!event.has_any_tag(&["us".into()]));
+        assert!(!mock_event("b").has_any_tag(&["eu".into()]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_liveness() {
+        let mut executor = Executor::new();
+        let (_p, _) = executor.start(vec!(mock_event("a"), mock_event("b"))).unwrap();
+        assert_eq!(executor.liveness(), HashMap::from([("a".to_string(), true), ("b".to_string(), true)]));
+
+        let res = executor.stop_pipeline("a").await;
+        assert!(res.is_ok());
+        // Stopped pipelines are no longer tracked, only crashed ones report as not alive.
+        assert_eq!(executor.liveness(), HashMap::from([("b".to_string(), true)]));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_add_pipeline_ok() {
+        let mut executor = Executor::new();
+        let (p, _) = executor.start(vec!(mock_event("a"))).unwrap();
+
+        let event = serde_yaml::from_str(r#"
+name: b
+trigger:
+  - type: mock
+    config:
+      messages:
+        - hello
+target:
+  - mock:
+      name: executor_tests_add_pipeline
+"#).unwrap();
+
+        let res = executor.add_pipeline(event);
+        assert!(res.is_ok());
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+            while sender::mock::sent("executor_tests_add_pipeline").is_empty() {
+                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            }
+        }).await;
+        assert!(res.is_ok());
+        assert_eq!(sender::mock::sent("executor_tests_add_pipeline"), vec!("hello".as_bytes().to_vec()));
+
+        let _ = executor.stop_pipeline("a").await;
+        let _ = executor.stop_pipeline("b").await;
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_diff_events() {
+        let old = vec!(
+            parse_event("name: a\ntrigger: [{type: mock}]\ntarget: []"),
+            parse_event("name: b\ntrigger: [{type: mock}]\ntarget: []"),
+            parse_event("name: c\ntrigger: [{type: mock}]\ntarget: []"),
+        );
+        let new = vec!(
+            parse_event("name: a\ntrigger: [{type: mock}]\ntarget: []"),
+            parse_event("name: b\ntrigger: [{type: mock}]\ntarget: []\ntimeout_ms: 10"),
+            parse_event("name: d\ntrigger: [{type: mock}]\ntarget: []"),
+        );
+
+        let diff = diff_events(&old, &new);
+        assert_eq!(diff.added.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!("b", "d"));
+        assert_eq!(diff.removed, vec!("b", "c"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_apply_diff() {
+        let mut executor = Executor::new();
+        let old = vec!(mock_event("a"), mock_event("b"));
+        let (p, _) = executor.start(old.clone()).unwrap();
+
+        let new = vec!(mock_event("a"), mock_event("c"));
+        let mut diff = diff_events(&old, &new);
+        assert_eq!(diff.removed, vec!("a", "b"));
+
+        // Events built without a source always restart; keep "a" running instead.
+        diff.added.retain(|e| e.name != "a");
+        diff.removed.retain(|n| n != "a");
+        executor.apply_diff(diff).await;
+
+        let mut names = executor.liveness().into_keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!("a", "c"));
+
+        executor.apply_diff(DiffResult { added: vec!(), removed: vec!("a".into(), "c".into()) }).await;
+        assert!(executor.liveness().is_empty());
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_reload_keeps_unchanged_pipelines() {
+        let event = |name: &str, extra: &str| parse_event(format!(r#"
+name: {}
+trigger:
+  - type: mock
+    config:
+      messages: [hello]
+target:
+  - mock:
+      name: executor_tests_reload_unchanged_{}
+{}"#, name, name, extra).as_str());
+        let sent = |name: &str| sender::mock::sent(format!("executor_tests_reload_unchanged_{}", name).as_str()).len();
+        let wait_sent = |name: &'static str, count: usize| async move {
+            tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+                while sent(name) < count {
+                    tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                }
+            }).await
+        };
+
+        let mut executor = Executor::new();
+        let (p, g) = executor.start(vec!(event("a", ""), event("b", ""), event("c", ""))).unwrap();
+        assert!(wait_sent("a", 1).await.is_ok());
+        assert!(wait_sent("b", 1).await.is_ok());
+
+        let res = executor.reload(vec!(event("a", ""), event("b", "timeout_ms: 10"), event("d", ""))).await;
+        assert!(res.is_ok());
+
+        let mut names = executor.liveness().into_keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!("a", "b", "d"));
+
+        // The restarted pipeline gets a new receiver that replays its message, the unchanged one does not.
+        assert!(wait_sent("b", 2).await.is_ok());
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert_eq!(sent("a"), 1);
+
+        g.call();
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_trigger_pull_error_retries() {
+        let event: Event = serde_yaml::from_str(r#"
+name: flaky
+trigger:
+  - type: mock
+    config:
+      failures: 2
+      messages: [hello]
+target:
+  - mock:
+      name: executor_tests_pull_error
+"#).unwrap();
+
+        let mut executor = Executor::new();
+        let (p, g) = executor.start(vec!(event)).unwrap();
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+            while sender::mock::sent("executor_tests_pull_error").is_empty() {
+                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            }
+        }).await;
+        assert!(res.is_ok());
+        assert_eq!(executor.liveness(), HashMap::from([("flaky".to_string(), true)]));
+
+        g.call();
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_pipeline_stops_when_receiver_fails() {
+        let event: Event = serde_yaml::from_str("name: broken\ntrigger: [{type: mock, config: {messages: 1}}]\ntarget: []").unwrap();
+        let startup = Arc::new(Startup::new());
+        startup.register("broken");
+        startup.executor_started();
+        let (p, _) = Pipeline::new(event, startup.clone(), Arc::new(PipelineMetrics::new())).start();
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+        // Only a pipeline whose receivers are built counts as started.
+        assert!(!startup.is_ready());
+        assert_eq!(startup.pending(), vec!("broken"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_add_pipeline_disabled_or_invalid() {
+        let mut executor = Executor::new();
+        let (_, g) = executor.start(vec!()).unwrap();
+
+        let disabled: Event = serde_yaml::from_str("name: a\ntrigger: [{type: mock}]\ntarget: []\nenabled: false").unwrap();
+        assert!(executor.add_pipeline(disabled).is_ok());
+        assert!(executor.liveness().is_empty());
+
+        let invalid: Event = serde_yaml::from_str("name: b\ntrigger: [{type: unknown}]\ntarget: []").unwrap();
+        assert!(matches!(executor.add_pipeline(invalid), Err(ExecutorError::InvalidEvent { name, .. }) if name == "b"));
+        assert!(executor.liveness().is_empty());
+
+        g.call();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_add_pipeline_duplicate_name() {
+        let mut executor = Executor::new();
+        let (_, g) = executor.start(vec!(mock_event("a"))).unwrap();
+
+        let res = executor.add_pipeline(mock_event("a"));
+        assert!(matches!(res, Err(ExecutorError::DuplicateName(name)) if name == "a"));
+
+        g.call();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_reload_invalid_config_keeps_pipeline() {
+        let event = |name: &str, trigger_config: &str| parse_event(format!(r#"
+name: {}
+trigger:
+  - type: mock
+    config:
+      channel: executor_tests_reload
+      {}
+target:
+  - mock:
+      name: executor_tests_reload
+"#, name, trigger_config).as_str());
+        utils::logger::capture::init();
+
+        let mut executor = Executor::new();
+        let (p, g) = executor.start(vec!(event("a", ""))).unwrap();
+
+        // "a" changes to a trigger config that cannot be built, so the running "a" must survive.
+        let res = executor.reload(vec!(event("a", "messages: 1"), event("b", ""))).await;
+        assert!(matches!(res, Err(errors) if errors.len() == 1));
+        assert_eq!(executor.liveness(), HashMap::from([("a".to_string(), true)]));
+        assert!(!utils::logger::capture::records(|r| r.message == "hot reload aborted due to config errors").is_empty());
+
+        trigger::mock::push("executor_tests_reload", "hello");
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+            while sender::mock::sent("executor_tests_reload").is_empty() {
+                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            }
+        }).await;
+        assert!(res.is_ok());
+
+        g.call();
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_validate_labels() {
+        let event = |label: &str| -> Event {
+            serde_yaml::from_str(format!(r#"
+name: a
+trigger:
+  - type: mock
+target: []
+labels:
+  {}: payments
+"#, label).as_str()).unwrap()
+        };
+
+        assert!(event("team").validate().is_ok());
+        assert!(event("_env2").validate().is_ok());
+        assert!(matches!(event("pipeline").validate(), Err(ExecutorError::InvalidEvent { .. })));
+        assert!(matches!(event("event_name").validate(), Err(ExecutorError::InvalidEvent { .. })));
+        assert!(matches!(event("__name").validate(), Err(ExecutorError::InvalidEvent { .. })));
+        assert!(matches!(event("2team").validate(), Err(ExecutorError::InvalidEvent { .. })));
+        assert!(matches!(event("team-name").validate(), Err(ExecutorError::InvalidEvent { .. })));
+    }
+
+    // The mock triggers never return a message, so the pipelines are ready before their first pull.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_startup_ready_after_pipelines_start() {
+        let mut executor = Executor::new();
+        let (_, g) = executor.start(vec!(mock_event("a"), mock_event("b"))).unwrap();
+        let startup = executor.startup();
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+            while !startup.is_ready() {
+                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            }
+        }).await;
+        assert!(res.is_ok());
+        assert!(startup.pending().is_empty());
+
+        g.call();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_stop_interrupts_sleep() {
+        let event: Event = serde_yaml::from_str(r#"
+name: sleepy
+trigger:
+  - type: mock
+    config:
+      messages: [hello]
+process:
+  - sleep: { duration_ms: 60000 }
+target:
+  - mock:
+      name: sleepy_target
+"#).unwrap();
+
+        let mut executor = Executor::new();
+        let (p, g) = executor.start(vec!(event)).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+
+        g.call();
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), p).await;
+        assert!(res.is_ok());
+        assert!(sender::mock::sent("sleepy_target").is_empty());
+    }
+
+    #[test]
+    fn test_queue_capacity() {
+        let event = |capacity: &str| -> Event {
+            serde_yaml::from_str(format!("name: a\ntrigger: []\ntarget: []\n{}", capacity).as_str()).unwrap()
+        };
+
+        assert_eq!(event("").queue_capacity, Some(0));
+        assert_eq!(event("queue_capacity: 64").queue_capacity, Some(64));
+        assert_eq!(event("queue_capacity: ~").queue_capacity, None);
+    }
+
+    #[tokio::test]
+    async fn test_execute_ops_max_state_keys() {
+        let event = |limit: &str| -> Event {
+            serde_yaml::from_str(format!(r#"
+name: a
+trigger:
+  - type: mock
+target: []
+{}
+process:
+  - set_env: {{ target: a, value: 1 }}
+  - set_env: {{ target: b, value: [2, 3] }}
+  - set_env: {{ target: c, value: 4 }}
+"#, limit).as_str()).unwrap()
+        };
+        let run = |event: Event| async move {
+            let msg = MockMessage { content: "".into(), ..Default::default() };
+            let ops = event.process.clone().unwrap();
+            dispatch_webhook(&event, &[], &msg, &ops, &PipelineMetrics::new(), &GracefulSignal::never(), &Delivered::default()).await
+        };
+
+        let res = run(event("max_state_keys: 3")).await;
+        assert!(matches!(res, Err(Error::ExecutionError(e)) if e.contains("limit is 3")));
+
+        let res = run(event("")).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_stop_pipeline_not_found() {
+        let executor = Executor::new();
+
+        let res = executor.stop_pipeline("a").await;
+        assert!(matches!(res, Err(ExecutorError::PipelineNotFound(name)) if name == "a"));
+    }
+
+    #[derive(Default)]
+    struct MockMessage {
+        done: std::sync::atomic::AtomicUsize,
+        nack: std::sync::atomic::AtomicUsize,
+        content: Vec<u8>,
+    }
+
+    #[async_trait::async_trait]
+    impl SourceEvent for MockMessage {
+        fn bytes(&self) -> &Vec<u8> {
+            &self.content
+        }
+
+        async fn done(&self) {
+            self.done.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        async fn nack(&self) {
+            self.nack.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    async fn handle_slow_message(name: &str, behavior: &str) -> MockMessage {
+        let event: Event = serde_yaml::from_str(format!(r#"
+name: {}
+trigger: []
+target:
+  - mock:
+      name: {}
+      delay_ms: 200
+timeout_ms: 20
+timeout_behavior: {}
+"#, name, name, behavior).as_str()).unwrap();
+
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let dead_letter_senders = match &event.timeout_behavior {
+            Some(TimeoutBehavior::DeadLetter { target }) => target.iter().map(|t| sender::new_sender(t).unwrap()).collect(),
+            _ => vec!(),
+        };
+
+        let msg = MockMessage { content: "hello".into(), ..Default::default() };
+        handle_message(&event, &senders, &dead_letter_senders, &msg, &[], &PipelineMetrics::new(), &GracefulSignal::never()).await;
+        msg
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_message_nack() {
+        let event: Event = serde_yaml::from_str(r#"
+name: interrupted_nack
+trigger: []
+target:
+  - mock:
+      name: interrupted_nack
+"#).unwrap();
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let ops: Vec<operation::Op> = serde_yaml::from_str("[sleep: {duration_ms: 60000}]").unwrap();
+        let metrics = PipelineMetrics::new();
+
+        let (invoker, graceful) = new_graceful_signal();
+        invoker.call();
+
+        let msg = MockMessage { content: "hello".into(), ..Default::default() };
+        handle_message(&event, &senders, &[], &msg, &ops, &metrics, &graceful).await;
+
+        assert_eq!(msg.done.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert_eq!(msg.nack.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(sender::mock::sent("interrupted_nack").is_empty());
+        assert!(!metrics.encode().contains("interrupted_nack"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_retry_keeps_correlation_id() {
+        utils::logger::capture::init();
+        handle_slow_message("timeout_retry_correlation", "{retry: {max_attempts: 2}}").await;
+
+        let records = utils::logger::capture::records(|r| r.message.starts_with("pipeline timeout_retry_correlation timed out"));
+        assert!(records.iter().all(|r| r.level == log::Level::Warn));
+        let ids = records.into_iter().map(|r| r.correlation_id).collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert!(ids[0].is_some());
+        assert_eq!(ids[0], ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_drop() {
+        let msg = handle_slow_message("timeout_drop", "drop").await;
+
+        assert_eq!(msg.done.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(msg.nack.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_retry() {
+        let msg = handle_slow_message("timeout_retry", "{retry: {max_attempts: 2}}").await;
+
+        assert_eq!(msg.done.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert_eq!(msg.nack.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(sender::mock::sent("timeout_retry").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_retry_skips_delivered_targets() {
+        let event: Event = serde_yaml::from_str(r#"
+name: timeout_retry_delivered
+trigger: []
+target:
+  - mock:
+      name: timeout_retry_delivered_fast
+  - mock:
+      name: timeout_retry_delivered_slow
+      delay_ms: 500
+timeout_ms: 100
+timeout_behavior: {retry: {max_attempts: 3}}
+"#).unwrap();
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+
+        let msg = MockMessage { content: "hello".into(), ..Default::default() };
+        handle_message(&event, &senders, &[], &msg, &[], &PipelineMetrics::new(), &GracefulSignal::never()).await;
+
+        assert_eq!(msg.nack.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(sender::mock::sent("timeout_retry_delivered_fast").len(), 1);
+        assert!(sender::mock::sent("timeout_retry_delivered_slow").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_dead_letter() {
+        let msg = handle_slow_message(
+            "timeout_dead_letter",
+            "{dead_letter: {target: [{mock: {name: timeout_dead_letter_target}}]}}",
+        ).await;
+
+        assert_eq!(msg.done.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(msg.nack.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert_eq!(sender::mock::sent("timeout_dead_letter_target"), vec!("hello".as_bytes().to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_webhook_http_ok() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::{body_json, method, path};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_json(serde_json::json!({"greeting": "hello"})))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let event: Event = serde_yaml::from_str(format!(r#"
+name: dispatch_http
+trigger: []
+target:
+  - http:
+      - post:
+          url: {}/hook
+"#, server.uri()).as_str()).unwrap();
+        let ops: Vec<operation::Op> = serde_yaml::from_str(r#"
+- set_env:
+    target: raw
+    value: hello
+- to_payload:
+    format: json
+    value:
+      as_map:
+        greeting:
+          from_state: raw
+"#).unwrap();
+
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let msg = MockMessage::default();
+        let metrics = PipelineMetrics::new();
+
+        let res = dispatch_webhook(&event, &senders, &msg, &ops, &metrics, &GracefulSignal::never(), &Delivered::default()).await;
+        assert!(res.is_ok());
+        assert!(metrics.encode().contains("webhook_events_processed_total{event_name=\"dispatch_http\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_webhook_malformed_payload_err() {
+        let event: Event = serde_yaml::from_str(r#"
+name: dispatch_malformed
+trigger: []
+target:
+  - mock:
+      name: dispatch_malformed_target
+"#).unwrap();
+        let ops: Vec<operation::Op> = serde_yaml::from_str(r#"
+- set_env:
+    target: body
+    value:
+      from_payload: json
+"#).unwrap();
+
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let msg = MockMessage { content: "{not json".into(), ..Default::default() };
+        let metrics = PipelineMetrics::new();
+
+        let res = dispatch_webhook(&event, &senders, &msg, &ops, &metrics, &GracefulSignal::never(), &Delivered::default()).await;
+        assert!(matches!(res, Err(Error::ExecutionError(e)) if e.contains("json")));
+        assert!(sender::mock::sent("dispatch_malformed_target").is_empty());
+        assert!(metrics.encode().contains("webhook_events_failed_total{event_name=\"dispatch_malformed\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_webhook_sender_err() {
+        let event: Event = serde_yaml::from_str(r#"
+name: dispatch_sender_err
+trigger: []
+target:
+  - http:
+      - post:
+          url: ""
+  - mock:
+      name: dispatch_sender_err_target
+"#).unwrap();
+
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let msg = MockMessage::default();
+        let metrics = PipelineMetrics::new();
+
+        let res = dispatch_webhook(&event, &senders, &msg, &[], &metrics, &GracefulSignal::never(), &Delivered::default()).await;
+        assert!(matches!(res, Err(Error::SendError(sender::Error::HttpRequestBuild(_)))));
+        assert_eq!(sender::mock::sent("dispatch_sender_err_target").len(), 1);
+        assert!(metrics.encode().contains("webhook_events_failed_total{event_name=\"dispatch_sender_err\"} 1"));
+        assert!(metrics.encode().contains("webhook_sender_failed_total{event_name=\"dispatch_sender_err\",sender=\"http[0]\"} 1"));
+        assert!(metrics.encode().contains("webhook_sender_duration_seconds_count{event_name=\"dispatch_sender_err\",sender=\"mock[1]\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_webhook_senders_run_concurrently() {
+        let event: Event = serde_yaml::from_str(r#"
+name: dispatch_concurrent
+trigger: []
+target:
+  - mock:
+      name: dispatch_concurrent_a
+      delay_ms: 300
+  - mock:
+      name: dispatch_concurrent_b
+      delay_ms: 300
+"#).unwrap();
+
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let msg = MockMessage::default();
+
+        let start = std::time::Instant::now();
+        let res = dispatch_webhook(&event, &senders, &msg, &[], &PipelineMetrics::new(), &GracefulSignal::never(), &Delivered::default()).await;
+        assert!(res.is_ok());
+        assert!(start.elapsed() < std::time::Duration::from_millis(550));
+        assert_eq!(sender::mock::sent("dispatch_concurrent_a").len(), 1);
+        assert_eq!(sender::mock::sent("dispatch_concurrent_b").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_webhook_waits_for_captured_response() {
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+        use wiremock::matchers::method;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_string(r#"{"id": 1}"#)
+                .set_delay(std::time::Duration::from_millis(200)))
+            .mount(&server)
+            .await;
+
+        let event: Event = serde_yaml::from_str(format!(r#"
+name: dispatch_captured
+trigger: []
+target:
+  - condition:
+      from_state: created
+    mock:
+      name: dispatch_captured_before
+  - http:
+      - post:
+          url: {}/hook
+          capture_response: {{ key: created, format: json }}
+  - condition:
+      from_state: created
+    mock:
+      name: dispatch_captured_after
+"#, server.uri()).as_str()).unwrap();
+
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let msg = MockMessage::default();
+
+        let res = dispatch_webhook(&event, &senders, &msg, &[], &PipelineMetrics::new(), &GracefulSignal::never(), &Delivered::default()).await;
+        assert!(res.is_ok());
+        assert!(sender::mock::sent("dispatch_captured_before").is_empty());
+        assert_eq!(sender::mock::sent("dispatch_captured_after").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_webhook_multiple_sender_err() {
+        let event: Event = serde_yaml::from_str(r#"
+name: dispatch_multiple_sender_err
+trigger: []
+target:
+  - http:
+      - post:
+          url: ""
+  - mock:
+      name: dispatch_multiple_sender_err_target
+  - http:
+      - post:
+          url: ""
+"#).unwrap();
+
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let msg = MockMessage::default();
+
+        let res = dispatch_webhook(&event, &senders, &msg, &[], &PipelineMetrics::new(), &GracefulSignal::never(), &Delivered::default()).await;
+        assert!(matches!(res, Err(Error::MultipleSendError(errors)) if errors.len() == 2));
+        assert_eq!(sender::mock::sent("dispatch_multiple_sender_err_target").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_webhook_condition_skips_sender() {
+        let event: Event = serde_yaml::from_str(r#"
+name: dispatch_condition
+trigger: []
+target:
+  - condition:
+      from_state: missing
+    mock:
+      name: dispatch_condition_skipped
+  - condition:
+      from_state: flag
+    mock:
+      name: dispatch_condition_sent
+  - mock:
+      name: dispatch_condition_unconditional
+"#).unwrap();
+        let ops: Vec<operation::Op> = serde_yaml::from_str(r#"
+- set_env:
+    target: flag
+    value: true
+"#).unwrap();
+
+        let senders = event.target.iter().map(|t| sender::new_sender(t).unwrap()).collect::<Vec<_>>();
+        let msg = MockMessage::default();
+        let metrics = PipelineMetrics::new();
+
+        let res = dispatch_webhook(&event, &senders, &msg, &ops, &metrics, &GracefulSignal::never(), &Delivered::default()).await;
+        assert!(res.is_ok());
+        assert!(sender::mock::sent("dispatch_condition_skipped").is_empty());
+        assert_eq!(sender::mock::sent("dispatch_condition_sent").len(), 1);
+        assert_eq!(sender::mock::sent("dispatch_condition_unconditional").len(), 1);
+    }
+}
+
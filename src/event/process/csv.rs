@@ -0,0 +1,154 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::Deserialize;
+
+use crate::event::process::{Error, Item, Result, Value};
+
+fn default_has_header() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CsvOptions {
+    #[serde(default = "default_has_header")]
+    pub has_header: bool,
+}
+
+fn csv_error(e: impl std::fmt::Display) -> Error {
+    Error::CsvError(e.to_string())
+}
+
+// Only canonical integers are converted, so values like "007" keep their leading zeros.
+fn cell(s: &str) -> Item {
+    match s.parse::<i64>() {
+        Ok(i) if i.to_string() == s => Item::Value(Value::IntValue(i)),
+        _ => Item::Value(Value::StringValue(s.into())),
+    }
+}
+
+pub fn parse(content: &[u8], has_header: bool) -> Result<Item> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(has_header)
+        .from_reader(content);
+
+    let headers = match has_header {
+        true => Some(reader.headers().map_err(csv_error)?.clone()),
+        false => None,
+    };
+
+    let rows = reader.records()
+        .map(|record| {
+            let record = record.map_err(csv_error)?;
+            Ok(match &headers {
+                Some(headers) => Item::Map(headers.iter()
+                    .zip(record.iter())
+                    .map(|(k, v)| (k.to_string(), cell(v)))
+                    .collect()),
+                None => Item::Vec(record.iter().map(cell).collect()),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Item::Vec(rows))
+}
+
+fn to_field(item: &Item) -> Result<String> {
+    match item {
+        Item::Value(v) => Ok(v.to_string()),
+        i => Err(csv_error(format!("cell must be a Value, found {}", i.type_name()))),
+    }
+}
+
+/// Serializes an array of maps, using the sorted union of their keys as the header, or an array of arrays.
+pub fn to_vec(item: &Item, has_header: bool) -> Result<Vec<u8>> {
+    let rows = match item {
+        Item::Vec(rows) => rows,
+        i => return Err(csv_error(format!("expected Array, found {}", i.type_name()))),
+    };
+
+    let maps = rows.iter()
+        .filter_map(|r| match r {
+            Item::Map(m) => Some(m),
+            _ => None,
+        })
+        .collect::<Vec<&HashMap<String, Item>>>();
+    let columns = maps.iter()
+        .flat_map(|m| m.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(maps.is_empty())
+        .from_writer(vec!());
+    if has_header && !columns.is_empty() {
+        writer.write_record(columns.iter()).map_err(csv_error)?;
+    }
+
+    for row in rows {
+        let fields = match row {
+            Item::Map(m) => columns.iter()
+                .map(|c| m.get(*c).map(to_field).unwrap_or_else(|| Ok(String::new())))
+                .collect::<Result<Vec<_>>>()?,
+            Item::Vec(v) if maps.is_empty() => v.iter().map(to_field).collect::<Result<Vec<_>>>()?,
+            i => return Err(csv_error(format!("rows must all be Maps or all be Arrays, found {}", i.type_name()))),
+        };
+        writer.write_record(fields).map_err(csv_error)?;
+    }
+
+    writer.into_inner().map_err(csv_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: &str = "id,name,note,sku\n1,Otter,\"likes \"\"fish\"\", a lot\",007\n2,Beaver,\"multi\nline\",42\n";
+
+    #[test]
+    fn test_parse_header_ok() {
+        let item = parse(ORDERS.as_bytes(), true).unwrap();
+
+        let expected: Item = serde_json::from_str(r#"[
+            {"id": 1, "name": "Otter", "note": "likes \"fish\", a lot", "sku": "007"},
+            {"id": 2, "name": "Beaver", "note": "multi\nline", "sku": 42}
+        ]"#).unwrap();
+        assert_eq!(item, expected);
+    }
+
+    #[test]
+    fn test_parse_no_header_ok() {
+        let item = parse("a,1\n\"b,c\",-2\n".as_bytes(), false).unwrap();
+
+        let expected: Item = serde_json::from_str(r#"[["a", 1], ["b,c", -2]]"#).unwrap();
+        assert_eq!(item, expected);
+    }
+
+    #[test]
+    fn test_round_trip_ok() {
+        let item = parse(ORDERS.as_bytes(), true).unwrap();
+        let content = to_vec(&item, true).unwrap();
+
+        assert_eq!(String::from_utf8(content.clone()).unwrap(), ORDERS);
+        assert_eq!(parse(content.as_slice(), true).unwrap(), item);
+
+        let item = parse("a,1\n\"b,c\",-2\n".as_bytes(), false).unwrap();
+        assert_eq!(to_vec(&item, false).unwrap(), b"a,1\n\"b,c\",-2\n");
+    }
+
+    #[test]
+    fn test_to_vec_missing_column_ok() {
+        let item: Item = serde_json::from_str(r#"[{"a": 1}, {"b": true, "c": null}]"#).unwrap();
+        assert_eq!(to_vec(&item, true).unwrap(), b"a,b,c\n1,,\n,true,\n");
+        assert_eq!(to_vec(&item, false).unwrap(), b"1,,\n,true,\n");
+    }
+
+    #[test]
+    fn test_err() {
+        assert!(matches!(parse("a,b\n1,2,3\n".as_bytes(), true), Err(Error::CsvError(_))));
+        assert!(matches!(parse(b"a\n\xff\n", true), Err(Error::CsvError(_))));
+
+        for json in [r#"{"a": 1}"#, r#"[{"a": [1]}]"#, r#"[{"a": 1}, [1]]"#, r#"[1]"#] {
+            let item: Item = serde_json::from_str(json).unwrap();
+            assert!(matches!(to_vec(&item, true), Err(Error::CsvError(_))), "{}", json);
+        }
+    }
+}
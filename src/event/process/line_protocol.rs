@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use crate::event::process::{Error, Item, Result, Value};
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidLineProtocol { reason: reason.into() }
+}
+
+fn escape(s: &str, special: &[char]) -> String {
+    s.chars().fold(String::new(), |mut acc, c| {
+        if c == '\\' || special.contains(&c) {
+            acc.push('\\');
+        }
+        acc.push(c);
+        acc
+    })
+}
+
+fn escape_key(s: &str) -> String {
+    escape(s, &[',', '=', ' '])
+}
+
+fn as_map<'a>(item: Option<&'a Item>, name: &str) -> Result<Option<&'a HashMap<String, Item>>> {
+    match item {
+        None => Ok(None),
+        Some(Item::Map(m)) => Ok(Some(m)),
+        Some(i) => Err(invalid(format!("{} must be a Map, found {}", name, i.type_name()).as_str())),
+    }
+}
+
+fn sorted(map: &HashMap<String, Item>) -> Vec<(&String, &Item)> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(k, _)| *k);
+    entries
+}
+
+pub fn to_vec(item: &Item) -> Result<Vec<u8>> {
+    let map = match item {
+        Item::Map(m) => m,
+        i => return Err(invalid(format!("expected Map, found {}", i.type_name()).as_str())),
+    };
+
+    let measurement = match map.get("measurement") {
+        Some(Item::Value(Value::StringValue(s))) if !s.is_empty() => s,
+        _ => return Err(invalid("measurement must be a non-empty String")),
+    };
+    let mut line = escape(measurement, &[',', ' ']);
+
+    if let Some(tags) = as_map(map.get("tags"), "tags")? {
+        for (key, value) in sorted(tags) {
+            let value = match value {
+                Item::Value(Value::None) => continue,
+                Item::Value(v) => v.to_string(),
+                i => return Err(invalid(format!("tag {} must be a Value, found {}", key, i.type_name()).as_str())),
+            };
+            line.push_str(format!(",{}={}", escape_key(key), escape_key(value.as_str())).as_str());
+        }
+    }
+
+    let fields = as_map(map.get("fields"), "fields")?
+        .map(|fields| {
+            sorted(fields).iter()
+                .filter(|(_, value)| !matches!(value, Item::Value(Value::None)))
+                .map(|(key, value)| {
+                    let value = match value {
+                        Item::Value(Value::BoolValue(b)) => format!("{}", b),
+                        Item::Value(Value::IntValue(i)) => format!("{}i", i),
+                        Item::Value(Value::FloatValue(v)) => format!("{}", v),
+                        Item::Value(Value::StringValue(s)) => format!("\"{}\"", escape(s, &['"'])),
+                        i => return Err(invalid(format!("field {} must be a Value, found {}", key, i.type_name()).as_str())),
+                    };
+                    Ok(format!("{}={}", escape_key(key), value))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    if fields.is_empty() {
+        return Err(invalid("at least one field is required"));
+    }
+    line.push(' ');
+    line.push_str(fields.join(",").as_str());
+
+    match map.get("timestamp") {
+        None | Some(Item::Value(Value::None)) => {}
+        Some(Item::Value(Value::IntValue(ts))) => line.push_str(format!(" {}", ts).as_str()),
+        Some(i) => return Err(invalid(format!("timestamp must be an Int, found {}", i.type_name()).as_str())),
+    }
+
+    Ok(line.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_vec_ok() {
+        let item = serde_json::from_str(r#"{
+            "measurement": "cpu load",
+            "tags": {"host": "server,01", "region": "ap-southeast"},
+            "fields": {"value": 42, "status": "say \"hi\""},
+            "timestamp": 1630000000000000000
+        }"#).unwrap();
+
+        let res = to_vec(&item);
+        assert!(res.is_ok());
+        assert_eq!(
+            String::from_utf8(res.unwrap()).unwrap(),
+            r#"cpu\ load,host=server\,01,region=ap-southeast status="say \"hi\"",value=42i 1630000000000000000"#,
+        );
+    }
+
+    #[test]
+    fn test_to_vec_without_tags_and_timestamp_ok() {
+        let item = serde_json::from_str(r#"{"measurement": "m", "fields": {"a=b": "c"}}"#).unwrap();
+
+        let res = to_vec(&item);
+        assert!(res.is_ok());
+        assert_eq!(String::from_utf8(res.unwrap()).unwrap(), r#"m a\=b="c""#);
+    }
+
+    #[test]
+    fn test_to_vec_float_field_ok() {
+        let item = serde_json::from_str(r#"{"measurement": "m", "fields": {"temp": 21.5}}"#).unwrap();
+
+        let res = to_vec(&item);
+        assert!(res.is_ok());
+        assert_eq!(String::from_utf8(res.unwrap()).unwrap(), "m temp=21.5");
+    }
+
+    #[test]
+    fn test_to_vec_missing_fields_err() {
+        let item = serde_json::from_str(r#"{"measurement": "m", "tags": {"a": "b"}}"#).unwrap();
+
+        let res = to_vec(&item);
+        assert!(matches!(res, Err(Error::InvalidLineProtocol { .. })));
+    }
+}
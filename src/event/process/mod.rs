@@ -1,37 +1,144 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Formatter;
 use std::str::FromStr;
 
+use ordered_float::OrderedFloat;
 use serde::{Serialize, Deserialize};
 use thiserror::Error;
-use std::num::ParseIntError;
 
+mod csv;
+mod line_protocol;
 pub mod operation;
+mod xml;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("unable to access field {field} from type {t}")]
-    NonMapAccess { field: String, t: String },
+    #[error("unable to access field \"{field}\" of \"{path}\": expected Map or Array, found {t}")]
+    NonMapAccess { path: String, field: String, t: String },
 
-    #[error("index {index} out of bound in array with length {len}")]
-    IndexOutOfBound { index: usize, len: usize },
+    #[error("index {index} out of bound for array \"{path}\" with length {len}, {}", index_hint(*.len))]
+    IndexOutOfBound { path: String, index: usize, len: usize },
 
-    #[error("invalid index: {reason}")]
-    InvalidIndex { reason: String },
+    #[error("invalid index \"{index}\" for array \"{path}\": {reason}")]
+    InvalidIndex { path: String, index: String, reason: String },
+
+    #[error("expected type {expected}, found {t}")]
+    UnexpectedType { expected: String, t: String },
+
+    #[error("invalid regex \"{pattern}\": {reason}")]
+    InvalidRegex { pattern: String, reason: String },
+
+    #[error("invalid line protocol: {reason}")]
+    InvalidLineProtocol { reason: String },
+
+    #[error("unable to parse payload as {format}: {reason}")]
+    SerializationError { format: String, reason: String },
+
+    #[error("invalid json: {0}")]
+    JsonError(String),
+
+    #[error("json (de)serialization failed: {0}")]
+    JsonDeserialize(String),
+
+    #[error("yaml (de)serialization failed: {0}")]
+    YamlDeserialize(String),
+
+    #[error("invalid base64: {0}")]
+    Base64DecodeError(String),
+
+    #[error("hmac error: {0}")]
+    HmacError(String),
+
+    #[error("invalid timezone \"{0}\"")]
+    InvalidTimezone(String),
+
+    #[error("invalid time format \"{0}\"")]
+    InvalidTimeFormat(String),
+
+    #[error("invalid csv: {0}")]
+    CsvError(String),
+
+    #[error("invalid xml: {0}")]
+    XmlError(String),
+
+    #[error("division by zero")]
+    DivisionByZero,
+
+    #[error("arithmetic overflow")]
+    ArithmeticOverflow,
+
+    #[error("interrupted by stop signal")]
+    Interrupted,
+
+    #[error("encryption error: {0}")]
+    EncryptionError(String),
+
+    #[error("invalid jwt: {0}")]
+    JwtInvalid(String),
+
+    #[error("template error: {0}")]
+    TemplateError(String),
+
+    #[error("state size limit exceeded: {actual} values, limit is {limit}")]
+    StateSizeExceeded { limit: usize, actual: usize },
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct State(HashMap<String, Item>);
+fn index_hint(len: usize) -> String {
+    match len {
+        0 => "the array is empty".into(),
+        len => format!("use an index between 0 and {}", len - 1),
+    }
+}
+
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct State {
+    items: HashMap<String, Item>,
+    leaves: usize,
+    sensitive: HashSet<String>,
+}
 
 impl State {
     pub fn new() -> Self {
-        State(HashMap::new())
+        State {
+            items: HashMap::new(),
+            leaves: 0,
+            sensitive: HashSet::new(),
+        }
+    }
+
+    pub fn from_item(item: Item) -> Result<Self> {
+        match item {
+            Item::Map(items) => {
+                let leaves = items.values().map(item_leaf_count).sum();
+                Ok(State { items, leaves, sensitive: HashSet::new() })
+            }
+            i => Err(Error::UnexpectedType { expected: "Map".into(), t: i.type_name().into() }),
+        }
     }
 
     pub fn get(&self, key: &Identifier) -> Option<&Item> {
-        Self::get_from_map(&self.0, key)
+        Self::get_from_map(&self.items, key)
+    }
+
+    /// Returns the top-level keys of the state, sorted.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = self.items.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        keys
+    }
+
+    /// Iterates over the top-level entries of the state in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Item)> {
+        self.items.iter()
+    }
+
+    /// Checks whether a value exists at the given dot-separated path.
+    pub fn contains_key(&self, key: &Identifier) -> bool {
+        self.get(key).is_some()
     }
 
     fn get_from_map<'a>(map: &'a HashMap<String, Item>, key: &Identifier) -> Option<&'a Item> {
@@ -47,7 +154,7 @@ impl State {
         }
     }
 
-    fn get_from_vec<'a>(vec: &'a Vec<Item>, key: &Identifier) -> Option<&'a Item> {
+    fn get_from_vec<'a>(vec: &'a [Item], key: &Identifier) -> Option<&'a Item> {
         let (key, path) = key.split();
 
         match key {
@@ -77,13 +184,123 @@ impl State {
         }
     }
 
+    /// Marks a key, and everything below it, as holding a secret that must not be logged.
+    pub fn mark_sensitive(&mut self, key: &Identifier) {
+        self.sensitive.insert(key.0.clone());
+    }
+
+    pub fn is_sensitive(&self, key: &Identifier) -> bool {
+        self.sensitive.iter().any(|s| {
+            key.0 == *s || (key.0.starts_with(s.as_str()) && key.0[s.len()..].starts_with('.'))
+        })
+    }
+
+    /// Carries the sensitive marks of `from` and the keys below it over to `to`, for a value copied
+    /// from `from` to `to`.
+    pub fn copy_sensitive(&mut self, from: &Identifier, to: &Identifier) {
+        if self.is_sensitive(from) {
+            self.mark_sensitive(to);
+        }
+        let children = self.sensitive_children(from)
+            .map(|rest| format!("{}.{}", to.0, rest))
+            .collect::<Vec<_>>();
+        self.sensitive.extend(children);
+    }
+
+    // Paths of the sensitive keys below `key`, relative to `key`.
+    fn sensitive_children<'a>(&'a self, key: &'a Identifier) -> impl Iterator<Item = &'a str> {
+        self.sensitive.iter()
+            .filter_map(move |s| s.strip_prefix(key.0.as_str()).and_then(|rest| rest.strip_prefix('.')))
+    }
+
+    /// Returns a value stored, or about to be stored, at `key` with every sensitive value in it
+    /// replaced by a placeholder.
+    pub fn redact(&self, key: &Identifier, value: &Item) -> Item {
+        if self.is_sensitive(key) {
+            return Item::Value(Value::StringValue(REDACTED.into()));
+        }
+
+        let mut copy = State::new();
+        copy.items.insert("value".into(), value.clone());
+        for rest in self.sensitive_children(key) {
+            let key = Identifier::from(format!("value.{}", rest).as_str());
+            if copy.get(&key).is_some() {
+                let _ = copy.set(key, Item::Value(Value::StringValue(REDACTED.into())));
+            }
+        }
+        copy.items.remove("value").unwrap_or(Item::Value(Value::None))
+    }
+
+    /// Formats a value stored, or about to be stored, at `key` for logging.
+    pub fn loggable(&self, key: &Identifier, value: &Item) -> String {
+        if self.is_sensitive(key) {
+            REDACTED.to_string()
+        } else {
+            format!("{:?}", self.redact(key, value))
+        }
+    }
+
+    /// Returns the top-level entries with every sensitive value replaced by a placeholder.
+    pub fn redacted(&self) -> HashMap<String, Item> {
+        let mut copy = State {
+            items: self.items.clone(),
+            leaves: self.leaves,
+            sensitive: HashSet::new(),
+        };
+        for key in self.sensitive.iter() {
+            let key = Identifier::from(key.as_str());
+            if copy.get(&key).is_some() {
+                let _ = copy.set(key, Item::Value(Value::StringValue(REDACTED.into())));
+            }
+        }
+        copy.items
+    }
+
     pub fn set(&mut self, key: Identifier, value: Item) -> Result<Option<Item>> {
-        Self::set_map(&mut self.0, key, value)
+        let added = item_leaf_count(&value);
+        let old = Self::set_map(&mut self.items, key.clone(), value, "")?;
+
+        // Setting an out of bound array index is a no-op, so only count the value if it was stored.
+        if self.get(&key).is_some() {
+            self.leaves += added;
+        }
+        self.leaves -= old.as_ref().map(item_leaf_count).unwrap_or(0);
+
+        Ok(old)
+    }
+
+    fn join_path(path_so_far: &str, key: &str) -> String {
+        if path_so_far.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path_so_far, key)
+        }
+    }
+
+    fn non_map_access(path: String, recursive_key: &Identifier, item: &Item) -> Error {
+        Error::NonMapAccess {
+            path,
+            field: recursive_key.split().0.unwrap_or_default(),
+            t: item.type_name().into(),
+        }
     }
 
-    fn set_map(map: &mut HashMap<String, Item>, key: Identifier, value: Item) -> Result<Option<Item>> {
+    fn parse_index(key: &str, path_so_far: &str) -> Result<usize> {
+        usize::from_str(key).map_err(|e| Error::InvalidIndex {
+            path: path_so_far.to_string(),
+            index: key.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    fn set_map(
+        map: &mut HashMap<String, Item>,
+        key: Identifier,
+        value: Item,
+        path_so_far: &str,
+    ) -> Result<Option<Item>> {
         let (key, path) = key.split();
-        log::trace!("setting internal state with key {:?} . {:?}, with value {:?}", key, path, value);
+        log::trace!("setting internal state with key {:?} . {:?}", key, path);
 
         match key {
             None => { Ok(None) }
@@ -93,24 +310,19 @@ impl State {
                         Ok(map.insert(key, value))
                     }
                     Some(recursive_key) => {
-                        let rec = map.get_mut(&key);
-
-                        let rec = if rec.is_none() {
-                            drop(rec);
-                            map.insert(key.clone(), Item::Map(HashMap::new()));
-                            map.get_mut(&key).unwrap()
-                        } else {
-                            rec.unwrap()
-                        };
+                        let current_path = Self::join_path(path_so_far, key.as_str());
+                        let rec = map
+                            .entry(key)
+                            .or_insert_with(|| Item::Map(HashMap::new()));
 
                         match rec {
                             Item::Map(map) => {
-                                Self::set_map(map, recursive_key, value)
+                                Self::set_map(map, recursive_key, value, current_path.as_str())
                             }
                             Item::Vec(v) => {
-                                Self::set_vec(v, recursive_key, value)
+                                Self::set_vec(v, recursive_key, value, current_path.as_str())
                             }
-                            i => Err(Error::NonMapAccess { field: key, t: i.type_name().into() })
+                            i => Err(Self::non_map_access(current_path, &recursive_key, i))
                         }
                     }
                 }
@@ -118,38 +330,36 @@ impl State {
         }
     }
 
-    fn set_vec(vec: &mut Vec<Item>, key: Identifier, value: Item) -> Result<Option<Item>> {
+    fn set_vec(vec: &mut [Item], key: Identifier, value: Item, path_so_far: &str) -> Result<Option<Item>> {
         let (key, path) = key.split();
-        log::trace!("setting internal state with key {:?} . {:?}, with value {:?}", key, path, value);
+        log::trace!("setting internal state with key {:?} . {:?}", key, path);
 
         match key {
             None => { Ok(None) }
             Some(key) => {
+                let idx = Self::parse_index(key.as_str(), path_so_far)?;
                 match path {
                     None => {
                         let mut value = value;
-                        Ok(usize::from_str(key.as_str())
-                            .map(|idx| vec.get_mut(idx))?
+                        Ok(vec.get_mut(idx)
                             .map(|val| {
                                 std::mem::swap(val, &mut value);
                                 value
                             }))
                     }
                     Some(recursive_key) => {
-                        let idx = usize::from_str(key.as_str());
-                        let rec = idx
-                            .clone()
-                            .map(|idx| vec.get_mut(idx))?;
+                        let len = vec.len();
+                        let current_path = Self::join_path(path_so_far, key.as_str());
 
-                        match rec {
-                            None => Err(Error::IndexOutOfBound { index: idx?, len: vec.len() }),
+                        match vec.get_mut(idx) {
+                            None => Err(Error::IndexOutOfBound { path: path_so_far.to_string(), index: idx, len }),
                             Some(Item::Map(map)) => {
-                                Self::set_map(map, recursive_key, value)
+                                Self::set_map(map, recursive_key, value, current_path.as_str())
                             }
                             Some(Item::Vec(v)) => {
-                                Self::set_vec(v, recursive_key, value)
+                                Self::set_vec(v, recursive_key, value, current_path.as_str())
                             }
-                            Some(i) => Err(Error::NonMapAccess { field: key, t: i.type_name().into() })
+                            Some(i) => Err(Self::non_map_access(current_path, &recursive_key, i))
                         }
                     }
                 }
@@ -157,8 +367,134 @@ impl State {
         }
     }
 
+    pub fn delete(&mut self, key: &Identifier) -> Result<Option<Item>> {
+        let old = Self::delete_map(&mut self.items, key, "")?;
+        self.leaves -= old.as_ref().map(item_leaf_count).unwrap_or(0);
+
+        Ok(old)
+    }
+
+    fn delete_child(item: Option<&mut Item>, key: &str, path: &Identifier, path_so_far: &str) -> Result<Option<Item>> {
+        let current_path = Self::join_path(path_so_far, key);
+
+        match item {
+            None => Ok(None),
+            Some(Item::Map(map)) => Self::delete_map(map, path, current_path.as_str()),
+            Some(Item::Vec(v)) => Self::delete_vec(v, path, current_path.as_str()),
+            Some(i) => Err(Self::non_map_access(current_path, path, i)),
+        }
+    }
+
+    fn delete_map(map: &mut HashMap<String, Item>, key: &Identifier, path_so_far: &str) -> Result<Option<Item>> {
+        let (key, path) = key.split();
+        log::trace!("deleting internal state with key {:?} . {:?}", key, path);
+
+        match (key, path) {
+            (None, _) => Ok(None),
+            (Some(key), None) => Ok(map.remove(&key)),
+            (Some(key), Some(path)) => Self::delete_child(map.get_mut(&key), key.as_str(), &path, path_so_far),
+        }
+    }
+
+    fn delete_vec(vec: &mut Vec<Item>, key: &Identifier, path_so_far: &str) -> Result<Option<Item>> {
+        let (key, path) = key.split();
+        log::trace!("deleting internal state with key {:?} . {:?}", key, path);
+
+        match (key, path) {
+            (None, _) => Ok(None),
+            (Some(key), None) => {
+                let idx = Self::parse_index(key.as_str(), path_so_far)?;
+                Ok(if idx < vec.len() { Some(vec.remove(idx)) } else { None })
+            }
+            (Some(key), Some(path)) => {
+                let idx = Self::parse_index(key.as_str(), path_so_far)?;
+                Self::delete_child(vec.get_mut(idx), key.as_str(), &path, path_so_far)
+            }
+        }
+    }
+
+    /// Appends to the array at the given path, creating it when the path does not exist.
+    pub fn push(&mut self, key: &Identifier, value: Item) -> Result<()> {
+        self.update_vec(key, |vec| vec.push(value))
+    }
+
+    /// Inserts at the front of the array at the given path, creating it when the path does not exist.
+    pub fn prepend(&mut self, key: &Identifier, value: Item) -> Result<()> {
+        self.update_vec(key, |vec| vec.insert(0, value))
+    }
+
+    fn update_vec(&mut self, key: &Identifier, f: impl FnOnce(&mut Vec<Item>)) -> Result<()> {
+        let mut vec = match self.get(key) {
+            None => vec!(),
+            Some(Item::Vec(vec)) => vec.clone(),
+            Some(i) => return Err(Error::NonMapAccess {
+                path: key.to_string(),
+                field: String::new(),
+                t: i.type_name().into(),
+            }),
+        };
+
+        f(&mut vec);
+        self.set(key.clone(), Item::Vec(vec)).map(|_| ())
+    }
+
+    /// Merges `other` into this state, `other` wins on conflicts. With `deep`, nested maps are merged
+    /// instead of replaced.
+    pub fn merge(&mut self, other: &State, deep: bool) {
+        merge_maps(&mut self.items, other.items.clone(), deep);
+        self.leaves = self.items.values().map(item_leaf_count).sum();
+    }
+
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.items.len()
+    }
+
+    pub fn len_recursive(&self) -> usize {
+        self.leaves
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn to_flat_map(&self, separator: &str) -> HashMap<String, String> {
+        let mut res = HashMap::new();
+        self.items.iter().for_each(|(key, item)| item.flatten_into(&mut res, Some(key.clone()), separator));
+        res
+    }
+}
+
+pub fn item_leaf_count(item: &Item) -> usize {
+    match item {
+        Item::Value(_) => 1,
+        Item::Vec(v) => v.iter().map(item_leaf_count).sum(),
+        Item::Map(m) => m.values().map(item_leaf_count).sum(),
+    }
+}
+
+fn merge_maps(base: &mut HashMap<String, Item>, overlay: HashMap<String, Item>, deep: bool) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Item::Map(base)), Item::Map(overlay)) if deep => merge_maps(base, overlay, deep),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("items", &self.redacted())
+            .field("leaves", &self.leaves)
+            .finish()
     }
 }
 
@@ -180,9 +516,9 @@ mod state_tests {
 
         drop(returned_item);
 
-        assert_eq!(state.0.len(), 1);
+        assert_eq!(state.items.len(), 1);
 
-        let item = state.0.get(&key.to_string());
+        let item = state.items.get(&key.to_string());
         assert!(item.is_some());
         assert_eq!(item.unwrap(), &value);
     }
@@ -206,9 +542,9 @@ mod state_tests {
         assert!(returned_item.is_some());
         assert_eq!(returned_item.unwrap(), other_value);
 
-        assert_eq!(state.0.len(), 1);
+        assert_eq!(state.items.len(), 1);
 
-        let item = state.0.get(&key.to_string());
+        let item = state.items.get(&key.to_string());
         assert!(item.is_some());
         assert_eq!(item.unwrap(), &value);
     }
@@ -227,9 +563,9 @@ mod state_tests {
 
         drop(returned_item);
 
-        assert_eq!(state.0.len(), 1);
+        assert_eq!(state.items.len(), 1);
 
-        let item = state.0.get(&String::from("key"));
+        let item = state.items.get(&String::from("key"));
         assert!(item.is_some());
 
         let item = item.unwrap();
@@ -266,9 +602,9 @@ mod state_tests {
         assert!(returned_value.is_some());
         assert_eq!(returned_value.unwrap(), old_value);
 
-        assert_eq!(state.0.len(), 1);
+        assert_eq!(state.items.len(), 1);
 
-        let item = state.0.get(&String::from("key"));
+        let item = state.items.get(&String::from("key"));
         assert!(item.is_some());
 
         let item = item.unwrap();
@@ -278,7 +614,7 @@ mod state_tests {
             Item::Vec(map) => map,
             _ => unreachable!()
         };
-        let item = map.get(0);
+        let item = map.first();
         assert!(item.is_some());
 
         let item = item.unwrap();
@@ -305,9 +641,9 @@ mod state_tests {
         assert!(returned_item.is_some());
         assert_eq!(returned_item.unwrap(), other_value);
 
-        assert_eq!(state.0.len(), 1);
+        assert_eq!(state.items.len(), 1);
 
-        let item = state.0.get(&String::from("key"));
+        let item = state.items.get(&String::from("key"));
         assert!(item.is_some());
 
         let item = item.unwrap();
@@ -411,6 +747,82 @@ mod state_tests {
         assert_eq!(result.unwrap(), &target)
     }
 
+    #[test]
+    fn set_non_map_access_err() {
+        let mut state = State::new();
+        let _ = state.set("a.b".into(), Item::Value(Value::IntValue(1)));
+
+        let res = state.set("a.b.c.d".into(), Item::Value(Value::None));
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "unable to access field \"c\" of \"a.b\": expected Map or Array, found Int",
+        );
+    }
+
+    #[test]
+    fn set_index_out_of_bound_err() {
+        let mut state = State::new();
+        let _ = state.set("a.list".into(), Item::Vec(vec!(Item::Vec(vec!()), Item::Vec(vec!()))));
+
+        let res = state.set("a.list.2.b".into(), Item::Value(Value::None));
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "index 2 out of bound for array \"a.list\" with length 2, use an index between 0 and 1",
+        );
+    }
+
+    #[test]
+    fn set_index_out_of_bound_empty_err() {
+        let mut state = State::new();
+        let _ = state.set("a.list".into(), Item::Vec(vec!()));
+
+        let res = state.set("a.list.0.b".into(), Item::Value(Value::None));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "index 0 out of bound for array \"a.list\" with length 0, the array is empty",
+        );
+    }
+
+    #[test]
+    fn set_invalid_index_err() {
+        let mut state = State::new();
+        let _ = state.set("a.list".into(), Item::Vec(vec!()));
+
+        let res = state.set("a.list.first".into(), Item::Value(Value::None));
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "invalid index \"first\" for array \"a.list\": invalid digit found in string",
+        );
+    }
+
+    #[test]
+    fn to_flat_map_ok() {
+        let mut state = State::new();
+
+        let _ = state.set("user.address.city".into(), Item::Value(Value::StringValue("Jakarta".into())));
+        let _ = state.set("user.address.zip".into(), Item::Value(Value::IntValue(12345)));
+        let _ = state.set("user.nickname".into(), Item::Value(Value::None));
+        let _ = state.set("tags".into(), Item::Vec(vec!(
+            Item::Value(Value::StringValue("a".into())),
+            Item::Value(Value::IntValue(1)),
+        )));
+
+        let flat = state.to_flat_map(".");
+        assert_eq!(flat.len(), 5);
+        assert_eq!(flat.get("user.address.city"), Some(&String::from("Jakarta")));
+        assert_eq!(flat.get("user.address.zip"), Some(&String::from("12345")));
+        assert_eq!(flat.get("user.nickname"), Some(&String::from("")));
+        assert_eq!(flat.get("tags.0"), Some(&String::from("a")));
+        assert_eq!(flat.get("tags.1"), Some(&String::from("1")));
+
+        let flat = state.to_flat_map("_");
+        assert_eq!(flat.get("user_address_city"), Some(&String::from("Jakarta")));
+        assert_eq!(flat.get("tags_1"), Some(&String::from("1")));
+    }
+
     #[test]
     fn get_array_element_nested_ok() {
         let mut state = State::new();
@@ -429,6 +841,360 @@ mod state_tests {
         assert!(result.is_some());
         assert_eq!(result.unwrap(), &target)
     }
+
+    #[test]
+    fn len_recursive_ok() {
+        let mut state = State::new();
+        assert!(state.is_empty());
+        assert_eq!(state.len_recursive(), 0);
+
+        let _ = state.set("a".into(), Item::Value(Value::IntValue(1)));
+        assert!(!state.is_empty());
+        assert_eq!(state.len_recursive(), 1);
+
+        let nested: Item = serde_json::from_str(r#"{"b": [1, 2, {"c": "d", "e": []}], "f": {}}"#).unwrap();
+        let _ = state.set("nested".into(), nested.clone());
+        assert_eq!(item_leaf_count(&nested), 3);
+        assert_eq!(state.len(), 2);
+        assert_eq!(state.len_recursive(), 4);
+
+        let _ = state.set("nested.b".into(), Item::Value(Value::None));
+        let _ = state.set("nested.f.9".into(), Item::Vec(vec!()));
+        assert_eq!(state.len_recursive(), 2);
+
+        let _ = state.set("nested.b".into(), Item::Vec(vec!()));
+        let _ = state.set("nested.b.0".into(), Item::Value(Value::IntValue(1)));
+        assert_eq!(state.len_recursive(), 1);
+    }
+
+    #[test]
+    fn from_item_ok() {
+        let item: Item = serde_json::from_str(r#"{"a": {"b": [1, {"c": "d"}]}, "e": "f"}"#).unwrap();
+
+        let state = State::from_item(item).unwrap();
+
+        assert_eq!(state.len(), 2);
+        assert_eq!(state.get(&"a.b.0".into()), Some(&Item::Value(Value::IntValue(1))));
+        assert_eq!(state.get(&"a.b.1.c".into()), Some(&Item::Value(Value::StringValue("d".into()))));
+        assert_eq!(state.get(&"e".into()), Some(&Item::Value(Value::StringValue("f".into()))));
+    }
+
+    #[test]
+    fn from_item_non_map_err() {
+        let res = State::from_item(Item::Vec(vec!()));
+        assert!(matches!(res, Err(Error::UnexpectedType { .. })));
+
+        let res = State::from_item(Item::Value(Value::None));
+        assert!(matches!(res, Err(Error::UnexpectedType { .. })));
+    }
+
+    #[test]
+    fn keys_iter_contains_key_empty_ok() {
+        let state = State::new();
+
+        assert!(state.keys().is_empty());
+        assert_eq!(state.iter().count(), 0);
+        assert!(!state.contains_key(&"a".into()));
+    }
+
+    #[test]
+    fn keys_iter_contains_key_flat_ok() {
+        let mut state = State::new();
+        let _ = state.set("b".into(), Item::Value(Value::IntValue(1)));
+        let _ = state.set("a".into(), Item::Value(Value::None));
+
+        assert_eq!(state.keys(), vec!("a", "b"));
+        let mut entries = state.iter().collect::<Vec<_>>();
+        entries.sort_by(|x, y| x.0.cmp(y.0));
+        assert_eq!(entries, vec!(
+            (&"a".to_string(), &Item::Value(Value::None)),
+            (&"b".to_string(), &Item::Value(Value::IntValue(1))),
+        ));
+        assert!(state.contains_key(&"a".into()));
+        assert!(!state.contains_key(&"c".into()));
+    }
+
+    #[test]
+    fn keys_iter_contains_key_nested_ok() {
+        let state = delete_state();
+
+        assert_eq!(state.keys(), vec!("a", "g"));
+        assert_eq!(state.iter().count(), 2);
+        assert!(state.contains_key(&"a.b.1.c".into()));
+        assert!(state.contains_key(&"a.e".into()));
+        assert!(!state.contains_key(&"a.b.2".into()));
+        assert!(!state.contains_key(&"g.h".into()));
+    }
+
+    fn delete_state() -> State {
+        let item: Item = serde_json::from_str(r#"{"a": {"b": [1, {"c": "d"}], "e": "f"}, "g": 2}"#).unwrap();
+        State::from_item(item).unwrap()
+    }
+
+    #[test]
+    fn sensitive_ok() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("auth"), serde_yaml::from_str("{token: secret, user: otter}").unwrap());
+        state.mark_sensitive(&Identifier::from("auth.token"));
+
+        assert!(state.is_sensitive(&Identifier::from("auth.token")));
+        assert!(state.is_sensitive(&Identifier::from("auth.token.inner")));
+        assert!(!state.is_sensitive(&Identifier::from("auth.tokens")));
+        assert!(!state.is_sensitive(&Identifier::from("auth")));
+
+        let value = |s: &str| Item::Value(Value::StringValue(s.into()));
+        assert_eq!(state.loggable(&Identifier::from("auth.token"), &value("secret")), REDACTED);
+        assert_eq!(state.loggable(&Identifier::from("auth.user"), &value("otter")), format!("{:?}", value("otter")));
+
+        let auth = state.get(&Identifier::from("auth")).unwrap();
+        assert!(!state.loggable(&Identifier::from("auth"), auth).contains("secret"));
+        assert_eq!(state.redact(&Identifier::from("auth"), auth), serde_yaml::from_str("{token: \"[REDACTED]\", user: otter}").unwrap());
+
+        state.copy_sensitive(&Identifier::from("auth"), &Identifier::from("copy"));
+        assert!(state.is_sensitive(&Identifier::from("copy.token")));
+        assert!(!state.is_sensitive(&Identifier::from("copy.user")));
+
+        assert_eq!(state.redacted()["auth"], serde_yaml::from_str("{token: \"[REDACTED]\", user: otter}").unwrap());
+        assert_eq!(state.get(&Identifier::from("auth.token")), Some(&Item::Value(Value::StringValue("secret".into()))));
+    }
+
+    #[test]
+    fn delete_ok() {
+        let mut state = delete_state();
+
+        let res = state.delete(&"g".into());
+        assert_eq!(res.unwrap(), Some(Item::Value(Value::IntValue(2))));
+        assert_eq!(state.get(&"g".into()), None);
+        assert_eq!(state.len(), 1);
+        assert_eq!(state.len_recursive(), 3);
+    }
+
+    #[test]
+    fn delete_recursive_ok() {
+        let mut state = delete_state();
+
+        let res = state.delete(&"a.b.1.c".into());
+        assert_eq!(res.unwrap(), Some(Item::Value(Value::StringValue("d".into()))));
+        assert_eq!(state.get(&"a.b.1".into()), Some(&Item::Map(HashMap::new())));
+
+        let res = state.delete(&"a.b.0".into());
+        assert_eq!(res.unwrap(), Some(Item::Value(Value::IntValue(1))));
+        assert_eq!(state.get(&"a.b".into()), Some(&Item::Vec(vec!(Item::Map(HashMap::new())))));
+
+        let res = state.delete(&"a".into());
+        assert!(matches!(res, Ok(Some(Item::Map(_)))));
+        assert_eq!(state.len_recursive(), 1);
+    }
+
+    #[test]
+    fn delete_missing_ok() {
+        let mut state = delete_state();
+
+        assert_eq!(state.delete(&"missing".into()).unwrap(), None);
+        assert_eq!(state.delete(&"missing.nested".into()).unwrap(), None);
+        assert_eq!(state.delete(&"a.b.5".into()).unwrap(), None);
+        assert_eq!(state, delete_state());
+    }
+
+    #[test]
+    fn push_ok() {
+        let mut state = delete_state();
+
+        assert!(state.push(&"a.b".into(), Item::Value(Value::IntValue(3))).is_ok());
+        assert!(state.prepend(&"a.b".into(), Item::Value(Value::IntValue(0))).is_ok());
+
+        let b: Item = serde_json::from_str(r#"[0, 1, {"c": "d"}, 3]"#).unwrap();
+        assert_eq!(state.get(&"a.b".into()), Some(&b));
+        assert_eq!(state.len_recursive(), 6);
+    }
+
+    #[test]
+    fn push_missing_key_ok() {
+        let mut state = State::new();
+
+        assert!(state.push(&"a.list".into(), Item::Value(Value::IntValue(1))).is_ok());
+        assert_eq!(state.get(&"a.list".into()), Some(&Item::Vec(vec!(Item::Value(Value::IntValue(1))))));
+
+        assert!(state.prepend(&"other".into(), Item::Value(Value::IntValue(2))).is_ok());
+        assert_eq!(state.get(&"other".into()), Some(&Item::Vec(vec!(Item::Value(Value::IntValue(2))))));
+        assert_eq!(state.len_recursive(), 2);
+    }
+
+    #[test]
+    fn push_non_vec_err() {
+        let mut state = delete_state();
+
+        let res = state.push(&"g".into(), Item::Value(Value::IntValue(1)));
+        assert!(matches!(res, Err(Error::NonMapAccess { path, .. }) if path == "g"));
+
+        let res = state.prepend(&"a".into(), Item::Value(Value::IntValue(1)));
+        assert!(matches!(res, Err(Error::NonMapAccess { .. })));
+        assert_eq!(state, delete_state());
+    }
+
+    fn merge_state(json: &str) -> State {
+        State::from_item(serde_json::from_str(json).unwrap()).unwrap()
+    }
+
+    const MERGE_BASE: &str = r#"{"a": {"b": 1, "c": {"d": 2}}, "e": [1, 2], "f": "base", "g": {"h": 3}}"#;
+    const MERGE_OVERLAY: &str = r#"{"a": {"c": {"x": 9}}, "e": [3], "f": {"nested": true}, "g": 4, "i": 5}"#;
+
+    #[test]
+    fn merge_shallow_ok() {
+        let mut state = merge_state(MERGE_BASE);
+        state.merge(&merge_state(MERGE_OVERLAY), false);
+
+        let expected = merge_state(r#"{"a": {"c": {"x": 9}}, "e": [3], "f": {"nested": true}, "g": 4, "i": 5}"#);
+        assert_eq!(state, expected);
+        assert_eq!(state.len_recursive(), 5);
+    }
+
+    #[test]
+    fn merge_deep_ok() {
+        let mut state = merge_state(MERGE_BASE);
+        state.merge(&merge_state(MERGE_OVERLAY), true);
+
+        let expected = merge_state(r#"{"a": {"b": 1, "c": {"d": 2, "x": 9}}, "e": [3], "f": {"nested": true}, "g": 4, "i": 5}"#);
+        assert_eq!(state, expected);
+        assert_eq!(state.len_recursive(), 7);
+    }
+
+    #[test]
+    fn merge_empty_ok() {
+        let mut state = merge_state(MERGE_BASE);
+        state.merge(&State::new(), true);
+        assert_eq!(state, merge_state(MERGE_BASE));
+
+        let mut state = State::new();
+        state.merge(&merge_state(MERGE_BASE), true);
+        assert_eq!(state, merge_state(MERGE_BASE));
+    }
+
+    #[test]
+    fn delete_non_map_access_err() {
+        let mut state = delete_state();
+
+        let res = state.delete(&"g.h".into());
+        assert!(matches!(res, Err(Error::NonMapAccess { path, field, .. }) if path == "g" && field == "h"));
+
+        let res = state.delete(&"a.b.first".into());
+        assert!(matches!(res, Err(Error::InvalidIndex { .. })));
+    }
+}
+
+#[cfg(test)]
+mod value_tests {
+    use super::*;
+
+    #[test]
+    fn test_truthy_ok() {
+        assert!(Value::BoolValue(true).truthy());
+        assert!(Value::IntValue(-1).truthy());
+        assert!(Value::FloatValue(0.5.into()).truthy());
+        assert!(Value::StringValue("false".into()).truthy());
+
+        assert!(!Value::None.truthy());
+        assert!(!Value::BoolValue(false).truthy());
+        assert!(!Value::IntValue(0).truthy());
+        assert!(!Value::FloatValue(0.0.into()).truthy());
+        assert!(!Value::StringValue("".into()).truthy());
+    }
+}
+
+#[cfg(test)]
+mod state_proptests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn segment() -> impl Strategy<Value = String> {
+        "[a-zA-Z0-9]{1,8}"
+    }
+
+    fn segments() -> impl Strategy<Value = Vec<String>> {
+        prop::collection::vec(segment(), 1..=5)
+    }
+
+    fn identifier() -> impl Strategy<Value = Identifier> {
+        segments().prop_map(|s| s.join(".").into())
+    }
+
+    fn value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            Just(Value::None),
+            any::<bool>().prop_map(Value::BoolValue),
+            any::<i64>().prop_map(Value::IntValue),
+            any::<f64>().prop_map(|f| Value::FloatValue(f.into())),
+            ".*".prop_map(Value::StringValue),
+        ]
+    }
+
+    fn item() -> impl Strategy<Value = Item> {
+        value().prop_map(Item::Value).prop_recursive(3, 32, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Item::Vec),
+                prop::collection::hash_map(segment(), inner, 0..4).prop_map(Item::Map),
+            ]
+        })
+    }
+
+    fn is_prefix(a: &[String], b: &[String]) -> bool {
+        a.len() <= b.len() && a.iter().zip(b).all(|(x, y)| x == y)
+    }
+
+    proptest! {
+        #[test]
+        fn get_returns_set_value(key in identifier(), v in item()) {
+            let mut state = State::new();
+            state.set(key.clone(), v.clone()).unwrap();
+
+            prop_assert_eq!(state.get(&key), Some(&v));
+        }
+
+        #[test]
+        fn set_does_not_affect_other_keys(k1 in segments(), k2 in segments(), v1 in item(), v2 in item()) {
+            prop_assume!(!is_prefix(&k1, &k2) && !is_prefix(&k2, &k1));
+            let k1: Identifier = k1.join(".").into();
+            let k2: Identifier = k2.join(".").into();
+
+            let mut state = State::new();
+            state.set(k1.clone(), v1.clone()).unwrap();
+            state.set(k2.clone(), v2.clone()).unwrap();
+
+            prop_assert_eq!(state.get(&k1), Some(&v1));
+            prop_assert_eq!(state.get(&k2), Some(&v2));
+        }
+
+        #[test]
+        fn set_overwrites_previous_value(key in identifier(), v1 in item(), v2 in item()) {
+            let mut state = State::new();
+            state.set(key.clone(), v1.clone()).unwrap();
+            let previous = state.set(key.clone(), v2.clone()).unwrap();
+
+            prop_assert_eq!(previous, Some(v1));
+            prop_assert_eq!(state.get(&key), Some(&v2));
+        }
+
+        #[test]
+        fn nested_keys_compose(
+            key in identifier(),
+            children in prop::collection::hash_map(segment(), item(), 1..4),
+            items in prop::collection::vec(item(), 1..4),
+        ) {
+            let mut state = State::new();
+            state.set(key.clone(), Item::Map(children.clone())).unwrap();
+            for (child, v) in &children {
+                let nested: Identifier = format!("{}.{}", key, child).into();
+                prop_assert_eq!(state.get(&nested), Some(v));
+            }
+
+            state.set(key.clone(), Item::Vec(items.clone())).unwrap();
+            for (idx, v) in items.iter().enumerate() {
+                let nested: Identifier = format!("{}.{}", key, idx).into();
+                prop_assert_eq!(state.get(&nested), Some(v));
+            }
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
@@ -447,13 +1213,88 @@ impl Item {
             Item::Map(_) => { "Map" }
         }
     }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Item::Value(v) => v.truthy(),
+            Item::Vec(v) => !v.is_empty(),
+            Item::Map(m) => !m.is_empty(),
+        }
+    }
+
+    pub fn flatten_into(&self, res: &mut HashMap<String, String>, key: Option<String>, separator: &str) {
+        let child_key = |k: String| match &key {
+            None => k,
+            Some(key) => format!("{}{}{}", key, separator, k),
+        };
+
+        match self {
+            Item::Value(v) => {
+                res.insert(key.unwrap_or_default(), v.to_string());
+            }
+            Item::Vec(v) => {
+                v.iter().enumerate().for_each(|(idx, item)| {
+                    item.flatten_into(res, Some(child_key(idx.to_string())), separator)
+                });
+            }
+            Item::Map(m) => {
+                m.iter().for_each(|(k, item)| {
+                    item.flatten_into(res, Some(child_key(k.clone())), separator)
+                });
+            }
+        }
+    }
+
+    pub fn unflatten(map: HashMap<String, Item>, separator: &str) -> Item {
+        let mut root = HashMap::new();
+
+        for (key, item) in map {
+            let mut segments = key.split(separator).collect::<Vec<_>>();
+            let last = segments.pop().unwrap_or_default();
+
+            let parent = segments.iter().fold(&mut root, |node, segment| {
+                let child = node
+                    .entry(segment.to_string())
+                    .or_insert_with(|| Item::Map(HashMap::new()));
+                if !matches!(child, Item::Map(_)) {
+                    *child = Item::Map(HashMap::new());
+                }
+                match child {
+                    Item::Map(m) => m,
+                    _ => unreachable!(),
+                }
+            });
+            parent.insert(last.to_string(), item);
+        }
+
+        Self::restore_arrays(Item::Map(root))
+    }
+
+    fn restore_arrays(item: Item) -> Item {
+        match item {
+            Item::Map(mut m) => {
+                let is_array = !m.is_empty() && (0..m.len()).all(|idx| m.contains_key(&idx.to_string()));
+                if is_array {
+                    Item::Vec((0..m.len())
+                        .map(|idx| Self::restore_arrays(m.remove(&idx.to_string()).unwrap()))
+                        .collect())
+                } else {
+                    Item::Map(m.into_iter().map(|(k, v)| (k, Self::restore_arrays(v))).collect())
+                }
+            }
+            Item::Vec(v) => Item::Vec(v.into_iter().map(Self::restore_arrays).collect()),
+            i => i,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 #[serde(untagged)]
 pub enum Value {
     None,
+    BoolValue(bool),
     IntValue(i64),
+    FloatValue(OrderedFloat<f64>),
     StringValue(String),
 }
 
@@ -461,10 +1302,34 @@ impl Value {
     pub fn type_name(&self) -> &str {
         match self {
             Value::None => { "None" }
+            Value::BoolValue(_) => { "Bool" }
             Value::IntValue(_) => { "Int" }
+            Value::FloatValue(_) => { "Float" }
             Value::StringValue(_) => { "String" }
         }
     }
+
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::None => false,
+            Value::BoolValue(b) => *b,
+            Value::IntValue(i) => *i != 0,
+            Value::FloatValue(v) => v.0 != 0.0,
+            Value::StringValue(s) => !s.is_empty(),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::None => Ok(()),
+            Value::BoolValue(b) => write!(f, "{}", b),
+            Value::IntValue(i) => write!(f, "{}", i),
+            Value::FloatValue(v) => write!(f, "{}", v),
+            Value::StringValue(s) => write!(f, "{}", s),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -473,10 +1338,10 @@ pub struct Identifier(String);
 impl Identifier {
     pub fn split(&self) -> (Option<String>, Option<Identifier>) {
         let mut iter = self.0.split(".");
-        let current = iter.next().map(|s| String::from(s));
+        let current = iter.next().map(String::from);
         let rest = iter.collect::<Vec<_>>().join(".");
 
-        (current, if rest.len() == 0 { None } else { Some(rest.into()) })
+        (current, if rest.is_empty() { None } else { Some(rest.into()) })
     }
 }
 
@@ -497,9 +1362,3 @@ impl From<&str> for Identifier {
         Identifier(String::from(s))
     }
 }
-
-impl From<std::num::ParseIntError> for Error {
-    fn from(e: ParseIntError) -> Self {
-        Error::InvalidIndex { reason: e.to_string() }
-    }
-}
\ No newline at end of file
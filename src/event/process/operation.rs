@@ -1,37 +1,503 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, RwLock};
 
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
 use serde::Deserialize;
 
 use crate::event::process;
 use crate::event::process::{Identifier, Item, State, Value};
-use crate::event::sender::Payload;
+use crate::event::process::csv::CsvOptions;
+use crate::event::process::xml::XmlOptions;
+use crate::event::sender::{EnvString, Payload};
+use crate::event::utils::sync::GracefulSignal;
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum Op {
     SetEnv { set_env: SetEnv },
     ToPayload { to_payload: ToPayload },
+    Split { split: SplitOp },
+    DropPayload { drop_payload: () },
+    RestorePayload { restore_payload: Expression },
+    SetFromPayloadField { set_from_payload_field: SetFromPayloadFieldOp },
+    CopyEnv { copy_env: CopyEnvOp },
+    DeleteEnv { delete_env: Identifier },
+    If {
+        #[serde(rename = "if")]
+        if_op: IfOp,
+    },
+    StringOp { string_op: StringOp },
+    Arithmetic { arithmetic: ArithmeticOp },
+    Regex { regex: RegexOp },
+    Base64 { base64: Base64Op },
+    Hmac { hmac: HmacOp },
+    GenerateUuid { generate_uuid: GenerateUuidOp },
+    Timestamp { timestamp: TimestampOp },
+    ArrayMap { array_map: ArrayMapOp },
+    ArrayFilter { array_filter: ArrayFilterOp },
+    ArrayLength { array_length: ArrayLengthOp },
+    ArrayAppend { array_append: ArrayPushOp },
+    ArrayPrepend { array_prepend: ArrayPushOp },
+    ArrayConcat { array_concat: ArrayConcatOp },
+    MergeMaps { merge_maps: MergeMapsOp },
+    ForEach { for_each: ForEachOp },
+    Sleep { sleep: SleepOp },
+    Log { log: LogOp },
+    DumpState { dump_state: DumpStateOp },
+    Encrypt { encrypt: CipherOp },
+    Decrypt { decrypt: CipherOp },
+    JwtDecode { jwt: JwtDecodeOp },
+    RenderTemplate { template: RenderTemplateOp },
+}
+
+/// Shared by every op of a pipeline, including the ones nested in `if`, `for_each` and `array_map`.
+pub struct Context<'a> {
+    pub pipeline: &'a str,
+    pub max_state_keys: Option<usize>,
+    pub graceful: GracefulSignal,
+}
+
+pub enum OpResult {
+    Single(Payload, State),
+    Multiple(Vec<(Payload, State)>),
 }
 
 impl Op {
-    pub fn execute(&self, payload: Payload, state: State) -> process::Result<(Payload, State)> {
+    pub async fn execute(&self, payload: Payload, state: State, ctx: &Context<'_>) -> process::Result<OpResult> {
         match self {
             Op::SetEnv { set_env } => {
                 let (value, payload, mut new_state) = set_env.value.evaluate(payload, state)?;
                 let idx = set_env.target.clone();
-                log::debug!("setting env with key {} as {:?}", idx, value);
+                if set_env.sensitive {
+                    new_state.mark_sensitive(&idx);
+                }
+                log::debug!("setting env with key {} as {}", idx, new_state.loggable(&idx, &value));
                 new_state.set(idx, value)?;
-                Ok((payload, new_state))
+                Ok(OpResult::Single(payload, new_state))
             }
             Op::ToPayload { to_payload } => {
                 let (item, _, state) = to_payload.value.evaluate(payload, state)?;
 
                 let item_bytes = to_payload.format.to_vec(&item)?;
-                let payload = Payload::new(item_bytes);
+                let payload = Payload::new(item_bytes).with_content_type(to_payload.format.content_type());
+
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::Split { split } => {
+                let (item, _, state) = split.source.evaluate(payload, state)?;
+
+                let items = match item {
+                    Item::Vec(items) => items,
+                    i => return Err(process::Error::UnexpectedType {
+                        expected: "Array".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
+
+                log::debug!("splitting payload into {} parts", items.len());
+                let format = split.format.clone().unwrap_or(PayloadFormat::Json);
+                let parts = items.iter()
+                    .map(|i| Ok((Payload::new(format.to_vec(i)?).with_content_type(format.content_type()), state.clone())))
+                    .collect::<process::Result<Vec<_>>>()?;
+
+                Ok(OpResult::Multiple(parts))
+            }
+            Op::DropPayload { drop_payload: () } => {
+                log::debug!("dropping payload with {} bytes", payload.content.len());
+                Ok(OpResult::Single(Payload::new(vec!()), state))
+            }
+            Op::RestorePayload { restore_payload } => {
+                let (item, _, state) = restore_payload.evaluate(payload, state)?;
+
+                let content = match item {
+                    Item::Value(Value::StringValue(s)) => s.into_bytes(),
+                    i => PayloadFormat::Json.to_vec(&i)?,
+                };
+
+                Ok(OpResult::Single(Payload::new(content), state))
+            }
+            Op::SetFromPayloadField { set_from_payload_field: op } => {
+                let item = op.format.parse_payload(&payload)?;
+                let value = State::get_from_child(Some(op.field.clone()), Some(&item))
+                    .cloned()
+                    .unwrap_or(Item::Value(Value::None));
+
+                let mut state = state;
+                log::debug!("setting env with key {} from payload field {} as {}", op.target, op.field, state.loggable(&op.target, &value));
+                state.set(op.target.clone(), value)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::CopyEnv { copy_env } => {
+                let value = state.get(&copy_env.from)
+                    .cloned()
+                    .unwrap_or(Item::Value(Value::None));
+
+                let mut state = state;
+                state.copy_sensitive(&copy_env.from, &copy_env.to);
+                log::debug!("copying env with key {} to {}", copy_env.from, copy_env.to);
+                state.set(copy_env.to.clone(), value)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::DeleteEnv { delete_env } => {
+                let mut state = state;
+                log::debug!("deleting env with key {}", delete_env);
+                state.delete(delete_env)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::If { if_op } => {
+                let (condition, payload, state) = if_op.condition.evaluate(payload, state)?;
+                let truthy = condition.is_truthy();
+                log::debug!("if condition evaluated to {}", truthy);
+
+                let ops = match (truthy, &if_op.else_ops) {
+                    (true, _) => &if_op.then_ops,
+                    (false, Some(else_ops)) => else_ops,
+                    (false, None) => return Ok(OpResult::Single(payload, state)),
+                };
+
+                let mut parts = execute_ops(ops, payload, state, ctx).await?;
+                if parts.len() == 1 {
+                    let (payload, state) = parts.remove(0);
+                    Ok(OpResult::Single(payload, state))
+                } else {
+                    Ok(OpResult::Multiple(parts))
+                }
+            }
+            Op::StringOp { string_op } => {
+                let args = string_op.args();
+                let (item, payload, mut state) = args.source.evaluate(payload, state)?;
+
+                let s = match item {
+                    Item::Value(Value::StringValue(s)) => s,
+                    i => return Err(process::Error::UnexpectedType {
+                        expected: "String".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
+
+                let value = string_op.apply(s);
+                log::debug!("setting env with key {} as {}", args.target, state.loggable(&args.target, &value));
+                state.set(args.target.clone(), value)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::Arithmetic { arithmetic } => {
+                let (left, payload, state) = arithmetic.left.evaluate(payload, state)?;
+                let (right, payload, mut state) = arithmetic.right.evaluate(payload, state)?;
+
+                let value = Item::Value(arithmetic.op.apply(left, right)?);
+                log::debug!("setting env with key {} as {}", arithmetic.target, state.loggable(&arithmetic.target, &value));
+                state.set(arithmetic.target.clone(), value)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::Regex { regex } => {
+                let (source, payload, mut state) = regex.source.evaluate(payload, state)?;
+                let source = match source {
+                    Item::Value(Value::StringValue(s)) => s,
+                    i => return Err(process::Error::UnexpectedType {
+                        expected: "String".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
+
+                let value = regex.apply(source.as_str());
+                log::debug!("setting env with key {} as {}", regex.target, state.loggable(&regex.target, &value));
+                state.set(regex.target.clone(), value)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::Base64 { base64 } => {
+                let (value, payload, mut state) = base64.value.evaluate(payload, state)?;
+                let value = match value {
+                    Item::Value(Value::StringValue(s)) => s,
+                    i => return Err(process::Error::UnexpectedType {
+                        expected: "String".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
+
+                let value = Item::Value(Value::StringValue(base64.apply(value)?));
+                log::debug!("setting env with key {} as {}", base64.target, state.loggable(&base64.target, &value));
+                state.set(base64.target.clone(), value)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::Hmac { hmac } => {
+                let (value, payload, mut state) = hmac.value.evaluate(payload, state)?;
+                let value = match value {
+                    Item::Value(Value::StringValue(s)) => s,
+                    i => return Err(process::Error::UnexpectedType {
+                        expected: "String".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
+                let key = hmac.key.to_string(&state)
+                    .ok_or_else(|| process::Error::HmacError("missing value for key".into()))?;
+
+                let signature = hmac.sign(key.as_bytes(), value.as_bytes())?;
+                log::debug!("setting env with key {} as hmac signature", hmac.target);
+                state.set(hmac.target.clone(), Item::Value(Value::StringValue(signature)))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::GenerateUuid { generate_uuid } => {
+                let id = match generate_uuid.version {
+                    UuidVersion::V4 => uuid::Uuid::new_v4(),
+                    UuidVersion::V7 => uuid::Uuid::now_v7(),
+                };
+
+                let mut state = state;
+                log::debug!("setting env with key {} as {}", generate_uuid.target, id);
+                state.set(generate_uuid.target.clone(), Item::Value(Value::StringValue(id.hyphenated().to_string())))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::Timestamp { timestamp } => {
+                let now = timestamp.now.format(chrono::Utc::now())?;
+
+                let mut state = state;
+                log::debug!("setting env with key {} as {}", timestamp.target, now);
+                state.set(timestamp.target.clone(), Item::Value(Value::StringValue(now)))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::ArrayMap { array_map } => {
+                let items = get_array(&state, &array_map.source)?;
+
+                // Every element runs against a copy of the current state; only the binding is collected.
+                let mut res = vec!();
+                for item in items {
+                    let mut item_state = state.clone();
+                    item_state.set(array_map.item_binding.clone(), item)?;
+
+                    for (_, s) in execute_ops(&array_map.ops, payload.clone(), item_state, ctx).await? {
+                        res.push(s.get(&array_map.item_binding).cloned().unwrap_or(Item::Value(Value::None)));
+                    }
+                }
+
+                let mut state = state;
+                log::debug!("setting env with key {} as array with {} items", array_map.target, res.len());
+                state.set(array_map.target.clone(), Item::Vec(res))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::ArrayFilter { array_filter } => {
+                let items = get_array(&state, &array_filter.source)?;
+
+                let mut res = vec!();
+                for item in items {
+                    let mut item_state = state.clone();
+                    item_state.set(array_filter.item_binding.clone(), item.clone())?;
+
+                    let (condition, _, _) = array_filter.condition.evaluate(payload.clone(), item_state)?;
+                    if condition.is_truthy() {
+                        res.push(item);
+                    }
+                }
+
+                let mut state = state;
+                log::debug!("setting env with key {} as array with {} items", array_filter.target, res.len());
+                state.set(array_filter.target.clone(), Item::Vec(res))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::ArrayLength { array_length } => {
+                let len = get_array(&state, &array_length.source)?.len();
+
+                let mut state = state;
+                log::debug!("setting env with key {} as {}", array_length.target, len);
+                state.set(array_length.target.clone(), Item::Value(Value::IntValue(len as i64)))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::ArrayAppend { array_append } => {
+                let (value, payload, mut state) = array_append.value.evaluate(payload, state)?;
+                log::debug!("appending {} to env with key {}", state.loggable(&array_append.source, &value), array_append.source);
+                state.push(&array_append.source, value)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::ArrayPrepend { array_prepend } => {
+                let (value, payload, mut state) = array_prepend.value.evaluate(payload, state)?;
+                log::debug!("prepending {} to env with key {}", state.loggable(&array_prepend.source, &value), array_prepend.source);
+                state.prepend(&array_prepend.source, value)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::ArrayConcat { array_concat } => {
+                let mut items = get_array(&state, &array_concat.left)?;
+                items.extend(get_array(&state, &array_concat.right)?);
+
+                let mut state = state;
+                log::debug!("setting env with key {} as array with {} items", array_concat.target, items.len());
+                state.set(array_concat.target.clone(), Item::Vec(items))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::MergeMaps { merge_maps } => {
+                let (base, payload, state) = merge_maps.base.evaluate(payload, state)?;
+                let (overlay, payload, mut state) = merge_maps.overlay.evaluate(payload, state)?;
+
+                let as_map = |item: Item| match item {
+                    Item::Map(map) => Ok(map),
+                    i => Err(process::Error::UnexpectedType {
+                        expected: "Map".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
+                let mut map = as_map(base)?;
+                process::merge_maps(&mut map, as_map(overlay)?, merge_maps.deep);
+
+                log::debug!("setting env with key {} as merged map with {} keys", merge_maps.target, map.len());
+                state.set(merge_maps.target.clone(), Item::Map(map))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::ForEach { for_each } => {
+                let items = get_array(&state, &for_each.source)?;
+                log::debug!("looping over {} items from env with key {}", items.len(), for_each.source);
+
+                // Unlike array_map, payload and state changes carry over from one iteration to the next.
+                let mut parts = vec!((payload, state));
+                for (idx, item) in items.into_iter().enumerate() {
+                    let mut next = vec!();
+                    for (payload, mut state) in parts {
+                        state.set(for_each.item_binding.clone(), item.clone())?;
+                        if let Some(index_binding) = &for_each.index_binding {
+                            state.set(index_binding.clone(), Item::Value(Value::IntValue(idx as i64)))?;
+                        }
+                        next.extend(execute_ops(&for_each.ops, payload, state, ctx).await?);
+                    }
+                    parts = next;
+                }
+
+                for (_, state) in parts.iter_mut() {
+                    state.delete(&for_each.item_binding)?;
+                    if let Some(index_binding) = &for_each.index_binding {
+                        state.delete(index_binding)?;
+                    }
+                }
+
+                if parts.len() == 1 {
+                    let (payload, state) = parts.remove(0);
+                    Ok(OpResult::Single(payload, state))
+                } else {
+                    Ok(OpResult::Multiple(parts))
+                }
+            }
+            Op::Log { log } => {
+                log::log!(target: log_target().as_str(), log.level.into(), "{}", log.render(&state));
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::DumpState { dump_state } => {
+                let dump = serde_json::to_string(&state.redacted())?;
+                log::log!(target: log_target().as_str(), dump_state.level.into(), "state: {}", dump);
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::Encrypt { encrypt } => {
+                log::debug!("encrypting payload with {} bytes", payload.content.len());
+                let content = encrypt.encrypt(&payload.content, &state)?;
+                Ok(OpResult::Single(Payload::new(content), state))
+            }
+            Op::Decrypt { decrypt } => {
+                log::debug!("decrypting payload with {} bytes", payload.content.len());
+                let content = decrypt.decrypt(&payload.content, &state)?;
+                Ok(OpResult::Single(Payload::new(content), state))
+            }
+            Op::JwtDecode { jwt } => {
+                let (token, payload, mut state) = jwt.token.evaluate(payload, state)?;
+                let token = match token {
+                    Item::Value(Value::StringValue(s)) => s,
+                    i => return Err(process::Error::UnexpectedType {
+                        expected: "String".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
 
-                Ok((payload, state))
+                let claims = jwt.decode(&token, &state)?;
+                log::debug!("setting env with key {} as jwt claims", jwt.target_claims);
+                state.set(jwt.target_claims.clone(), claims)?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::RenderTemplate { template } => {
+                let (context, payload, mut state) = template.context.evaluate(payload, state)?;
+                if !matches!(context, Item::Map(_)) {
+                    return Err(process::Error::UnexpectedType {
+                        expected: "Map".into(),
+                        t: context.type_name().into(),
+                    });
+                }
+
+                let rendered = template.render(&context, &state).await?;
+                log::debug!("setting env with key {} as rendered template with {} bytes", template.target, rendered.len());
+                state.set(template.target.clone(), Item::Value(Value::StringValue(rendered)))?;
+                Ok(OpResult::Single(payload, state))
+            }
+            Op::Sleep { sleep } => {
+                log::debug!("sleeping for {}ms", sleep.duration_ms);
+                tokio::select! {
+                    _ = tokio::time::sleep(tokio::time::Duration::from_millis(sleep.duration_ms)) => {
+                        Ok(OpResult::Single(payload, state))
+                    }
+                    _ = ctx.graceful.called() => {
+                        log::debug!("sleep interrupted by stop signal");
+                        Err(process::Error::Interrupted)
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Pipeline log ops are emitted under the pipeline name so they can be filtered per event.
+fn log_target() -> String {
+    crate::event::utils::logger::current_pipeline().unwrap_or_else(|| module_path!().to_string())
+}
+
+fn get_array(state: &State, key: &Identifier) -> process::Result<Vec<Item>> {
+    match state.get(key) {
+        Some(Item::Vec(items)) => Ok(items.clone()),
+        i => Err(process::Error::UnexpectedType {
+            expected: "Array".into(),
+            t: i.unwrap_or(&Item::Value(Value::None)).type_name().into(),
+        }),
+    }
+}
+
+/// Runs `ops` in order. Every part produced by a split runs the remaining ops on its own.
+pub fn execute_ops<'a>(
+    ops: &'a [Op],
+    payload: Payload,
+    state: State,
+    ctx: &'a Context<'a>,
+) -> BoxFuture<'a, process::Result<Vec<(Payload, State)>>> {
+    async move {
+        let (mut payload, mut state) = (payload, state);
+
+        for (idx, op) in ops.iter().enumerate() {
+            match op.execute(payload, state, ctx).await? {
+                OpResult::Single(new_payload, new_state) => {
+                    log::trace!("pipeline \"{}\" new state: {:?}", ctx.pipeline, new_state);
+                    check_state_size(ctx, &new_state)?;
+                    payload = new_payload;
+                    state = new_state;
+                }
+                OpResult::Multiple(parts) => {
+                    log::trace!("pipeline \"{}\" split into {} parts", ctx.pipeline, parts.len());
+                    parts.iter().try_for_each(|(_, state)| check_state_size(ctx, state))?;
+                    let rest = &ops[idx + 1..];
+                    let mut res = vec!();
+                    for (payload, state) in parts {
+                        res.extend(execute_ops(rest, payload, state, ctx).await?);
+                    }
+                    return Ok(res);
+                }
             }
         }
+
+        Ok(vec!((payload, state)))
+    }.boxed()
+}
+
+fn check_state_size(ctx: &Context<'_>, state: &State) -> process::Result<()> {
+    match ctx.max_state_keys {
+        Some(limit) if state.len_recursive() > limit => Err(process::Error::StateSizeExceeded {
+            limit,
+            actual: state.len_recursive(),
+        }),
+        _ => Ok(()),
     }
 }
 
@@ -42,8 +508,29 @@ mod op_tests {
 
     use super::*;
 
-    #[test]
-    fn test_set_env_ok() {
+    fn ctx() -> Context<'static> {
+        Context { pipeline: "test", max_state_keys: None, graceful: GracefulSignal::never() }
+    }
+
+    #[tokio::test]
+    async fn test_nested_ops_max_state_keys() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- set_env: { target: items, value: [1, 2] }
+- for_each:
+    source: items
+    item_binding: item
+    ops:
+      - set_env: { target: a, value: 1 }
+      - set_env: { target: b, value: 2 }
+"#).unwrap();
+        let ctx = Context { max_state_keys: Some(4), ..ctx() };
+
+        let res = execute_ops(&ops, crate::event::sender::Payload::new(vec![]), State::new(), &ctx).await;
+        assert!(matches!(res, Err(Error::StateSizeExceeded { limit: 4, actual: 5 })));
+    }
+
+    #[tokio::test]
+    async fn test_set_env_ok() {
         let mut state = State::new();
         let _ = state.set(Identifier::from("o"), Item::Value(Value::None));
 
@@ -53,24 +540,28 @@ mod op_tests {
 
         let op = Op::SetEnv {
             set_env: SetEnv {
-                target: key.clone().into(),
+                target: key.clone(),
                 value,
+                sensitive: false,
             },
         };
         let payload = crate::event::sender::Payload::new(vec![]);
 
-        let res = op.execute(payload, state);
+        let res = op.execute(payload, state, &ctx()).await;
         assert!(res.is_ok());
 
-        let (_, state) = res.unwrap();
+        let state = match res.unwrap() {
+            OpResult::Single(_, state) => state,
+            OpResult::Multiple(_) => unreachable!(),
+        };
 
         assert_eq!(state.len(), 2);
         assert!(state.get(&key).is_some());
         assert_eq!(state.get(&key).unwrap(), &item);
     }
 
-    #[test]
-    fn test_to_payload_ok() {
+    #[tokio::test]
+    async fn test_to_payload_ok() {
         let mut state = State::new();
         let item = Item::Value(Value::IntValue(123));
         let value = Box::new(Expression::Item(item.clone()));
@@ -85,230 +576,2876 @@ mod op_tests {
         };
         let payload = crate::event::sender::Payload::new(vec![]);
 
-        let res = op.execute(payload, state);
+        let res = op.execute(payload, state, &ctx()).await;
         assert!(res.is_ok());
 
-        let (payload, _) = res.unwrap();
-        assert!(payload.content.len() > 0);
+        let payload = match res.unwrap() {
+            OpResult::Single(payload, _) => payload,
+            OpResult::Multiple(_) => unreachable!(),
+        };
+        assert!(!payload.content.is_empty());
         assert_eq!(payload.content, "123".as_bytes());
+        assert_eq!(payload.content_type.as_deref(), Some("application/json"));
     }
-}
 
-#[derive(Deserialize, Debug, Clone)]
-#[serde(untagged)]
-pub enum Expression {
-    SetEnv { set_env: SetEnv },
-    GetEnv { get_env: Identifier },
-    FromJson { from_json: String },
-    FromPayload { from_payload: PayloadFormat },
-    AsMap { as_map: HashMap<String, Expression> },
-    Item(Item),
-}
+    #[tokio::test]
+    async fn test_split_ok() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("o"), Item::Value(Value::None));
 
-impl Expression {
-    pub fn evaluate(
-        &self,
-        payload: Payload,
-        state: State,
-    ) -> process::Result<(Item, Payload, State)> {
-        match self {
-            Expression::SetEnv { set_env } => {
-                let (value, payload, mut new_state) = set_env.value.evaluate(payload, state)?;
-                let idx = set_env.target.clone();
-                log::trace!("setting env with key {} as {:?}", idx, value);
-                new_state.set(idx, value.clone())?;
-                Ok((value, payload, new_state))
-            }
-            Expression::GetEnv { get_env } => {
-                let value = state.get(&get_env);
-                let item = value
-                    .and_then(|o| Some(o.clone()))
-                    .unwrap_or(Item::Value(Value::None));
-                Ok((item, payload, state))
-            }
-            Expression::FromPayload {
-                from_payload: format,
-            } => {
-                let item = format.parse_payload(&payload)?;
-                Ok((item, payload, state))
-            }
-            Expression::Item(i) => Ok((i.clone(), payload, state)),
-            Expression::FromJson { .. } => {
-                unimplemented!()
-            }
-            Expression::AsMap { as_map: map } => {
-                let (map, payload, state) = map.iter().fold(
-                    Ok((HashMap::new(), payload, state)),
-                    |acc: process::Result<_>, (key, expr)| {
-                        let (mut acc, payload, state) = acc?;
-                        let (item, payload, state) = expr.evaluate(payload, state)?;
-                        acc.insert(key.clone(), item);
-                        Ok((acc, payload, state))
-                    },
-                )?;
+        let op = Op::Split {
+            split: SplitOp {
+                source: Expression::FromPayload {
+                    from_payload: PayloadFormat::Json,
+                },
+                format: None,
+            },
+        };
+        let payload = crate::event::sender::Payload::new("[1, \"a\", {\"b\": 2}]".into());
 
-                Ok((Item::Map(map), payload, state))
-            }
-        }
-    }
-}
+        let res = op.execute(payload, state.clone(), &ctx()).await;
+        assert!(res.is_ok());
 
-#[cfg(test)]
-mod expression_tests {
-    use crate::event::process::operation::SetEnv;
-    use crate::event::process::*;
+        let parts = match res.unwrap() {
+            OpResult::Multiple(parts) => parts,
+            OpResult::Single(..) => unreachable!(),
+        };
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].0.content, "1".as_bytes());
+        assert_eq!(parts[1].0.content, "\"a\"".as_bytes());
+        assert_eq!(parts[2].0.content, "{\"b\":2}".as_bytes());
+        assert!(parts.iter().all(|(_, s)| s == &state));
+    }
 
-    use super::*;
+    #[tokio::test]
+    async fn test_drop_and_restore_payload_ok() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- drop_payload:
+- restore_payload:
+    get_env: saved
+"#).unwrap();
 
-    #[test]
-    fn test_set_env_ok() {
         let mut state = State::new();
-        let _ = state.set(Identifier::from("o"), Item::Value(Value::None));
+        let _ = state.set(Identifier::from("saved"), Item::Value(Value::StringValue("otter".into())));
+        let payload = crate::event::sender::Payload::new("large payload".into());
 
-        let key = Identifier::from("key");
-        let item = Item::Value(Value::IntValue(123));
-        let value = Box::new(Expression::Item(item.clone()));
-
-        let exp = Expression::SetEnv {
-            set_env: SetEnv {
-                target: key.clone().into(),
-                value,
-            },
+        let (payload, state) = match ops[0].execute(payload, state, &ctx()).await {
+            Ok(OpResult::Single(payload, state)) => (payload, state),
+            _ => unreachable!(),
         };
-        let payload = crate::event::sender::Payload::new(vec![]);
+        assert!(payload.content.is_empty());
 
-        let res = exp.evaluate(payload, state);
-        assert!(res.is_ok());
+        let payload = match ops[1].execute(payload, state, &ctx()).await {
+            Ok(OpResult::Single(payload, _)) => payload,
+            _ => unreachable!(),
+        };
+        assert_eq!(payload.content, "otter".as_bytes());
+    }
 
-        let (ret_item, _, state) = res.unwrap();
+    #[tokio::test]
+    async fn test_set_from_payload_field_ok() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- set_from_payload_field: { format: json, field: user.name, target: name }
+- set_from_payload_field: { format: json, field: tags.1, target: meta.tag }
+- set_from_payload_field: { format: json, field: user.email, target: email }
+"#).unwrap();
 
-        assert_eq!(state.len(), 2);
-        assert!(state.get(&key).is_some());
-        assert_eq!(state.get(&key).unwrap(), &item);
+        let payload = crate::event::sender::Payload::new(r#"{"user": {"name": "otter"}, "tags": ["a", "b"]}"#.into());
+        let (payload, state) = run(&ops, payload, State::new()).await;
 
-        assert_eq!(ret_item, item);
+        let string = |s: &str| Item::Value(Value::StringValue(s.into()));
+        assert_eq!(state.get(&Identifier::from("name")), Some(&string("otter")));
+        assert_eq!(state.get(&Identifier::from("meta.tag")), Some(&string("b")));
+        assert_eq!(state.get(&Identifier::from("email")), Some(&Item::Value(Value::None)));
+        assert_eq!(payload.content, r#"{"user": {"name": "otter"}, "tags": ["a", "b"]}"#.as_bytes());
     }
 
-    #[test]
-    fn test_get_env_ok() {
-        let mut state = State::new();
-        let key = Identifier::from("key");
-        let item = Item::Value(Value::IntValue(123));
+    #[tokio::test]
+    async fn test_set_from_payload_field_invalid_payload_err() {
+        let op: Op = serde_yaml::from_str("set_from_payload_field: { format: json, field: a, target: b }").unwrap();
+        let payload = crate::event::sender::Payload::new("not json".into());
 
-        let _ = state.set(key.clone(), item.clone());
+        let res = op.execute(payload, State::new(), &ctx()).await;
+        assert!(matches!(res, Err(Error::SerializationError { format, .. }) if format == "json"));
 
-        let exp = Expression::GetEnv {
-            get_env: key.clone().into(),
+        let op: Op = serde_yaml::from_str("set_from_payload_field: { format: yaml, field: a, target: b }").unwrap();
+        let payload = crate::event::sender::Payload::new("a: [b".into());
+
+        let res = op.execute(payload, State::new(), &ctx()).await;
+        assert!(matches!(res, Err(Error::SerializationError { format, .. }) if format == "yaml"));
+    }
+
+    #[tokio::test]
+    async fn test_copy_env_ok() {
+        let copy = |from: &str, to: &str, state: State| {
+            let op: Op = serde_yaml::from_str(format!("copy_env: {{ from: {}, to: {} }}", from, to).as_str()).unwrap();
+            async move {
+                match op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await {
+                    Ok(OpResult::Single(_, state)) => state,
+                    _ => unreachable!(),
+                }
+            }
         };
-        let payload = crate::event::sender::Payload::new(vec![]);
+        let get = |state: &State, key: &str| state.get(&Identifier::from(key)).cloned();
 
-        let res = exp.evaluate(payload, state);
-        assert!(res.is_ok());
+        let mut state = State::new();
+        let item = Item::Value(Value::StringValue("otter".into()));
+        let _ = state.set(Identifier::from("a.b"), item.clone());
+        let _ = state.set(Identifier::from("c"), Item::Value(Value::IntValue(1)));
 
-        let (ret_item, _, state) = res.unwrap();
+        let state = copy("a.b", "d", state).await;
+        assert_eq!(get(&state, "a.b"), Some(item.clone()));
+        assert_eq!(get(&state, "d"), Some(item.clone()));
 
-        assert_eq!(state.len(), 1);
-        assert!(state.get(&key).is_some());
-        assert_eq!(state.get(&key).unwrap(), &item);
+        let state = copy("missing", "e", state).await;
+        assert_eq!(get(&state, "e"), Some(Item::Value(Value::None)));
 
-        assert_eq!(ret_item, item);
+        let state = copy("a.b", "c", state).await;
+        assert_eq!(get(&state, "c"), Some(item));
     }
 
-    #[test]
-    fn test_item_ok() {
-        let state = State::new();
+    #[tokio::test]
+    async fn test_delete_env_ok() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- set_env: { target: a.b, value: 1 }
+- set_env: { target: a.c, value: 2 }
+- delete_env: a.b
+- delete_env: missing
+"#).unwrap();
 
-        let item = Item::Value(Value::IntValue(123));
-        let exp = Expression::Item(item.clone());
-        let payload = crate::event::sender::Payload::new(vec![]);
+        let payload = crate::event::sender::Payload::new("hello".into());
+        let (payload, state) = run(&ops, payload, State::new()).await;
 
-        let res = exp.evaluate(payload, state);
-        assert!(res.is_ok());
+        assert_eq!(state.get(&Identifier::from("a.b")), None);
+        assert_eq!(state.get(&Identifier::from("a.c")), Some(&Item::Value(Value::IntValue(2))));
+        assert_eq!(payload.content, "hello".as_bytes());
+    }
 
-        let (ret_item, _, state) = res.unwrap();
+    #[tokio::test]
+    async fn test_delete_env_non_map_access_err() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("a"), Item::Value(Value::IntValue(1)));
 
-        assert_eq!(state.len(), 0);
-        assert_eq!(ret_item, item);
+        let op: Op = serde_yaml::from_str("delete_env: a.b").unwrap();
+        let res = op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await;
+        assert!(matches!(res, Err(Error::NonMapAccess { .. })));
     }
 
-    #[test]
-    fn test_as_map_ok() {
-        let env_id = Identifier("id".into());
-        let env_value = Item::Value(Value::StringValue("test".into()));
-        let state = {
-            let mut state = State::new();
+    async fn string_op(op: &str, value: &str) -> process::Result<Item> {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("input"), serde_yaml::from_str(value).unwrap());
 
-            let _ = state.set(env_id.clone(), env_value.clone());
+        let op: Op = serde_yaml::from_str(op).unwrap();
+        match op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await? {
+            OpResult::Single(_, state) => Ok(state.get(&Identifier::from("output")).cloned().unwrap()),
+            OpResult::Multiple(_) => unreachable!(),
+        }
+    }
 
-            state
-        };
+    fn string(s: &str) -> Item {
+        Item::Value(Value::StringValue(s.into()))
+    }
 
-        let new_item = Item::Value(Value::IntValue(123));
-        let to_env_id = Identifier("to_id".into());
-        let to_env_item = Item::Value(Value::IntValue(123));
+    #[tokio::test]
+    async fn test_string_op_upper_ok() {
+        let res = string_op("string_op: { upper: { source: { get_env: input }, target: output } }", "Otter@Mail.com").await;
+        assert_eq!(res.unwrap(), string("OTTER@MAIL.COM"));
+    }
 
-        let map = {
-            let mut res = HashMap::new();
+    #[tokio::test]
+    async fn test_string_op_lower_ok() {
+        let res = string_op("string_op: { lower: { source: { get_env: input }, target: output } }", "Otter@Mail.com").await;
+        assert_eq!(res.unwrap(), string("otter@mail.com"));
+    }
 
-            res.insert(
-                String::from("from_env"),
-                Expression::GetEnv {
-                    get_env: env_id.clone(),
-                },
-            );
-            res.insert(String::from("value"), Expression::Item(new_item.clone()));
-            res.insert(
-                String::from("to_env"),
-                Expression::SetEnv {
-                    set_env: SetEnv {
-                        target: to_env_id.clone(),
-                        value: Box::new(Expression::Item(to_env_item.clone())),
-                    },
-                },
-            );
+    #[tokio::test]
+    async fn test_string_op_trim_ok() {
+        let res = string_op("string_op: { trim: { source: { get_env: input }, target: output } }", "\"  otter \\n\"").await;
+        assert_eq!(res.unwrap(), string("otter"));
+    }
 
-            res
-        };
-        let exp = Expression::AsMap { as_map: map };
-        let payload = crate::event::sender::Payload::new(vec![]);
+    #[tokio::test]
+    async fn test_string_op_split_ok() {
+        let op = "string_op: { split: { source: { get_env: input }, delimiter: \",\", target: output } }";
 
-        let exp_res = exp.evaluate(payload, state);
-        assert!(exp_res.is_ok());
-        let (item, _, state) = exp_res.unwrap();
+        let res = string_op(op, "a,b,,c").await;
+        assert_eq!(res.unwrap(), Item::Vec(vec!(string("a"), string("b"), string(""), string("c"))));
 
-        assert!(matches!(item, Item::Map(_)));
-        let map = match item {
-            Item::Map(m) => m,
-            _ => unreachable!(),
-        };
+        let res = string_op(op, "abc").await;
+        assert_eq!(res.unwrap(), Item::Vec(vec!(string("abc"))));
+    }
 
-        assert_eq!(map.len(), 3);
+    #[tokio::test]
+    async fn test_string_op_replace_ok() {
+        let op = "string_op: { replace: { source: { get_env: input }, from: \"-\", to: _, target: output } }";
+        let res = string_op(op, "a-b-c").await;
+        assert_eq!(res.unwrap(), string("a_b_c"));
+    }
+
+    #[tokio::test]
+    async fn test_string_op_literal_source_ok() {
+        let res = string_op("string_op: { upper: { source: otter, target: output } }", "~").await;
+        assert_eq!(res.unwrap(), string("OTTER"));
+    }
+
+    #[tokio::test]
+    async fn test_string_op_non_string_err() {
+        for value in ["123", "true", "[a]", "{a: b}", "~"] {
+            let res = string_op("string_op: { trim: { source: { get_env: input }, target: output } }", value).await;
+            assert!(matches!(res, Err(Error::UnexpectedType { .. })), "{}", value);
+        }
+    }
+
+    async fn arithmetic(left: &str, op: &str, right: &str) -> process::Result<Item> {
+        let op: Op = serde_yaml::from_str(format!(
+            "arithmetic: {{ left: {}, op: {}, right: {}, target: output }}", left, op, right,
+        ).as_str()).unwrap();
+
+        match op.execute(crate::event::sender::Payload::new(vec![]), State::new(), &ctx()).await? {
+            OpResult::Single(_, state) => Ok(state.get(&Identifier::from("output")).cloned().unwrap()),
+            OpResult::Multiple(_) => unreachable!(),
+        }
+    }
+
+    fn int(i: i64) -> Item {
+        Item::Value(Value::IntValue(i))
+    }
+
+    fn float(f: f64) -> Item {
+        Item::Value(Value::FloatValue(f.into()))
+    }
+
+    #[tokio::test]
+    async fn test_arithmetic_int_ok() {
+        assert_eq!(arithmetic("7", "add", "2").await.unwrap(), int(9));
+        assert_eq!(arithmetic("7", "sub", "2").await.unwrap(), int(5));
+        assert_eq!(arithmetic("7", "mul", "2").await.unwrap(), int(14));
+        assert_eq!(arithmetic("7", "div", "2").await.unwrap(), int(3));
+        assert_eq!(arithmetic("7", "mod", "2").await.unwrap(), int(1));
+        assert_eq!(arithmetic("-7", "div", "2").await.unwrap(), int(-3));
+    }
+
+    #[tokio::test]
+    async fn test_arithmetic_float_ok() {
+        assert_eq!(arithmetic("7.5", "add", "2").await.unwrap(), float(9.5));
+        assert_eq!(arithmetic("7", "sub", "2.5").await.unwrap(), float(4.5));
+        assert_eq!(arithmetic("1.5", "mul", "2.0").await.unwrap(), float(3.0));
+        assert_eq!(arithmetic("7", "div", "2.0").await.unwrap(), float(3.5));
+        assert_eq!(arithmetic("7.5", "mod", "2").await.unwrap(), float(1.5));
+    }
+
+    #[tokio::test]
+    async fn test_arithmetic_from_state_ok() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("order.price"), int(250));
+        let _ = state.set(Identifier::from("order.quantity"), int(4));
+
+        let op: Op = serde_yaml::from_str(r#"
+arithmetic:
+  left: { get_env: order.price }
+  op: mul
+  right: { get_env: order.quantity }
+  target: order.total
+"#).unwrap();
+        let (_, state) = run(&[op], crate::event::sender::Payload::new(vec![]), state).await;
+
+        assert_eq!(state.get(&Identifier::from("order.total")), Some(&int(1000)));
+    }
+
+    #[tokio::test]
+    async fn test_arithmetic_division_by_zero_err() {
+        for (left, op, right) in [("1", "div", "0"), ("1", "mod", "0"), ("1.0", "div", "0"), ("1", "div", "0.0")] {
+            let res = arithmetic(left, op, right).await;
+            assert!(matches!(res, Err(Error::DivisionByZero)), "{} {} {}", left, op, right);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_arithmetic_overflow_err() {
+        let (max, min) = (i64::MAX.to_string(), i64::MIN.to_string());
+        for (left, op, right) in [
+            (max.as_str(), "add", "1"),
+            (min.as_str(), "sub", "1"),
+            (max.as_str(), "mul", "2"),
+            (min.as_str(), "div", "-1"),
+            (min.as_str(), "mod", "-1"),
+            ("1.0e308", "mul", "10"),
+        ] {
+            let res = arithmetic(left, op, right).await;
+            assert!(matches!(res, Err(Error::ArithmeticOverflow)), "{} {} {}", left, op, right);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_arithmetic_non_numeric_err() {
+        for (left, right) in [("a", "1"), ("1", "true"), ("~", "1"), ("[1]", "1")] {
+            let res = arithmetic(left, "add", right).await;
+            assert!(matches!(res, Err(Error::UnexpectedType { .. })), "{} {}", left, right);
+        }
+    }
+
+    async fn regex(mode: &str, pattern: &str, value: &str) -> Item {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("input"), Item::Value(Value::StringValue(value.into())));
+
+        let op: Op = serde_yaml::from_str(format!(
+            "regex: {{ source: {{ get_env: input }}, pattern: '{}', target: output, mode: {} }}", pattern, mode,
+        ).as_str()).unwrap();
+        let (_, state) = run(&[op], crate::event::sender::Payload::new(vec![]), state).await;
+        state.get(&Identifier::from("output")).cloned().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_regex_matches_ok() {
+        assert_eq!(regex("matches", r"^\w+@example\.com$", "otter@example.com").await, Item::Value(Value::BoolValue(true)));
+        assert_eq!(regex("matches", r"^\w+@example\.com$", "otter@example.org").await, Item::Value(Value::BoolValue(false)));
+    }
+
+    #[tokio::test]
+    async fn test_regex_capture_all_ok() {
+        let item = regex("capture_all", r"#\w+", "#rust and #otters").await;
+        assert_eq!(item, Item::Vec(vec!(
+            Item::Value(Value::StringValue("#rust".into())),
+            Item::Value(Value::StringValue("#otters".into())),
+        )));
+
+        assert_eq!(regex("capture_all", r"#\w+", "none").await, Item::Vec(vec!()));
+    }
+
+    #[tokio::test]
+    async fn test_regex_capture_named_ok() {
+        let item = regex("capture_named", r"^(?P<user>\w+)@(?P<domain>[\w.]+)(?P<port>:\d+)?$", "otter@example.com").await;
+
+        let mut map = HashMap::new();
+        map.insert("user".to_string(), Item::Value(Value::StringValue("otter".into())));
+        map.insert("domain".to_string(), Item::Value(Value::StringValue("example.com".into())));
+        map.insert("port".to_string(), Item::Value(Value::None));
+        assert_eq!(item, Item::Map(map));
+    }
+
+    #[tokio::test]
+    async fn test_regex_capture_named_no_match_ok() {
+        let item = regex("capture_named", r"^(?P<user>\w+)@", "otter").await;
+        assert_eq!(item, Item::Value(Value::None));
+    }
+
+    #[tokio::test]
+    async fn test_regex_non_string_err() {
+        let op: Op = serde_yaml::from_str("regex: { source: 1, pattern: a, target: output, mode: matches }").unwrap();
+        let res = op.execute(crate::event::sender::Payload::new(vec![]), State::new(), &ctx()).await;
+        assert!(matches!(res, Err(Error::UnexpectedType { .. })));
+    }
+
+    #[test]
+    fn test_regex_invalid_pattern_err() {
+        assert!(matches!(RegexPattern::new("(unclosed"), Err(Error::InvalidRegex { .. })));
+
+        let res = serde_yaml::from_str::<Op>("regex: { source: a, pattern: '(unclosed', target: output, mode: matches }");
+        assert!(res.is_err());
+    }
+
+    async fn base64(mode: &str, alphabet: &str, value: &str) -> process::Result<Item> {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("input"), Item::Value(Value::StringValue(value.into())));
+
+        let op: Op = serde_yaml::from_str(format!(
+            "base64: {{ value: {{ get_env: input }}, target: output, mode: {}, alphabet: {} }}", mode, alphabet,
+        ).as_str()).unwrap();
+        match op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await? {
+            OpResult::Single(_, state) => Ok(state.get(&Identifier::from("output")).cloned().unwrap()),
+            OpResult::Multiple(_) => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_base64_alphabets_ok() {
+        // "??>" encodes to bytes that differ between the standard and url-safe alphabets
+        let cases = [("standard", "Pz8+"), ("url_safe", "Pz8-"), ("url_safe_no_padding", "Pz8-")];
+        for (alphabet, encoded) in cases.iter() {
+            assert_eq!(base64("encode", alphabet, "??>").await.unwrap(), string(encoded), "{}", alphabet);
+            assert_eq!(base64("decode", alphabet, encoded).await.unwrap(), string("??>"), "{}", alphabet);
+        }
+
+        assert_eq!(base64("encode", "standard", "otter").await.unwrap(), string("b3R0ZXI="));
+        assert_eq!(base64("encode", "url_safe", "otter").await.unwrap(), string("b3R0ZXI="));
+        assert_eq!(base64("encode", "url_safe_no_padding", "otter").await.unwrap(), string("b3R0ZXI"));
+    }
+
+    #[tokio::test]
+    async fn test_base64_round_trip_ok() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- base64: { value: { get_env: input }, target: encoded, mode: encode }
+- base64: { value: { get_env: encoded }, target: decoded, mode: decode }
+"#).unwrap();
+
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("input"), string("héllo, wörld!"));
+        let (_, state) = run(&ops, crate::event::sender::Payload::new(vec![]), state).await;
+
+        assert_eq!(state.get(&Identifier::from("decoded")), Some(&string("héllo, wörld!")));
+    }
+
+    #[tokio::test]
+    async fn test_base64_decode_err() {
+        for (alphabet, value) in [("standard", "not base64!"), ("standard", "Pz8-"), ("url_safe", "Pz8+"), ("standard", "/w==")].iter() {
+            let res = base64("decode", alphabet, value).await;
+            assert!(matches!(res, Err(Error::Base64DecodeError(_))), "{} {}", alphabet, value);
+        }
+    }
+
+    async fn hmac(key: &str, algorithm: &str, encoding: &str, value: &str) -> process::Result<Item> {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("input"), Item::Value(Value::StringValue(value.into())));
+        let _ = state.set(Identifier::from("secret"), Item::Value(Value::StringValue(key.into())));
+
+        let op: Op = serde_yaml::from_str(format!(
+            "hmac: {{ value: {{ get_env: input }}, key: {{ from_env: secret }}, algorithm: {}, encoding: {}, target: output }}",
+            algorithm, encoding,
+        ).as_str()).unwrap();
+        match op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await? {
+            OpResult::Single(_, state) => Ok(state.get(&Identifier::from("output")).cloned().unwrap()),
+            OpResult::Multiple(_) => unreachable!(),
+        }
+    }
+
+    // Test vectors from RFC 4231, section 4.
+    #[tokio::test]
+    async fn test_hmac_rfc4231_ok() {
+        let cases = [
+            (
+                "\x0b".repeat(20), "Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+                "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+            ),
+            (
+                "Jefe".to_string(), "what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            ),
+        ];
+
+        for (key, data, sha256, sha512) in cases.iter() {
+            assert_eq!(hmac(key, "sha256", "hex", data).await.unwrap(), string(sha256));
+            assert_eq!(hmac(key, "sha512", "hex", data).await.unwrap(), string(sha512));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hmac_base64_literal_key_ok() {
+        let op: Op = serde_yaml::from_str(
+            "hmac: { value: what do ya want for nothing?, key: Jefe, algorithm: sha256, encoding: base64, target: output }",
+        ).unwrap();
+        let (_, state) = run(&[op], crate::event::sender::Payload::new(vec![]), State::new()).await;
+
+        let expected = base64::encode(hex::decode("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843").unwrap());
+        assert_eq!(state.get(&Identifier::from("output")), Some(&string(expected.as_str())));
+    }
+
+    #[tokio::test]
+    async fn test_hmac_missing_key_err() {
+        let op: Op = serde_yaml::from_str(
+            "hmac: { value: a, key: { from_env: missing }, algorithm: sha256, encoding: hex, target: output }",
+        ).unwrap();
+        let res = op.execute(crate::event::sender::Payload::new(vec![]), State::new(), &ctx()).await;
+        assert!(matches!(res, Err(Error::HmacError(_))));
+    }
+
+    async fn generate_uuid(version: &str) -> String {
+        let op: Op = serde_yaml::from_str(format!("generate_uuid: {{ target: id, version: {} }}", version).as_str()).unwrap();
+        let (_, state) = run(&[op], crate::event::sender::Payload::new(vec![]), State::new()).await;
+
+        match state.get(&Identifier::from("id")) {
+            Some(Item::Value(Value::StringValue(s))) => s.clone(),
+            i => panic!("unexpected item {:?}", i),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_uuid_v4_ok() {
+        let re = regex::Regex::new("^[0-9a-f]{8}-[0-9a-f]{4}-4[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$").unwrap();
+
+        let (a, b) = (generate_uuid("v4").await, generate_uuid("v4").await);
+        assert!(re.is_match(a.as_str()), "{}", a);
+        assert!(re.is_match(b.as_str()), "{}", b);
+        assert_ne!(a, b);
+    }
+
+    #[tokio::test]
+    async fn test_generate_uuid_v7_ok() {
+        let re = regex::Regex::new("^[0-9a-f]{8}-[0-9a-f]{4}-7[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$").unwrap();
+
+        let mut ids = vec!();
+        for _ in 0..100 {
+            ids.push(generate_uuid("v7").await);
+        }
+
+        ids.iter().for_each(|id| assert!(re.is_match(id.as_str()), "{}", id));
+        ids.windows(2).for_each(|w| assert!(w[0] < w[1], "{} >= {}", w[0], w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_generate_uuid_default_version_ok() {
+        let op: Op = serde_yaml::from_str("generate_uuid: { target: id }").unwrap();
+        assert!(matches!(op, Op::GenerateUuid { generate_uuid: GenerateUuidOp { version: UuidVersion::V4, .. } }));
+    }
+
+    fn now(yaml: &str) -> NowExpr {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn fixed_time() -> chrono::DateTime<chrono::Utc> {
+        use chrono::TimeZone;
+        chrono::Utc.with_ymd_and_hms(2021, 9, 5, 22, 30, 15).unwrap() + chrono::Duration::milliseconds(42)
+    }
+
+    #[test]
+    fn test_now_utc_ok() {
+        assert_eq!(now("{}").format(fixed_time()).unwrap(), "2021-09-05T22:30:15.042Z");
+        assert_eq!(now("timezone: UTC").format(fixed_time()).unwrap(), "2021-09-05T22:30:15.042+00:00");
+    }
+
+    #[test]
+    fn test_now_named_timezone_ok() {
+        let res = now("{ format: \"%Y-%m-%d %H:%M %:z\", timezone: Asia/Jakarta }").format(fixed_time());
+        assert_eq!(res.unwrap(), "2021-09-06 05:30 +07:00");
+        assert_eq!(now("timezone: Asia/Jakarta").format(fixed_time()).unwrap(), "2021-09-06T05:30:15.042+07:00");
+    }
+
+    #[test]
+    fn test_now_custom_format_ok() {
+        assert_eq!(now("format: \"%d/%m/%Y\"").format(fixed_time()).unwrap(), "05/09/2021");
+        assert_eq!(now("format: \"%s\"").format(fixed_time()).unwrap(), "1630881015");
+    }
+
+    #[test]
+    fn test_now_err() {
+        assert!(matches!(now("timezone: Mars/Olympus").format(fixed_time()), Err(Error::InvalidTimezone(_))));
+        assert!(matches!(now("format: \"%Q\"").format(fixed_time()), Err(Error::InvalidTimeFormat(_))));
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_ok() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- timestamp: { target: received_at }
+- set_env:
+    target: event
+    value:
+      as_map:
+        at: { now: { format: "%Y" } }
+"#).unwrap();
+        let (_, state) = run(&ops, crate::event::sender::Payload::new(vec![]), State::new()).await;
+
+        let re = regex::Regex::new(r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d{3}Z$").unwrap();
+        match state.get(&Identifier::from("received_at")) {
+            Some(Item::Value(Value::StringValue(s))) => assert!(re.is_match(s), "{}", s),
+            i => panic!("unexpected item {:?}", i),
+        }
+        match state.get(&Identifier::from("event.at")) {
+            Some(Item::Value(Value::StringValue(s))) => assert_eq!(s.len(), 4),
+            i => panic!("unexpected item {:?}", i),
+        }
+    }
+
+    async fn array_op(op: &str, items: &str) -> Item {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("items"), serde_yaml::from_str(items).unwrap());
+
+        let op: Op = serde_yaml::from_str(op).unwrap();
+        let (payload, state) = run(&[op], crate::event::sender::Payload::new("original".into()), state).await;
+
+        assert_eq!(payload.content, "original".as_bytes());
+        assert_eq!(state.get(&Identifier::from("item")), None);
+        state.get(&Identifier::from("output")).cloned().unwrap()
+    }
+
+    const ARRAY_MAP: &str = r#"
+array_map:
+  source: items
+  item_binding: item
+  ops:
+    - arithmetic: { left: { get_env: item.price }, op: mul, right: 2, target: item.price }
+    - delete_env: item.name
+  target: output
+"#;
+
+    #[tokio::test]
+    async fn test_array_map_ok() {
+        let item = array_op(ARRAY_MAP, "[{name: a, price: 1}, {name: b, price: 5}]").await;
+        assert_eq!(item, serde_yaml::from_str("[{price: 2}, {price: 10}]").unwrap());
+
+        let item = array_op(ARRAY_MAP, "[{name: a, price: 3}]").await;
+        assert_eq!(item, serde_yaml::from_str("[{price: 6}]").unwrap());
+
+        let item = array_op(ARRAY_MAP, "[]").await;
+        assert_eq!(item, Item::Vec(vec!()));
+    }
+
+    const ARRAY_FILTER: &str = r#"
+array_filter:
+  source: items
+  item_binding: item
+  condition: { compare: { left: { get_env: item }, op: ge, right: 10 } }
+  target: output
+"#;
+
+    #[tokio::test]
+    async fn test_array_filter_ok() {
+        assert_eq!(array_op(ARRAY_FILTER, "[5, 10, 1, 20]").await, serde_yaml::from_str("[10, 20]").unwrap());
+        assert_eq!(array_op(ARRAY_FILTER, "[15]").await, serde_yaml::from_str("[15]").unwrap());
+        assert_eq!(array_op(ARRAY_FILTER, "[5]").await, Item::Vec(vec!()));
+        assert_eq!(array_op(ARRAY_FILTER, "[]").await, Item::Vec(vec!()));
+    }
+
+    #[tokio::test]
+    async fn test_array_length_ok() {
+        let op = "array_length: { source: items, target: output }";
+
+        assert_eq!(array_op(op, "[a, b, c]").await, Item::Value(Value::IntValue(3)));
+        assert_eq!(array_op(op, "[a]").await, Item::Value(Value::IntValue(1)));
+        assert_eq!(array_op(op, "[]").await, Item::Value(Value::IntValue(0)));
+    }
+
+    #[tokio::test]
+    async fn test_array_op_non_array_err() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("items"), Item::Value(Value::IntValue(1)));
+
+        for op in [ARRAY_MAP, ARRAY_FILTER, "array_length: { source: items, target: output }", "array_length: { source: missing, target: output }"] {
+            let op: Op = serde_yaml::from_str(op).unwrap();
+            let res = op.execute(crate::event::sender::Payload::new(vec![]), state.clone(), &ctx()).await;
+            assert!(matches!(res, Err(Error::UnexpectedType { .. })));
+        }
+    }
+
+    async fn for_each(items: &str) -> State {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- set_env: { target: total, value: 0 }
+- set_env: { target: indexes, value: [] }
+- for_each:
+    source: items
+    item_binding: item
+    index_binding: idx
+    ops:
+      - arithmetic: { left: { get_env: total }, op: add, right: { get_env: item }, target: total }
+      - array_append: { source: indexes, value: { get_env: idx } }
+"#).unwrap();
+
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("items"), serde_yaml::from_str(items).unwrap());
+        let (payload, state) = run(&ops, crate::event::sender::Payload::new("original".into()), state).await;
+
+        assert_eq!(payload.content, "original".as_bytes());
+        assert_eq!(state.get(&Identifier::from("item")), None);
+        assert_eq!(state.get(&Identifier::from("idx")), None);
+        state
+    }
+
+    #[tokio::test]
+    async fn test_for_each_ok() {
+        let state = for_each("[]").await;
+        assert_eq!(state.get(&Identifier::from("total")), Some(&Item::Value(Value::IntValue(0))));
+        assert_eq!(state.get(&Identifier::from("indexes")), Some(&Item::Vec(vec!())));
+
+        let state = for_each("[5]").await;
+        assert_eq!(state.get(&Identifier::from("total")), Some(&Item::Value(Value::IntValue(5))));
+        assert_eq!(state.get(&Identifier::from("indexes")), Some(&serde_yaml::from_str("[0]").unwrap()));
+
+        let state = for_each("[1, 2, 3]").await;
+        assert_eq!(state.get(&Identifier::from("total")), Some(&Item::Value(Value::IntValue(6))));
+        assert_eq!(state.get(&Identifier::from("indexes")), Some(&serde_yaml::from_str("[0, 1, 2]").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_for_each_non_array_err() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("items"), Item::Value(Value::IntValue(1)));
+
+        let op: Op = serde_yaml::from_str("for_each: { source: items, item_binding: item, ops: [] }").unwrap();
+        let res = op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await;
+        assert!(matches!(res, Err(Error::UnexpectedType { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_sleep_ok() {
+        let op: Op = serde_yaml::from_str("sleep: { duration_ms: 20 }").unwrap();
+
+        let start = tokio::time::Instant::now();
+        let res = op.execute(crate::event::sender::Payload::new("a".into()), State::new(), &ctx()).await;
+        assert!(matches!(res, Ok(OpResult::Single(p, _)) if p.content == "a".as_bytes()));
+        assert!(start.elapsed() >= tokio::time::Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_sleep_interrupted_err() {
+        use crate::event::utils::sync::GracefulSignalInvoker;
+
+        let (invoker, signal) = crate::event::utils::sync::new_graceful_signal();
+        let op: Op = serde_yaml::from_str("sleep: { duration_ms: 60000 }").unwrap();
+
+        let ctx = Context { graceful: signal, ..ctx() };
+        let start = tokio::time::Instant::now();
+        let res = tokio::join!(
+            op.execute(crate::event::sender::Payload::new(vec![]), State::new(), &ctx),
+            async {
+                tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+                invoker.call();
+            },
+        ).0;
+        assert!(matches!(res, Err(Error::Interrupted)));
+        assert!(start.elapsed() < tokio::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_log_render_ok() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("user.name"), Item::Value(Value::StringValue("otter".into())));
+        let _ = state.set(Identifier::from("count"), Item::Value(Value::IntValue(3)));
+        let _ = state.set(Identifier::from("tags"), serde_yaml::from_str("[a, b]").unwrap());
+
+        let op: LogOp = serde_yaml::from_str("{ level: info, message: '{user.name} has {count} items {tags}, {missing}.' }").unwrap();
+        assert_eq!(op.render(&state), r#"otter has 3 items ["a","b"], ."#);
+    }
+
+    #[tokio::test]
+    async fn test_log_ops_keep_payload_and_state() {
+        use crate::event::utils::logger::capture;
+
+        capture::init();
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- log: { level: warn, message: "log op value is {log_op_key}" }
+- dump_state: { level: debug }
+"#).unwrap();
+
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("log_op_key"), Item::Value(Value::IntValue(1)));
+        let (payload, new_state) = run(&ops, crate::event::sender::Payload::new("original".into()), state.clone()).await;
+
+        assert_eq!(payload.content, "original".as_bytes());
+        assert_eq!(new_state, state);
+
+        let logged = capture::records(|r| r.message.starts_with("log op value is"));
+        assert_eq!(logged.len(), 1);
+        assert_eq!(logged[0].message, "log op value is 1");
+        assert_eq!(logged[0].level, log::Level::Warn);
+
+        let dumped = capture::records(|r| r.message.starts_with("state: ") && r.message.contains("\"log_op_key\""));
+        assert_eq!(dumped.len(), 1);
+        assert_eq!(dumped[0].message, r#"state: {"log_op_key":1}"#);
+        assert_eq!(dumped[0].level, log::Level::Debug);
+    }
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+
+    async fn cipher(op: &str, content: Vec<u8>) -> process::Result<Vec<u8>> {
+        let op: Op = serde_yaml::from_str(op).unwrap();
+        match op.execute(crate::event::sender::Payload::new(content), State::new(), &ctx()).await? {
+            OpResult::Single(payload, _) => Ok(payload.content),
+            OpResult::Multiple(_) => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_decrypt_round_trip_ok() {
+        for algorithm in ["aes256gcm", "chacha20poly1305"] {
+            let encrypt = format!("encrypt: {{ key: \"{}\", algorithm: {} }}", KEY, algorithm);
+            let decrypt = format!("decrypt: {{ key: \"{}\", algorithm: {} }}", KEY, algorithm);
+
+            let encrypted = cipher(&encrypt, b"secret".to_vec()).await.unwrap();
+            assert_eq!(encrypted.len(), NONCE_LEN + "secret".len() + 16);
+            assert_ne!(&encrypted[NONCE_LEN..], b"secret");
+            assert_eq!(cipher(&decrypt, encrypted).await.unwrap(), b"secret");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_fixed_nonce_ok() {
+        let op = format!("encrypt: {{ key: \"{}\", algorithm: aes256gcm, nonce: AAAAAAAAAAAAAAAA }}", KEY);
+
+        let first = cipher(&op, b"secret".to_vec()).await.unwrap();
+        assert_eq!(&first[..NONCE_LEN], &[0; NONCE_LEN]);
+        assert_eq!(first, cipher(&op, b"secret".to_vec()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_tampered_err() {
+        let encrypt = format!("encrypt: {{ key: \"{}\", algorithm: chacha20poly1305 }}", KEY);
+        let decrypt = format!("decrypt: {{ key: \"{}\", algorithm: chacha20poly1305 }}", KEY);
+
+        let mut encrypted = cipher(&encrypt, b"secret".to_vec()).await.unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+
+        assert!(matches!(cipher(&decrypt, encrypted).await, Err(Error::EncryptionError(_))));
+        assert!(matches!(cipher(&decrypt, vec!(1, 2)).await, Err(Error::EncryptionError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_invalid_key_err() {
+        let res = cipher("encrypt: { key: c2hvcnQ=, algorithm: aes256gcm }", b"secret".to_vec()).await;
+        assert!(matches!(res, Err(Error::EncryptionError(e)) if e.contains("key length")));
+
+        let res = cipher("encrypt: { key: \"not base64!\", algorithm: aes256gcm }", b"secret".to_vec()).await;
+        assert!(matches!(res, Err(Error::EncryptionError(_))));
+    }
+
+    fn jwt_token(claims: serde_json::Value, algorithm: jsonwebtoken::Algorithm, secret: &str) -> String {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(algorithm),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+        ).unwrap()
+    }
+
+    async fn jwt_decode(token: String, op: &str) -> process::Result<State> {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("token"), Item::Value(Value::StringValue(token)));
+
+        let op: Op = serde_yaml::from_str(op).unwrap();
+        match op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await? {
+            OpResult::Single(_, state) => Ok(state),
+            OpResult::Multiple(_) => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jwt_decode_ok() {
+        let exp = chrono::Utc::now().timestamp() + 600;
+        let claims = serde_json::json!({"sub": "otter", "iss": "auth", "exp": exp, "roles": ["admin"]});
+
+        for (algorithm, name) in [(jsonwebtoken::Algorithm::HS256, "hs256"), (jsonwebtoken::Algorithm::HS512, "hs512")] {
+            let token = format!("Bearer {}", jwt_token(claims.clone(), algorithm, "secret"));
+            let op = format!("jwt: {{ token: {{ from_state: token }}, secret_or_key: secret, algorithm: {}, target_claims: claims, issuer: auth }}", name);
+
+            let state = jwt_decode(token, &op).await.unwrap();
+            assert_eq!(state.get(&Identifier::from("claims.sub")), Some(&Item::Value(Value::StringValue("otter".into()))));
+            assert_eq!(state.get(&Identifier::from("claims.roles")), Some(&serde_yaml::from_str("[admin]").unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jwt_decode_invalid_err() {
+        let now = chrono::Utc::now().timestamp();
+        let op = "jwt: { token: { from_state: token }, secret_or_key: secret, algorithm: hs256, target_claims: claims, issuer: auth }";
+
+        let cases = [
+            (serde_json::json!({"iss": "auth", "exp": now + 600}), "other"),
+            (serde_json::json!({"iss": "auth", "exp": now - 600}), "secret"),
+            (serde_json::json!({"iss": "auth", "exp": now + 600, "nbf": now + 300}), "secret"),
+            (serde_json::json!({"iss": "other", "exp": now + 600}), "secret"),
+        ];
+        for (claims, secret) in cases {
+            let res = jwt_decode(jwt_token(claims.clone(), jsonwebtoken::Algorithm::HS256, secret), op).await;
+            assert!(matches!(res, Err(Error::JwtInvalid(_))), "{:?}", claims);
+        }
+
+        let res = jwt_decode("not a token".into(), op).await;
+        assert!(matches!(res, Err(Error::JwtInvalid(_))));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_decode_without_verify_ok() {
+        let token = jwt_token(serde_json::json!({"sub": "otter"}), jsonwebtoken::Algorithm::HS256, "other");
+        let op = "jwt: { token: { from_state: token }, secret_or_key: secret, algorithm: hs256, target_claims: claims, verify: false }";
+
+        let state = jwt_decode(token, op).await.unwrap();
+        assert_eq!(state.get(&Identifier::from("claims.sub")), Some(&Item::Value(Value::StringValue("otter".into()))));
+    }
+
+    async fn render_template(op: &str) -> process::Result<Item> {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("order"), serde_yaml::from_str("{id: 42, items: [apple, pear], note: <b>}").unwrap());
+
+        let op: Op = serde_yaml::from_str(op).unwrap();
+        match op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await? {
+            OpResult::Single(_, state) => Ok(state.get(&Identifier::from("output")).cloned().unwrap()),
+            OpResult::Multiple(_) => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render_template_ok() {
+        let res = render_template(r#"
+template:
+  source: "Order {{id}}: {{#each items}}{{this}} {{/each}}{{note}} {{{note}}}"
+  context: { from_state: order }
+  target: output
+"#).await;
+        assert_eq!(res.unwrap(), Item::Value(Value::StringValue("Order 42: apple pear &lt;b&gt; <b>".into())));
+    }
+
+    #[tokio::test]
+    async fn test_render_template_file_ok() {
+        let dir = std::env::temp_dir().join(format!("webhook-template-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("order.hbs");
+        std::fs::write(&path, "#{{id}}").unwrap();
+
+        let res = render_template(format!(r#"
+template:
+  source_file: {}
+  context: {{ from_state: order }}
+  target: output
+"#, path.display()).as_str()).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(res.unwrap(), Item::Value(Value::StringValue("#42".into())));
+    }
+
+    #[tokio::test]
+    async fn test_render_template_file_changed_ok() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("order.hbs");
+        let op = format!(r#"
+template:
+  source_file: {}
+  context: {{ from_state: order }}
+  target: output
+"#, path.display());
+
+        std::fs::write(&path, "#{{id}}").unwrap();
+        assert_eq!(render_template(op.as_str()).await.unwrap(), Item::Value(Value::StringValue("#42".into())));
+
+        std::fs::write(&path, "order {{id}}").unwrap();
+        assert_eq!(render_template(op.as_str()).await.unwrap(), Item::Value(Value::StringValue("order 42".into())));
+
+        use sha2::Digest;
+        let cached = TEMPLATE_CACHE.read().unwrap().get(&path.display().to_string()).unwrap().hash.clone();
+        assert_eq!(cached, hex::encode(sha2::Sha256::digest("order {{id}}".as_bytes())));
+    }
+
+    #[tokio::test]
+    async fn test_render_template_file_outside_root_err() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("templates")).unwrap();
+        std::fs::write(dir.path().join("templates/order.hbs"), "#{{id}}").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        let op = format!(r#"
+template:
+  source_file: {{ from_env: template_name }}
+  root: {}
+  context: {{ from_state: order }}
+  target: output
+"#, dir.path().join("templates").display());
+        let op: Op = serde_yaml::from_str(op.as_str()).unwrap();
+
+        let run = |name: String| {
+            let op = op.clone();
+            async move {
+                let mut state = State::new();
+                let _ = state.set("order".into(), Item::Map(HashMap::from([("id".to_string(), Item::Value(Value::IntValue(42)))])));
+                let _ = state.set("template_name".into(), Item::Value(Value::StringValue(name)));
+                op.execute(Payload::new(vec!()), state, &ctx()).await
+                    .map(|r| match r {
+                        OpResult::Single(_, state) => state.get(&Identifier::from("output")).cloned(),
+                        OpResult::Multiple(_) => None,
+                    })
+            }
+        };
+
+        let res = run("order.hbs".into()).await;
+        assert_eq!(res.unwrap(), Some(Item::Value(Value::StringValue("#42".into()))));
+
+        let res = run("../secret.txt".into()).await;
+        assert!(matches!(res, Err(Error::TemplateError(e)) if e.contains("outside of the template root")));
+
+        let res = run(dir.path().join("secret.txt").display().to_string()).await;
+        assert!(matches!(res, Err(Error::TemplateError(e)) if e.contains("outside of the template root")));
+    }
+
+    #[test]
+    fn test_template_cache_evicts_least_recently_used() {
+        let mut cache = HashMap::new();
+        let compile = || CompiledTemplate::compile("hash".into(), "{{id}}").unwrap();
+
+        for i in 0..TEMPLATE_CACHE_CAPACITY {
+            CompiledTemplate::insert(&mut cache, i.to_string(), compile());
+        }
+        cache.get("0").unwrap().touch();
+        CompiledTemplate::insert(&mut cache, "new".into(), compile());
+
+        assert_eq!(cache.len(), TEMPLATE_CACHE_CAPACITY);
+        assert!(cache.contains_key("0"));
+        assert!(!cache.contains_key("1"));
+        assert!(cache.contains_key("new"));
+    }
+
+    #[tokio::test]
+    async fn test_render_template_err() {
+        let res = render_template("template: { source: \"{{#each}}\", context: { from_state: order }, target: output }").await;
+        assert!(matches!(res, Err(Error::TemplateError(_))));
+
+        let res = render_template("template: { source_file: /nonexistent/template.hbs, context: { from_state: order }, target: output }").await;
+        assert!(matches!(res, Err(Error::TemplateError(_))));
+
+        let res = render_template("template: { source: \"{{id}}\", context: { from_state: order.id }, target: output }").await;
+        assert!(matches!(res, Err(Error::UnexpectedType { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_set_env_sensitive() {
+        crate::event::utils::logger::capture::init();
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- set_env: { target: auth.token, value: hunter2, sensitive: true }
+- set_env: { target: user, value: otter }
+- copy_env: { from: auth.token, to: copy }
+- copy_env: { from: auth, to: credentials }
+- if:
+    condition: { get_env: credentials.token }
+    then_ops: [{ set_env: { target: checked, value: true } }]
+"#).unwrap();
+        let mut parts = execute_ops(&ops, crate::event::sender::Payload::new(vec![]), State::new(), &ctx()).await.unwrap();
+        let (_, state) = parts.remove(0);
+
+        let leaked = crate::event::utils::logger::capture::records(|r| r.message.contains("hunter2"));
+        assert!(leaked.is_empty(), "{:?}", leaked);
+
+        assert_eq!(state.get(&Identifier::from("auth.token")), Some(&Item::Value(Value::StringValue("hunter2".into()))));
+        assert!(state.is_sensitive(&Identifier::from("auth.token")));
+        assert!(state.is_sensitive(&Identifier::from("copy")));
+        assert!(state.is_sensitive(&Identifier::from("credentials.token")));
+        assert!(!state.is_sensitive(&Identifier::from("user")));
+
+        let debug = format!("{:?}", state);
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("otter"));
+
+        let op: LogOp = serde_yaml::from_str("{ level: info, message: '{user}: {auth.token}' }").unwrap();
+        assert_eq!(op.render(&state), "otter: [REDACTED]");
+
+        let op: LogOp = serde_yaml::from_str("{ level: info, message: '{credentials}' }").unwrap();
+        assert_eq!(op.render(&state), r#"{"token":"[REDACTED]"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_array_append_prepend_ok() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- array_append: { source: event.tags, value: b }
+- array_append: { source: event.tags, value: { get_env: extra } }
+- array_prepend: { source: event.tags, value: a }
+"#).unwrap();
+
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("extra"), Item::Value(Value::IntValue(1)));
+        let (_, state) = run(&ops, crate::event::sender::Payload::new(vec![]), state).await;
+
+        assert_eq!(state.get(&Identifier::from("event.tags")), Some(&serde_yaml::from_str("[a, b, 1]").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_array_append_non_array_err() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("a"), Item::Value(Value::IntValue(1)));
+
+        let op: Op = serde_yaml::from_str("array_append: { source: a, value: 2 }").unwrap();
+        let res = op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await;
+        assert!(matches!(res, Err(Error::NonMapAccess { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_array_concat_ok() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("left"), serde_yaml::from_str("[1, 2]").unwrap());
+        let _ = state.set(Identifier::from("right"), serde_yaml::from_str("[3]").unwrap());
+        let _ = state.set(Identifier::from("empty"), Item::Vec(vec!()));
+
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- array_concat: { left: left, right: right, target: all }
+- array_concat: { left: empty, right: left, target: copy }
+"#).unwrap();
+        let (_, state) = run(&ops, crate::event::sender::Payload::new(vec![]), state).await;
+
+        assert_eq!(state.get(&Identifier::from("all")), Some(&serde_yaml::from_str("[1, 2, 3]").unwrap()));
+        assert_eq!(state.get(&Identifier::from("copy")), Some(&serde_yaml::from_str("[1, 2]").unwrap()));
+
+        let op: Op = serde_yaml::from_str("array_concat: { left: left, right: missing, target: all }").unwrap();
+        let res = op.execute(crate::event::sender::Payload::new(vec![]), state, &ctx()).await;
+        assert!(matches!(res, Err(Error::UnexpectedType { .. })));
+    }
+
+    async fn merge(deep: bool) -> Item {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("defaults"), serde_yaml::from_str("{retry: {count: 3, delay: 1}, tags: [a], name: default}").unwrap());
+        let _ = state.set(Identifier::from("event"), serde_yaml::from_str("{retry: {count: 5}, tags: [b], extra: true}").unwrap());
+
+        let op: Op = serde_yaml::from_str(format!(
+            "merge_maps: {{ base: {{ get_env: defaults }}, overlay: {{ get_env: event }}, deep: {}, target: config }}", deep,
+        ).as_str()).unwrap();
+        let (_, state) = run(&[op], crate::event::sender::Payload::new(vec![]), state).await;
+        state.get(&Identifier::from("config")).cloned().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_merge_maps_shallow_ok() {
+        let expected = serde_yaml::from_str("{retry: {count: 5}, tags: [b], name: default, extra: true}").unwrap();
+        assert_eq!(merge(false).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_merge_maps_deep_ok() {
+        let expected = serde_yaml::from_str("{retry: {count: 5, delay: 1}, tags: [b], name: default, extra: true}").unwrap();
+        assert_eq!(merge(true).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_merge_maps_non_map_err() {
+        for (base, overlay) in [("{a: 1}", "[1]"), ("1", "{a: 1}"), ("{a: 1}", "~")] {
+            let op: Op = serde_yaml::from_str(format!(
+                "merge_maps: {{ base: {}, overlay: {}, target: config }}", base, overlay,
+            ).as_str()).unwrap();
+            let res = op.execute(crate::event::sender::Payload::new(vec![]), State::new(), &ctx()).await;
+            assert!(matches!(res, Err(Error::UnexpectedType { .. })), "{} {}", base, overlay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_xml_payload_ok() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- set_from_payload_field: { field: order.@id, format: xml, target: id }
+- set_env: { target: body, value: { from_payload: xml } }
+- to_payload:
+    value: { get_env: body }
+    format: { xml: { root_element: request, include_declaration: false } }
+"#).unwrap();
+
+        let payload = crate::event::sender::Payload::new(r#"<request><order id="7">otter</order></request>"#.into());
+        let (payload, state) = run(&ops, payload, State::new()).await;
+
+        assert_eq!(state.get(&Identifier::from("id")), Some(&Item::Value(Value::StringValue("7".into()))));
+        assert_eq!(payload.content, r#"<request><order id="7">otter</order></request>"#.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_xml_payload_err() {
+        let op: Op = serde_yaml::from_str("set_env: { target: body, value: { from_payload: xml } }").unwrap();
+        let res = op.execute(crate::event::sender::Payload::new("<a>".into()), State::new(), &ctx()).await;
+        assert!(matches!(res, Err(Error::XmlError(_))));
+    }
+
+    #[test]
+    fn test_msgpack_vectors_ok() {
+        let cases: Vec<(&str, Vec<u8>)> = vec!(
+            ("null", vec!(0xc0)),
+            ("true", vec!(0xc3)),
+            ("1", vec!(0x01)),
+            ("-1", vec!(0xff)),
+            ("300", vec!(0xcd, 0x01, 0x2c)),
+            ("1.5", vec!(0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0)),
+            (r#""abc""#, vec!(0xa3, b'a', b'b', b'c')),
+            (r#"[1, "a"]"#, vec!(0x92, 0x01, 0xa1, b'a')),
+            (r#"{"a": [null]}"#, vec!(0x81, 0xa1, b'a', 0x91, 0xc0)),
+        );
+
+        for (json, bytes) in cases {
+            let item: Item = serde_json::from_str(json).unwrap();
+            assert_eq!(PayloadFormat::Msgpack.to_vec(&item).unwrap(), bytes, "{}", json);
+
+            let parsed = PayloadFormat::Msgpack.parse_payload(&crate::event::sender::Payload::new(bytes)).unwrap();
+            assert_eq!(parsed, item, "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_msgpack_round_trip_ok() {
+        let json = r#"{"id": 42, "price": 9.75, "ok": false, "tags": ["a", "b"], "meta": {"none": null, "nested": {"x": -7}}}"#;
+        let item: Item = serde_json::from_str(json).unwrap();
+
+        let content = PayloadFormat::Msgpack.to_vec(&item).unwrap();
+        assert!(content.len() < json.len());
+
+        let parsed = PayloadFormat::Msgpack.parse_payload(&crate::event::sender::Payload::new(content)).unwrap();
+        assert_eq!(parsed, item);
+    }
+
+    #[test]
+    fn test_msgpack_parse_err() {
+        let res = PayloadFormat::Msgpack.parse_payload(&crate::event::sender::Payload::new(vec!(0x92, 0x01)));
+        assert!(matches!(res, Err(Error::SerializationError { format, .. }) if format == "msgpack"));
+
+        let format: PayloadFormat = serde_yaml::from_str("msgpack").unwrap();
+        assert!(matches!(format, PayloadFormat::Msgpack));
+    }
+
+    #[tokio::test]
+    async fn test_csv_payload_ok() {
+        let ops: Vec<Op> = serde_yaml::from_str(r#"
+- set_env: { target: rows, value: { from_payload: csv } }
+- array_length: { source: rows, target: count }
+- to_payload: { value: { get_env: rows }, format: { csv: { has_header: false } } }
+"#).unwrap();
+
+        let payload = crate::event::sender::Payload::new("name,qty\n\"Otter, Inc\",3\n".into());
+        let (payload, state) = run(&ops, payload, State::new()).await;
+
+        assert_eq!(state.get(&Identifier::from("rows.0.qty")), Some(&Item::Value(Value::IntValue(3))));
+        assert_eq!(state.get(&Identifier::from("count")), Some(&Item::Value(Value::IntValue(1))));
+        assert_eq!(payload.content, "\"Otter, Inc\",3\n".as_bytes());
+    }
+
+    async fn run(ops: &[Op], payload: crate::event::sender::Payload, state: State) -> (crate::event::sender::Payload, State) {
+        let (mut payload, mut state) = (payload, state);
+        for op in ops {
+            match op.execute(payload, state, &ctx()).await {
+                Ok(OpResult::Single(new_payload, new_state)) => {
+                    payload = new_payload;
+                    state = new_state;
+                }
+                _ => unreachable!(),
+            }
+        }
+        (payload, state)
+    }
+
+    fn if_ops(value: &str) -> Vec<Op> {
+        serde_yaml::from_str(format!(r#"
+- set_env: {{ target: value, value: {} }}
+- if:
+    condition: {{ get_env: value }}
+    then_ops:
+      - set_env: {{ target: branch, value: then }}
+      - to_payload: {{ value: {{ get_env: branch }}, format: json }}
+    else_ops:
+      - set_env: {{ target: branch, value: else }}
+"#, value).as_str()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_if_then_ok() {
+        let payload = crate::event::sender::Payload::new("original".into());
+        let (payload, state) = run(&if_ops("true"), payload, State::new()).await;
+
+        assert_eq!(state.get(&Identifier::from("branch")), Some(&Item::Value(Value::StringValue("then".into()))));
+        assert_eq!(payload.content, "\"then\"".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_if_else_ok() {
+        let payload = crate::event::sender::Payload::new("original".into());
+        let (payload, state) = run(&if_ops("0"), payload, State::new()).await;
+
+        assert_eq!(state.get(&Identifier::from("branch")), Some(&Item::Value(Value::StringValue("else".into()))));
+        assert_eq!(payload.content, "original".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_if_without_else_ok() {
+        let op: Op = serde_yaml::from_str(r#"
+if:
+  condition: false
+  then_ops:
+    - set_env: { target: branch, value: then }
+"#).unwrap();
+
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("a"), Item::Value(Value::IntValue(1)));
+        let payload = crate::event::sender::Payload::new("original".into());
+
+        let (payload, new_state) = run(&[op], payload, state.clone()).await;
+        assert_eq!(new_state, state);
+        assert_eq!(payload.content, "original".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_if_nested_ok() {
+        let op: Op = serde_yaml::from_str(r#"
+if:
+  condition: { get_env: outer }
+  then_ops:
+    - if:
+        condition: { get_env: inner }
+        then_ops:
+          - set_env: { target: branch, value: both }
+        else_ops:
+          - set_env: { target: branch, value: outer }
+  else_ops:
+    - set_env: { target: branch, value: none }
+"#).unwrap();
+
+        for (outer, inner, expected) in &[(1, 1, "both"), (1, 0, "outer"), (0, 1, "none")] {
+            let mut state = State::new();
+            let _ = state.set(Identifier::from("outer"), Item::Value(Value::IntValue(*outer)));
+            let _ = state.set(Identifier::from("inner"), Item::Value(Value::IntValue(*inner)));
+
+            let (_, state) = run(std::slice::from_ref(&op), crate::event::sender::Payload::new(vec![]), state).await;
+            assert_eq!(state.get(&Identifier::from("branch")), Some(&Item::Value(Value::StringValue(expected.to_string()))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_if_split_ok() {
+        let op: Op = serde_yaml::from_str(r#"
+if:
+  condition: true
+  then_ops:
+    - split: { source: { from_payload: json } }
+"#).unwrap();
+        let payload = crate::event::sender::Payload::new("[1, 2]".into());
+
+        let parts = match op.execute(payload, State::new(), &ctx()).await {
+            Ok(OpResult::Multiple(parts)) => parts,
+            _ => unreachable!(),
+        };
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].0.content, "2".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_if_condition_err() {
+        let op: Op = serde_yaml::from_str(r#"
+if:
+  condition: { from_json: "{" }
+  then_ops: []
+"#).unwrap();
+
+        let res = op.execute(crate::event::sender::Payload::new(vec![]), State::new(), &ctx()).await;
+        assert!(matches!(res, Err(Error::JsonError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_split_non_array_err() {
+        let op = Op::Split {
+            split: SplitOp {
+                source: Expression::Item(Item::Value(Value::IntValue(123))),
+                format: None,
+            },
+        };
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let res = op.execute(payload, State::new(), &ctx()).await;
+        assert!(matches!(res, Err(Error::UnexpectedType { .. })));
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Expression {
+    SetEnv {
+        #[serde(alias = "to_state")]
+        set_env: SetEnv,
+    },
+    GetEnv {
+        #[serde(alias = "from_state")]
+        get_env: Identifier,
+    },
+    FromJson { from_json: String },
+    FromPayload { from_payload: PayloadFormat },
+    AsMap { as_map: HashMap<String, Expression> },
+    MatchRegex { match_regex: MatchRegexExpr },
+    Flatten { flatten: FlattenExpr },
+    Unflatten { unflatten: UnflattenExpr },
+    Debug { debug: DebugExpr },
+    Conditional { conditional: ConditionalExpr },
+    Compare { compare: CompareExpr },
+    Now { now: NowExpr },
+    Item(#[serde(deserialize_with = "literal_item")] Item),
+}
+
+/// A literal item. Untagged expressions fall back to it, so an expression whose config is invalid,
+/// such as a `match_regex` with a pattern that does not compile, is rejected here instead of being
+/// taken as a literal map.
+fn literal_item<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Item, D::Error> {
+    let item = Item::deserialize(deserializer)?;
+    if let Item::Map(map) = &item {
+        if let (1, Some(Item::Map(config))) = (map.len(), map.get("match_regex")) {
+            if let Some(Item::Value(Value::StringValue(pattern))) = config.get("pattern") {
+                RegexPattern::new(pattern).map_err(serde::de::Error::custom)?;
+            }
+            return Err(serde::de::Error::custom("invalid match_regex expression"));
+        }
+    }
+    Ok(item)
+}
+
+impl Expression {
+    pub fn evaluate(
+        &self,
+        payload: Payload,
+        state: State,
+    ) -> process::Result<(Item, Payload, State)> {
+        match self {
+            Expression::SetEnv { set_env } => {
+                let (value, payload, mut new_state) = set_env.value.evaluate(payload, state)?;
+                let idx = set_env.target.clone();
+                if set_env.sensitive {
+                    new_state.mark_sensitive(&idx);
+                }
+                log::trace!("setting env with key {} as {}", idx, new_state.loggable(&idx, &value));
+                new_state.set(idx, value.clone())?;
+                Ok((value, payload, new_state))
+            }
+            Expression::GetEnv { get_env } => {
+                let value = state.get(get_env);
+                let item = value
+                    .cloned()
+                    .unwrap_or(Item::Value(Value::None));
+                Ok((item, payload, state))
+            }
+            Expression::FromPayload {
+                from_payload: format,
+            } => {
+                let item = format.parse_payload(&payload)?;
+                Ok((item, payload, state))
+            }
+            Expression::Item(i) => Ok((i.clone(), payload, state)),
+            Expression::FromJson { from_json } => {
+                let item = serde_json::from_str(from_json.as_str())
+                    .map_err(|e| process::Error::JsonError(e.to_string()))?;
+                Ok((item, payload, state))
+            }
+            Expression::AsMap { as_map: map } => {
+                let (map, payload, state) = map.iter().try_fold(
+                    (HashMap::new(), payload, state),
+                    |(mut acc, payload, state), (key, expr)| {
+                        let (item, payload, state) = expr.evaluate(payload, state)?;
+                        acc.insert(key.clone(), item);
+                        Ok::<_, process::Error>((acc, payload, state))
+                    },
+                )?;
+
+                Ok((Item::Map(map), payload, state))
+            }
+            Expression::MatchRegex { match_regex } => {
+                let re = &match_regex.pattern.0;
+
+                let (input, payload, state) = match_regex.input.evaluate(payload, state)?;
+                let input = match input {
+                    Item::Value(Value::StringValue(s)) => s,
+                    i => return Err(process::Error::UnexpectedType {
+                        expected: "String".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
+
+                let captures = match re.captures(input.as_str()) {
+                    None => return Ok((Item::Value(Value::None), payload, state)),
+                    Some(captures) => captures,
+                };
+
+                let as_item = |m: Option<regex::Match>| {
+                    m.map(|m| Item::Value(Value::StringValue(m.as_str().into())))
+                        .unwrap_or(Item::Value(Value::None))
+                };
+
+                let mut map = HashMap::new();
+                map.insert(String::from("match"), as_item(captures.get(0)));
+                (0..captures.len()).for_each(|idx| {
+                    map.insert(idx.to_string(), as_item(captures.get(idx)));
+                });
+                re.capture_names().flatten().for_each(|name| {
+                    map.insert(name.into(), as_item(captures.name(name)));
+                });
+
+                Ok((Item::Map(map), payload, state))
+            }
+            Expression::Flatten { flatten } => {
+                let (item, payload, state) = flatten.source.evaluate(payload, state)?;
+
+                let mut map = HashMap::new();
+                item.flatten_into(&mut map, flatten.prefix.clone(), flatten.separator.as_str());
+                let map = map.into_iter()
+                    .map(|(k, v)| (k, Item::Value(Value::StringValue(v))))
+                    .collect();
+
+                Ok((Item::Map(map), payload, state))
+            }
+            Expression::Unflatten { unflatten } => {
+                let (item, payload, state) = unflatten.source.evaluate(payload, state)?;
+
+                let map = match item {
+                    Item::Map(map) => map,
+                    i => return Err(process::Error::UnexpectedType {
+                        expected: "Map".into(),
+                        t: i.type_name().into(),
+                    }),
+                };
+
+                Ok((Item::unflatten(map, unflatten.separator.as_str()), payload, state))
+            }
+            Expression::Now { now } => {
+                let now = now.format(chrono::Utc::now())?;
+                Ok((Item::Value(Value::StringValue(now)), payload, state))
+            }
+            Expression::Debug { debug } => {
+                let (item, payload, state) = debug.expr.evaluate(payload, state)?;
+                log::debug!("{}", debug.message(&item));
+                Ok((item, payload, state))
+            }
+            Expression::Conditional { conditional } => {
+                let (condition, payload, state) = conditional.condition.evaluate(payload, state)?;
+
+                if condition.is_truthy() {
+                    conditional.then_expr.evaluate(payload, state)
+                } else {
+                    conditional.else_expr.evaluate(payload, state)
+                }
+            }
+            Expression::Compare { compare } => {
+                let (left, payload, state) = compare.left.evaluate(payload, state)?;
+                let (right, payload, state) = compare.right.evaluate(payload, state)?;
+
+                let res = compare.op.apply(&left, &right)?;
+                Ok((Item::Value(Value::BoolValue(res)), payload, state))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod expression_tests {
+    use crate::event::process::operation::SetEnv;
+    use crate::event::process::*;
+
+    use super::*;
+
+    #[test]
+    fn test_set_env_ok() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("o"), Item::Value(Value::None));
+
+        let key = Identifier::from("key");
+        let item = Item::Value(Value::IntValue(123));
+        let value = Box::new(Expression::Item(item.clone()));
+
+        let exp = Expression::SetEnv {
+            set_env: SetEnv {
+                target: key.clone(),
+                value,
+                sensitive: false,
+            },
+        };
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let res = exp.evaluate(payload, state);
+        assert!(res.is_ok());
+
+        let (ret_item, _, state) = res.unwrap();
+
+        assert_eq!(state.len(), 2);
+        assert!(state.get(&key).is_some());
+        assert_eq!(state.get(&key).unwrap(), &item);
+
+        assert_eq!(ret_item, item);
+    }
+
+    #[test]
+    fn test_get_env_ok() {
+        let mut state = State::new();
+        let key = Identifier::from("key");
+        let item = Item::Value(Value::IntValue(123));
+
+        let _ = state.set(key.clone(), item.clone());
+
+        let exp = Expression::GetEnv {
+            get_env: key.clone(),
+        };
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let res = exp.evaluate(payload, state);
+        assert!(res.is_ok());
+
+        let (ret_item, _, state) = res.unwrap();
+
+        assert_eq!(state.len(), 1);
+        assert!(state.get(&key).is_some());
+        assert_eq!(state.get(&key).unwrap(), &item);
+
+        assert_eq!(ret_item, item);
+    }
+
+    #[test]
+    fn test_item_ok() {
+        let state = State::new();
+
+        let item = Item::Value(Value::IntValue(123));
+        let exp = Expression::Item(item.clone());
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let res = exp.evaluate(payload, state);
+        assert!(res.is_ok());
+
+        let (ret_item, _, state) = res.unwrap();
+
+        assert_eq!(state.len(), 0);
+        assert_eq!(ret_item, item);
+    }
+
+    #[test]
+    fn test_as_map_ok() {
+        let env_id = Identifier("id".into());
+        let env_value = Item::Value(Value::StringValue("test".into()));
+        let state = {
+            let mut state = State::new();
+
+            let _ = state.set(env_id.clone(), env_value.clone());
+
+            state
+        };
+
+        let new_item = Item::Value(Value::IntValue(123));
+        let to_env_id = Identifier("to_id".into());
+        let to_env_item = Item::Value(Value::IntValue(123));
+
+        let map = {
+            let mut res = HashMap::new();
+
+            res.insert(
+                String::from("from_env"),
+                Expression::GetEnv {
+                    get_env: env_id.clone(),
+                },
+            );
+            res.insert(String::from("value"), Expression::Item(new_item.clone()));
+            res.insert(
+                String::from("to_env"),
+                Expression::SetEnv {
+                    set_env: SetEnv {
+                        target: to_env_id.clone(),
+                        value: Box::new(Expression::Item(to_env_item.clone())),
+                        sensitive: false,
+                    },
+                },
+            );
+
+            res
+        };
+        let exp = Expression::AsMap { as_map: map };
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let exp_res = exp.evaluate(payload, state);
+        assert!(exp_res.is_ok());
+        let (item, _, state) = exp_res.unwrap();
+
+        assert!(matches!(item, Item::Map(_)));
+        let map = match item {
+            Item::Map(m) => m,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(map.len(), 3);
+
+        assert_eq!(map.get(&String::from("from_env")), Some(&env_value));
+        assert_eq!(map.get(&String::from("value")), Some(&new_item));
+        assert_eq!(map.get(&String::from("to_env")), Some(&to_env_item));
+
+        assert_eq!(state.len(), 2);
+    }
+
+    #[test]
+    fn test_state_aliases_ok() {
+        let evaluate = |yaml: &str| {
+            let exp: Expression = serde_yaml::from_str(yaml).unwrap();
+            let mut state = State::new();
+            let _ = state.set(Identifier::from("key"), Item::Value(Value::IntValue(123)));
+            let payload = crate::event::sender::Payload::new(vec![]);
+
+            let (item, _, state) = exp.evaluate(payload, state).unwrap();
+            (item, state)
+        };
+
+        let old = evaluate(r#"
+as_map:
+  a: { get_env: key }
+  b: { set_env: { target: other, value: { get_env: key } } }
+"#);
+        let new = evaluate(r#"
+as_map:
+  a: { from_state: key }
+  b: { to_state: { target: other, value: { from_state: key } } }
+"#);
+
+        assert_eq!(old, new);
+        assert_eq!(new.1.get(&Identifier::from("other")), Some(&Item::Value(Value::IntValue(123))));
+    }
+
+    #[test]
+    fn test_debug_ok() {
+        let exp: Expression = serde_yaml::from_str(r#"
+as_map:
+  parsed:
+    debug:
+      label: after parse
+      expr:
+        from_payload: json
+"#).unwrap();
+        let payload = crate::event::sender::Payload::new("{\"a\": 1}".into());
+
+        let res = exp.evaluate(payload, State::new());
+        assert!(res.is_ok());
+
+        let (item, payload, state) = res.unwrap();
+        let expected: Item = serde_json::from_str(r#"{"parsed": {"a": 1}}"#).unwrap();
+        assert_eq!(item, expected);
+        assert_eq!(payload.content, "{\"a\": 1}".as_bytes());
+        assert_eq!(state.len(), 0);
+    }
+
+    #[test]
+    fn test_debug_message_ok() {
+        let debug = DebugExpr {
+            label: "after parse".into(),
+            expr: Box::new(Expression::Item(Item::Value(Value::IntValue(1)))),
+        };
+
+        let message = debug.message(&Item::Value(Value::IntValue(1)));
+        assert!(message.contains("after parse"));
+        assert!(message.contains("Int"));
+    }
+
+    fn match_regex(pattern: &str, input: &str) -> Item {
+        let exp = Expression::MatchRegex {
+            match_regex: MatchRegexExpr {
+                pattern: RegexPattern::new(pattern).unwrap(),
+                input: Box::new(Expression::Item(Item::Value(Value::StringValue(input.into())))),
+            },
+        };
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let res = exp.evaluate(payload, State::new());
+        assert!(res.is_ok());
+
+        let (item, _, _) = res.unwrap();
+        item
+    }
+
+    #[test]
+    fn test_match_regex_ok() {
+        let item = match_regex(r"^(\d+)-(?P<name>.+)$", "123-otter");
+
+        let map = match item {
+            Item::Map(m) => m,
+            _ => unreachable!(),
+        };
+
+        let string = |s: &str| Some(Item::Value(Value::StringValue(s.into())));
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.get("match").cloned(), string("123-otter"));
+        assert_eq!(map.get("0").cloned(), string("123-otter"));
+        assert_eq!(map.get("1").cloned(), string("123"));
+        assert_eq!(map.get("2").cloned(), string("otter"));
+        assert_eq!(map.get("name").cloned(), string("otter"));
+    }
+
+    fn nested_item() -> Item {
+        serde_json::from_str(r#"{"a": {"b": 1, "c": ["x", {"d": "y"}]}, "e": "z"}"#).unwrap()
+    }
+
+    #[test]
+    fn test_flatten_ok() {
+        let exp = Expression::Flatten {
+            flatten: FlattenExpr {
+                source: Box::new(Expression::Item(nested_item())),
+                separator: "_".into(),
+                prefix: Some("p".into()),
+            },
+        };
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let res = exp.evaluate(payload, State::new());
+        assert!(res.is_ok());
+
+        let (item, _, _) = res.unwrap();
+        let expected = serde_json::from_str(r#"{"p_a_b": "1", "p_a_c_0": "x", "p_a_c_1_d": "y", "p_e": "z"}"#).unwrap();
+        assert_eq!(item, Item::Map(expected));
+    }
+
+    #[test]
+    fn test_unflatten_ok() {
+        let flat = serde_json::from_str(r#"{"a.b": 1, "a.c.0": "x", "a.c.1.d": "y", "e": "z"}"#).unwrap();
+        let exp = Expression::Unflatten {
+            unflatten: UnflattenExpr {
+                source: Box::new(Expression::Item(Item::Map(flat))),
+                separator: ".".into(),
+            },
+        };
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let res = exp.evaluate(payload, State::new());
+        assert!(res.is_ok());
+
+        let (item, _, _) = res.unwrap();
+        assert_eq!(item, nested_item());
+    }
+
+    fn conditional(condition: &str) -> (Item, State) {
+        let exp: Expression = serde_yaml::from_str(format!(r#"
+conditional:
+  condition: {}
+  then_expr: {{ to_state: {{ target: branch, value: "yes" }} }}
+  else_expr: {{ to_state: {{ target: branch, value: "no" }} }}
+"#, condition).as_str()).unwrap();
+
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("flag"), Item::Value(Value::IntValue(1)));
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        let (item, _, state) = exp.evaluate(payload, state).unwrap();
+        (item, state)
+    }
+
+    #[test]
+    fn test_conditional_truthy_ok() {
+        let (item, state) = conditional("{ from_state: flag }");
+
+        let yes = Item::Value(Value::StringValue("yes".into()));
+        assert_eq!(item, yes);
+        assert_eq!(state.get(&Identifier::from("branch")), Some(&yes));
+    }
+
+    #[test]
+    fn test_conditional_falsy_ok() {
+        for condition in &["{ from_state: missing }", "0", "\"\"", "[]", "false"] {
+            let (item, state) = conditional(condition);
+
+            let no = Item::Value(Value::StringValue("no".into()));
+            assert_eq!(item, no);
+            assert_eq!(state.get(&Identifier::from("branch")), Some(&no));
+        }
+    }
+
+    fn compare(left: &str, op: &str, right: &str) -> process::Result<bool> {
+        let exp: Expression = serde_yaml::from_str(format!(
+            "compare: {{ left: {}, op: {}, right: {} }}", left, op, right,
+        ).as_str()).unwrap();
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        match exp.evaluate(payload, State::new())? {
+            (Item::Value(Value::BoolValue(b)), _, _) => Ok(b),
+            (i, _, _) => panic!("unexpected result {:?}", i),
+        }
+    }
+
+    #[test]
+    fn test_compare_eq_ne_ok() {
+        let cases = [
+            ("1", "1", true), ("1", "2", false), ("1", "1.0", true), ("1.5", "1.5", true),
+            ("a", "a", true), ("a", "b", false), ("true", "true", true), ("~", "~", true),
+            ("[1, a]", "[1, a]", true), ("{a: 1}", "{a: 1}", true), ("{a: 1}", "{a: 2}", false),
+            ("1", "\"1\"", false), ("~", "0", false),
+        ];
+
+        for (left, right, eq) in cases.iter() {
+            assert_eq!(compare(left, "eq", right).unwrap(), *eq, "{} == {}", left, right);
+            assert_eq!(compare(left, "ne", right).unwrap(), !*eq, "{} != {}", left, right);
+        }
+    }
+
+    #[test]
+    fn test_compare_ordered_ok() {
+        let cases = [("1", "2"), ("1", "1.5"), ("0.5", "1"), ("-2.5", "-1.5"), ("apple", "banana"), ("B", "a")];
+
+        for (less, greater) in cases.iter() {
+            assert!(compare(less, "lt", greater).unwrap());
+            assert!(compare(less, "le", greater).unwrap());
+            assert!(!compare(less, "gt", greater).unwrap());
+            assert!(!compare(less, "ge", greater).unwrap());
+            assert!(compare(greater, "gt", less).unwrap());
+            assert!(compare(greater, "ge", less).unwrap());
+            assert!(compare(less, "le", less).unwrap());
+            assert!(compare(less, "ge", less).unwrap());
+            assert!(!compare(less, "lt", less).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_compare_from_state_ok() {
+        let mut state = State::new();
+        let _ = state.set(Identifier::from("order.total"), Item::Value(Value::IntValue(150)));
+
+        let exp: Expression = serde_yaml::from_str("compare: { left: { from_state: order.total }, op: gt, right: 100 }").unwrap();
+        let (item, _, _) = exp.evaluate(crate::event::sender::Payload::new(vec![]), state).unwrap();
+
+        assert_eq!(item, Item::Value(Value::BoolValue(true)));
+    }
+
+    #[test]
+    fn test_compare_type_mismatch_err() {
+        for (left, right) in [("1", "a"), ("a", "1.5"), ("true", "false"), ("~", "1"), ("[1]", "[2]")].iter() {
+            for op in ["lt", "le", "gt", "ge"].iter() {
+                let res = compare(left, op, right);
+                assert!(matches!(res, Err(Error::UnexpectedType { .. })), "{} {} {}", left, op, right);
+            }
+        }
+
+        let message = |left: &str, right: &str| compare(left, "lt", right).unwrap_err().to_string();
+        assert_eq!(message("true", "false"), "expected type Int, Float or String, found Bool");
+        assert_eq!(message("[1]", "[2]"), "expected type Int, Float or String, found Array");
+        assert_eq!(message("1", "~"), "expected type Int, Float or String, found None");
+        assert_eq!(message("1", "a"), "expected type Int or Float, found String");
+        assert_eq!(message("a", "1.5"), "expected type String, found Float");
+    }
+
+    fn from_json(json: &str) -> process::Result<Item> {
+        let exp = Expression::FromJson { from_json: json.into() };
+        let payload = crate::event::sender::Payload::new(vec![]);
+
+        exp.evaluate(payload, State::new()).map(|(item, _, _)| item)
+    }
+
+    #[test]
+    fn test_from_json_ok() {
+        let item = from_json(r#"{"a": {"b": [1, "c", null]}, "d": null}"#).unwrap();
+
+        let mut inner = HashMap::new();
+        inner.insert("b".to_string(), Item::Vec(vec!(
+            Item::Value(Value::IntValue(1)),
+            Item::Value(Value::StringValue("c".into())),
+            Item::Value(Value::None),
+        )));
+        let mut expected = HashMap::new();
+        expected.insert("a".to_string(), Item::Map(inner));
+        expected.insert("d".to_string(), Item::Value(Value::None));
+        assert_eq!(item, Item::Map(expected));
+
+        assert_eq!(from_json("[]").unwrap(), Item::Vec(vec!()));
+        assert_eq!(from_json("null").unwrap(), Item::Value(Value::None));
+    }
+
+    #[test]
+    fn test_from_json_float_ok() {
+        assert_eq!(from_json("1.5").unwrap(), Item::Value(Value::FloatValue(1.5.into())));
+        assert_eq!(from_json("2").unwrap(), Item::Value(Value::IntValue(2)));
+
+        let item = from_json(r#"{"a": -0.25}"#).unwrap();
+        assert_eq!(String::from_utf8(PayloadFormat::Json.to_vec(&item).unwrap()).unwrap(), r#"{"a":-0.25}"#);
+    }
+
+    #[test]
+    fn test_from_json_bool_ok() {
+        let item = from_json(r#"{"enabled": true, "success": false, "active": "true"}"#).unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert("enabled".to_string(), Item::Value(Value::BoolValue(true)));
+        expected.insert("success".to_string(), Item::Value(Value::BoolValue(false)));
+        expected.insert("active".to_string(), Item::Value(Value::StringValue("true".into())));
+        assert_eq!(item, Item::Map(expected));
+
+        let json = PayloadFormat::Json.to_vec(&item).unwrap();
+        let payload = crate::event::sender::Payload::new(json);
+        assert_eq!(PayloadFormat::Json.parse_payload(&payload).unwrap(), item);
+    }
+
+    #[test]
+    fn test_from_json_invalid_err() {
+        assert!(matches!(from_json("{\"a\": "), Err(Error::JsonError(_))));
+    }
+
+    #[test]
+    fn test_match_regex_no_match_ok() {
+        let item = match_regex(r"^(\d+)$", "otter");
+        assert_eq!(item, Item::Value(Value::None));
+    }
+
+    #[test]
+    fn test_match_regex_invalid_pattern_err() {
+        assert!(matches!(RegexPattern::new("("), Err(Error::InvalidRegex { .. })));
+
+        let res = serde_yaml::from_str::<Expression>("match_regex: { pattern: \"(\", input: a }");
+        assert!(res.is_err());
+        let res = serde_yaml::from_str::<Op>("set_env: { target: a, value: { match_regex: { pattern: \"(\", input: a } } }");
+        assert!(res.is_err());
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MatchRegexExpr {
+    pattern: RegexPattern,
+    input: Box<Expression>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ConditionalExpr {
+    condition: Box<Expression>,
+    then_expr: Box<Expression>,
+    else_expr: Box<Expression>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CompareExpr {
+    left: Box<Expression>,
+    op: CompareOp,
+    right: Box<Expression>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn apply(&self, left: &Item, right: &Item) -> process::Result<bool> {
+        let ordering = match (left, right) {
+            (Item::Value(Value::IntValue(l)), Item::Value(Value::IntValue(r))) => l.partial_cmp(r),
+            (Item::Value(Value::IntValue(l)), Item::Value(Value::FloatValue(r))) => (*l as f64).partial_cmp(&r.into_inner()),
+            (Item::Value(Value::FloatValue(l)), Item::Value(Value::IntValue(r))) => l.into_inner().partial_cmp(&(*r as f64)),
+            (Item::Value(Value::FloatValue(l)), Item::Value(Value::FloatValue(r))) => l.partial_cmp(r),
+            (Item::Value(Value::StringValue(l)), Item::Value(Value::StringValue(r))) => l.partial_cmp(r),
+            _ => None,
+        };
+
+        Ok(match (self, ordering) {
+            (CompareOp::Eq, Some(o)) => o.is_eq(),
+            (CompareOp::Ne, Some(o)) => o.is_ne(),
+            (CompareOp::Eq, None) => left == right,
+            (CompareOp::Ne, None) => left != right,
+            (CompareOp::Lt, Some(o)) => o.is_lt(),
+            (CompareOp::Le, Some(o)) => o.is_le(),
+            (CompareOp::Gt, Some(o)) => o.is_gt(),
+            (CompareOp::Ge, Some(o)) => o.is_ge(),
+            (_, None) => {
+                let orderable = |i: &Item| matches!(i, Item::Value(Value::IntValue(_) | Value::FloatValue(_) | Value::StringValue(_)));
+                let (expected, found) = if !orderable(left) {
+                    ("Int, Float or String", left)
+                } else if !orderable(right) {
+                    ("Int, Float or String", right)
+                } else if matches!(left, Item::Value(Value::StringValue(_))) {
+                    ("String", right)
+                } else {
+                    ("Int or Float", right)
+                };
+                return Err(process::Error::UnexpectedType {
+                    expected: expected.into(),
+                    t: found.type_name().into(),
+                });
+            }
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DebugExpr {
+    label: String,
+    expr: Box<Expression>,
+}
+
+impl DebugExpr {
+    fn message(&self, item: &Item) -> String {
+        format!("[{}] {}: {:?}", self.label, item.type_name(), item)
+    }
+}
+
+fn default_separator() -> String {
+    String::from(".")
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FlattenExpr {
+    source: Box<Expression>,
+    #[serde(default = "default_separator")]
+    separator: String,
+    prefix: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct UnflattenExpr {
+    source: Box<Expression>,
+    #[serde(default = "default_separator")]
+    separator: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SetEnv {
+    target: Identifier,
+    value: Box<Expression>,
+    #[serde(default)]
+    sensitive: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ToPayload {
+    format: PayloadFormat,
+    value: Box<Expression>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SetFromPayloadFieldOp {
+    format: PayloadFormat,
+    field: Identifier,
+    target: Identifier,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CopyEnvOp {
+    from: Identifier,
+    to: Identifier,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct IfOp {
+    condition: Expression,
+    then_ops: Vec<Op>,
+    else_ops: Option<Vec<Op>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum StringOp {
+    Upper { upper: StringOpArgs },
+    Lower { lower: StringOpArgs },
+    Trim { trim: StringOpArgs },
+    Split { split: SplitStringOp },
+    Replace { replace: ReplaceStringOp },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StringOpArgs {
+    source: Expression,
+    target: Identifier,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SplitStringOp {
+    #[serde(flatten)]
+    args: StringOpArgs,
+    delimiter: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReplaceStringOp {
+    #[serde(flatten)]
+    args: StringOpArgs,
+    from: String,
+    to: String,
+}
+
+impl StringOp {
+    fn args(&self) -> &StringOpArgs {
+        match self {
+            StringOp::Upper { upper } => upper,
+            StringOp::Lower { lower } => lower,
+            StringOp::Trim { trim } => trim,
+            StringOp::Split { split } => &split.args,
+            StringOp::Replace { replace } => &replace.args,
+        }
+    }
+
+    fn apply(&self, s: String) -> Item {
+        let s = match self {
+            StringOp::Upper { .. } => s.to_uppercase(),
+            StringOp::Lower { .. } => s.to_lowercase(),
+            StringOp::Trim { .. } => s.trim().to_string(),
+            StringOp::Replace { replace } => s.replace(replace.from.as_str(), replace.to.as_str()),
+            StringOp::Split { split } => {
+                return Item::Vec(s.split(split.delimiter.as_str())
+                    .map(|p| Item::Value(Value::StringValue(p.to_string())))
+                    .collect());
+            }
+        };
+
+        Item::Value(Value::StringValue(s))
+    }
+}
 
-        assert_eq!(map.get(&String::from("from_env")), Some(&env_value));
-        assert_eq!(map.get(&String::from("value")), Some(&new_item));
-        assert_eq!(map.get(&String::from("to_env")), Some(&to_env_item));
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArrayMapOp {
+    source: Identifier,
+    item_binding: Identifier,
+    ops: Vec<Op>,
+    target: Identifier,
+}
 
-        assert_eq!(state.len(), 2);
+#[derive(Deserialize, Debug, Clone)]
+pub struct ForEachOp {
+    source: Identifier,
+    item_binding: Identifier,
+    index_binding: Option<Identifier>,
+    ops: Vec<Op>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SleepOp {
+    duration_ms: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<LogLevel> for log::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => log::Level::Trace,
+            LogLevel::Debug => log::Level::Debug,
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Warn => log::Level::Warn,
+            LogLevel::Error => log::Level::Error,
+        }
     }
 }
 
 #[derive(Deserialize, Debug, Clone)]
-pub struct SetEnv {
+pub struct LogOp {
+    level: LogLevel,
+    message: String,
+}
+
+impl LogOp {
+    fn render(&self, state: &State) -> String {
+        static PLACEHOLDER: Lazy<regex::Regex> = Lazy::new(|| regex::Regex::new(r"\{([^{}\s]+)\}").expect("invalid placeholder regex"));
+
+        PLACEHOLDER.replace_all(&self.message, |c: &regex::Captures| {
+            let key = Identifier::from(&c[1]);
+            match state.get(&key) {
+                Some(_) if state.is_sensitive(&key) => process::REDACTED.to_string(),
+                Some(Item::Value(v)) => v.to_string(),
+                Some(i) => serde_json::to_string(&state.redact(&key, i)).unwrap_or_default(),
+                None => String::new(),
+            }
+        }).to_string()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DumpStateOp {
+    level: LogLevel,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArrayFilterOp {
+    source: Identifier,
+    item_binding: Identifier,
+    condition: Expression,
     target: Identifier,
-    value: Box<Expression>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
-pub struct ToPayload {
-    format: PayloadFormat,
+pub struct ArrayLengthOp {
+    source: Identifier,
+    target: Identifier,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArrayPushOp {
+    source: Identifier,
     value: Box<Expression>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
+pub struct ArrayConcatOp {
+    left: Identifier,
+    right: Identifier,
+    target: Identifier,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MergeMapsOp {
+    base: Expression,
+    overlay: Expression,
+    #[serde(default)]
+    deep: bool,
+    target: Identifier,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TimestampOp {
+    target: Identifier,
+    #[serde(flatten)]
+    now: NowExpr,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NowExpr {
+    format: Option<String>,
+    timezone: Option<String>,
+}
+
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+// With a timezone the offset is written out, since the local time is not in UTC.
+const DEFAULT_ZONED_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%:z";
+
+impl NowExpr {
+    fn format(&self, now: chrono::DateTime<chrono::Utc>) -> process::Result<String> {
+        let format = self.format.as_deref().unwrap_or(match self.timezone {
+            None => DEFAULT_TIME_FORMAT,
+            Some(_) => DEFAULT_ZONED_TIME_FORMAT,
+        });
+        let mut res = String::new();
+
+        let written = match &self.timezone {
+            None => write!(res, "{}", now.format(format)),
+            Some(tz) => {
+                let tz = tz.parse::<chrono_tz::Tz>()
+                    .map_err(|_| process::Error::InvalidTimezone(tz.clone()))?;
+                write!(res, "{}", now.with_timezone(&tz).format(format))
+            }
+        };
+
+        written.map_err(|_| process::Error::InvalidTimeFormat(format.into()))?;
+        Ok(res)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GenerateUuidOp {
+    target: Identifier,
+    #[serde(default)]
+    version: UuidVersion,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UuidVersion {
+    #[default]
+    V4,
+    V7,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HmacOp {
+    value: Expression,
+    key: EnvString,
+    algorithm: HmacAlgorithm,
+    encoding: HexOrBase64,
+    target: Identifier,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum HmacAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum HexOrBase64 {
+    Hex,
+    Base64,
+}
+
+impl HmacOp {
+    fn sign(&self, key: &[u8], value: &[u8]) -> process::Result<String> {
+        let invalid_key = |e: hmac::digest::InvalidLength| process::Error::HmacError(e.to_string());
+        let signature = match self.algorithm {
+            HmacAlgorithm::Sha256 => {
+                let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key).map_err(invalid_key)?;
+                mac.update(value);
+                mac.finalize().into_bytes().to_vec()
+            }
+            HmacAlgorithm::Sha512 => {
+                let mut mac = Hmac::<sha2::Sha512>::new_from_slice(key).map_err(invalid_key)?;
+                mac.update(value);
+                mac.finalize().into_bytes().to_vec()
+            }
+        };
+
+        Ok(match self.encoding {
+            HexOrBase64::Hex => hex::encode(signature),
+            HexOrBase64::Base64 => base64::encode(signature),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Base64Op {
+    value: Expression,
+    target: Identifier,
+    mode: Base64Mode,
+    #[serde(default)]
+    alphabet: Base64Alphabet,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Base64Mode {
+    Encode,
+    Decode,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Base64Alphabet {
+    #[default]
+    Standard,
+    UrlSafe,
+    UrlSafeNoPadding,
+}
+
+impl Base64Op {
+    fn apply(&self, value: String) -> process::Result<String> {
+        let config = match self.alphabet {
+            Base64Alphabet::Standard => base64::STANDARD,
+            Base64Alphabet::UrlSafe => base64::URL_SAFE,
+            Base64Alphabet::UrlSafeNoPadding => base64::URL_SAFE_NO_PAD,
+        };
+
+        match self.mode {
+            Base64Mode::Encode => Ok(base64::encode_config(value, config)),
+            Base64Mode::Decode => {
+                let bytes = base64::decode_config(value, config)
+                    .map_err(|e| process::Error::Base64DecodeError(e.to_string()))?;
+                String::from_utf8(bytes)
+                    .map_err(|e| process::Error::Base64DecodeError(format!("decoded value is not valid utf-8: {}", e)))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum EncAlgorithm {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+const NONCE_LEN: usize = 12;
+
+/// Keys and nonces are base64 encoded. The output of `encrypt` is the nonce followed by the
+/// ciphertext, which is what `decrypt` expects; `nonce` is only used when encrypting.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CipherOp {
+    key: EnvString,
+    algorithm: EncAlgorithm,
+    nonce: Option<EnvString>,
+}
+
+impl CipherOp {
+    fn encrypt(&self, plaintext: &[u8], state: &State) -> process::Result<Vec<u8>> {
+        let nonce = match &self.nonce {
+            Some(nonce) => {
+                let nonce = Self::decode(nonce, "nonce", state)?;
+                if nonce.len() != NONCE_LEN {
+                    return Err(process::Error::EncryptionError(format!("nonce must be {} bytes, found {}", NONCE_LEN, nonce.len())));
+                }
+                nonce
+            }
+            None => {
+                let mut nonce = vec!(0; NONCE_LEN);
+                rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut nonce);
+                nonce
+            }
+        };
+
+        let key = Self::decode(&self.key, "key", state)?;
+        let ciphertext = match self.algorithm {
+            EncAlgorithm::Aes256Gcm => Self::seal::<aes_gcm::Aes256Gcm>(&key, &nonce, plaintext),
+            EncAlgorithm::ChaCha20Poly1305 => Self::seal::<chacha20poly1305::ChaCha20Poly1305>(&key, &nonce, plaintext),
+        }?;
+
+        Ok([nonce, ciphertext].concat())
+    }
+
+    fn decrypt(&self, content: &[u8], state: &State) -> process::Result<Vec<u8>> {
+        if content.len() < NONCE_LEN {
+            return Err(process::Error::EncryptionError("payload is shorter than the nonce".into()));
+        }
+        let (nonce, ciphertext) = content.split_at(NONCE_LEN);
+
+        let key = Self::decode(&self.key, "key", state)?;
+        match self.algorithm {
+            EncAlgorithm::Aes256Gcm => Self::open::<aes_gcm::Aes256Gcm>(&key, nonce, ciphertext),
+            EncAlgorithm::ChaCha20Poly1305 => Self::open::<chacha20poly1305::ChaCha20Poly1305>(&key, nonce, ciphertext),
+        }
+    }
+
+    fn decode(value: &EnvString, name: &str, state: &State) -> process::Result<Vec<u8>> {
+        let value = value.to_string(state)
+            .ok_or_else(|| process::Error::EncryptionError(format!("missing value for {}", name)))?;
+        base64::decode(value)
+            .map_err(|e| process::Error::EncryptionError(format!("invalid {}: {}", name, e)))
+    }
+
+    fn seal<C: aes_gcm::aead::Aead + aes_gcm::aead::KeyInit>(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> process::Result<Vec<u8>> {
+        C::new_from_slice(key)
+            .map_err(|_| process::Error::EncryptionError(format!("invalid key length {}", key.len())))?
+            .encrypt(aes_gcm::aead::generic_array::GenericArray::from_slice(nonce), plaintext)
+            .map_err(|_| process::Error::EncryptionError("encryption failed".into()))
+    }
+
+    fn open<C: aes_gcm::aead::Aead + aes_gcm::aead::KeyInit>(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> process::Result<Vec<u8>> {
+        C::new_from_slice(key)
+            .map_err(|_| process::Error::EncryptionError(format!("invalid key length {}", key.len())))?
+            .decrypt(aes_gcm::aead::generic_array::GenericArray::from_slice(nonce), ciphertext)
+            .map_err(|_| process::Error::EncryptionError("decryption failed: ciphertext could not be authenticated".into()))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum JwtAlgorithm {
+    Hs256,
+    Hs512,
+    Rs256,
+    Rs512,
+}
+
+/// `secret_or_key` is the shared secret for HS algorithms and a PEM encoded public key for RS
+/// algorithms. A leading `Bearer ` in the token is ignored.
+#[derive(Deserialize, Debug, Clone)]
+pub struct JwtDecodeOp {
+    token: Expression,
+    secret_or_key: EnvString,
+    algorithm: JwtAlgorithm,
+    target_claims: Identifier,
+    #[serde(default = "default_verify")]
+    verify: bool,
+    issuer: Option<String>,
+}
+
+fn default_verify() -> bool {
+    true
+}
+
+impl JwtDecodeOp {
+    fn decode(&self, token: &str, state: &State) -> process::Result<Item> {
+        use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+
+        let invalid = |e: jsonwebtoken::errors::Error| process::Error::JwtInvalid(e.to_string());
+        let token = token.strip_prefix("Bearer ").unwrap_or(token).trim();
+
+        let algorithm = match self.algorithm {
+            JwtAlgorithm::Hs256 => Algorithm::HS256,
+            JwtAlgorithm::Hs512 => Algorithm::HS512,
+            JwtAlgorithm::Rs256 => Algorithm::RS256,
+            JwtAlgorithm::Rs512 => Algorithm::RS512,
+        };
+        let mut validation = Validation::new(algorithm);
+        validation.validate_nbf = true;
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let key = if self.verify {
+            let secret = self.secret_or_key.to_string(state)
+                .ok_or_else(|| process::Error::JwtInvalid("missing value for secret_or_key".into()))?;
+            match self.algorithm {
+                JwtAlgorithm::Hs256 | JwtAlgorithm::Hs512 => DecodingKey::from_secret(secret.as_bytes()),
+                JwtAlgorithm::Rs256 | JwtAlgorithm::Rs512 => DecodingKey::from_rsa_pem(secret.as_bytes()).map_err(invalid)?,
+            }
+        } else {
+            validation.insecure_disable_signature_validation();
+            validation.validate_exp = false;
+            validation.validate_nbf = false;
+            validation.required_spec_claims.clear();
+            DecodingKey::from_secret(&[])
+        };
+
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &key, &validation)
+            .map_err(invalid)?
+            .claims;
+        match serde_json::from_value(claims)? {
+            Item::Map(claims) => Ok(Item::Map(claims)),
+            i => Err(process::Error::JwtInvalid(format!("claims must be an object, found {}", i.type_name()))),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegexOp {
+    source: Expression,
+    pattern: RegexPattern,
+    target: Identifier,
+    mode: RegexMode,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RegexMode {
+    Matches,
+    CaptureAll,
+    CaptureNamed,
+}
+
+impl RegexOp {
+    fn apply(&self, source: &str) -> Item {
+        let re = &self.pattern.0;
+        let as_item = |s: &str| Item::Value(Value::StringValue(s.into()));
+
+        match self.mode {
+            RegexMode::Matches => Item::Value(Value::BoolValue(re.is_match(source))),
+            RegexMode::CaptureAll => Item::Vec(re.find_iter(source).map(|m| as_item(m.as_str())).collect()),
+            RegexMode::CaptureNamed => match re.captures(source) {
+                None => Item::Value(Value::None),
+                Some(captures) => Item::Map(re.capture_names()
+                    .flatten()
+                    .map(|name| {
+                        let value = captures.name(name)
+                            .map(|m| as_item(m.as_str()))
+                            .unwrap_or(Item::Value(Value::None));
+                        (name.to_string(), value)
+                    })
+                    .collect()),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TemplateSource {
+    Inline { source: String },
+    File {
+        source_file: EnvString,
+        // A path taken from state is read under this directory, so values from the message cannot reach other files.
+        #[serde(default = "default_template_root")]
+        root: std::path::PathBuf,
+    },
+}
+
+fn default_template_root() -> std::path::PathBuf {
+    std::path::PathBuf::from(".")
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RenderTemplateOp {
+    #[serde(flatten)]
+    source: TemplateSource,
+    context: Expression,
+    target: Identifier,
+}
+
+// Compiled templates keyed by their source: the inline template or the template file path. A file
+// is compiled again when the hash of its content changes, replacing the previous entry. Once the
+// cache is full, the least recently used template is evicted.
+static TEMPLATE_CACHE: Lazy<RwLock<HashMap<String, CompiledTemplate>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+static TEMPLATE_CACHE_TICK: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+const TEMPLATE_CACHE_CAPACITY: usize = 256;
+
+#[derive(Clone)]
+struct CompiledTemplate {
+    hash: String,
+    registry: Arc<handlebars::Handlebars<'static>>,
+    last_used: Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl CompiledTemplate {
+    const NAME: &'static str = "template";
+
+    fn compile(hash: String, template: &str) -> process::Result<Self> {
+        let mut registry = handlebars::Handlebars::new();
+        registry.register_template_string(Self::NAME, template)
+            .map_err(|e| process::Error::TemplateError(e.to_string()))?;
+        Ok(CompiledTemplate { hash, registry: Arc::new(registry), last_used: Arc::new(Self::tick().into()) })
+    }
+
+    fn tick() -> u64 {
+        TEMPLATE_CACHE_TICK.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn touch(&self) {
+        self.last_used.store(Self::tick(), std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn insert(cache: &mut HashMap<String, CompiledTemplate>, key: String, compiled: CompiledTemplate) {
+        if cache.len() >= TEMPLATE_CACHE_CAPACITY && !cache.contains_key(&key) {
+            let oldest = cache.iter()
+                .min_by_key(|(_, c)| c.last_used.load(std::sync::atomic::Ordering::Relaxed))
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(key, compiled);
+    }
+}
+
+impl RenderTemplateOp {
+    async fn render(&self, context: &Item, state: &State) -> process::Result<String> {
+        use sha2::Digest;
+
+        let (key, template) = match &self.source {
+            TemplateSource::Inline { source } => (source.clone(), source.clone()),
+            TemplateSource::File { source_file, root } => {
+                let path = source_file.to_string(state)
+                    .ok_or_else(|| process::Error::TemplateError("missing value for source_file".into()))?;
+                let path = source_file.confine_path(root, path)
+                    .map_err(|p| process::Error::TemplateError(format!("template path {} is outside of the template root", p)))?;
+                let template = tokio::fs::read_to_string(&path).await
+                    .map_err(|e| process::Error::TemplateError(format!("unable to read \"{}\": {}", path.display(), e)))?;
+                (path.display().to_string(), template)
+            }
+        };
+        let hash = hex::encode(sha2::Sha256::digest(template.as_bytes()));
+
+        let cached = TEMPLATE_CACHE.read().unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            .filter(|c| c.hash == hash)
+            .cloned();
+        let compiled = match cached {
+            Some(compiled) => {
+                compiled.touch();
+                compiled
+            }
+            None => {
+                let compiled = CompiledTemplate::compile(hash, &template)?;
+                CompiledTemplate::insert(&mut TEMPLATE_CACHE.write().unwrap_or_else(|e| e.into_inner()), key, compiled.clone());
+                compiled
+            }
+        };
+
+        compiled.registry.render(CompiledTemplate::NAME, context)
+            .map_err(|e| process::Error::TemplateError(e.to_string()))
+    }
+}
+
+static REGEX_CACHE: Lazy<Mutex<HashMap<String, regex::Regex>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A regex compiled when the config is loaded. Identical patterns share one compiled instance.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "String")]
+pub struct RegexPattern(regex::Regex);
+
+impl RegexPattern {
+    pub fn new(pattern: &str) -> process::Result<Self> {
+        let mut cache = REGEX_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(re) = cache.get(pattern) {
+            return Ok(RegexPattern(re.clone()));
+        }
+
+        let re = regex::Regex::new(pattern)
+            .map_err(|e| process::Error::InvalidRegex {
+                pattern: pattern.into(),
+                reason: e.to_string(),
+            })?;
+        cache.insert(pattern.into(), re.clone());
+        Ok(RegexPattern(re))
+    }
+}
+
+impl TryFrom<String> for RegexPattern {
+    type Error = process::Error;
+
+    fn try_from(pattern: String) -> process::Result<Self> {
+        RegexPattern::new(pattern.as_str())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArithmeticOp {
+    left: Expression,
+    right: Expression,
+    op: ArithOp,
+    target: Identifier,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn from_item(item: Item) -> process::Result<Self> {
+        match item {
+            Item::Value(Value::IntValue(i)) => Ok(Number::Int(i)),
+            Item::Value(Value::FloatValue(f)) => Ok(Number::Float(f.into_inner())),
+            i => Err(process::Error::UnexpectedType {
+                expected: "Numeric".into(),
+                t: i.type_name().into(),
+            }),
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::Float(f) => *f,
+        }
+    }
+}
+
+impl ArithOp {
+    fn apply(&self, left: Item, right: Item) -> process::Result<Value> {
+        let (left, right) = (Number::from_item(left)?, Number::from_item(right)?);
+
+        if let (Number::Int(l), Number::Int(r)) = (&left, &right) {
+            let (l, r) = (*l, *r);
+            let res = match self {
+                ArithOp::Add => l.checked_add(r),
+                ArithOp::Sub => l.checked_sub(r),
+                ArithOp::Mul => l.checked_mul(r),
+                ArithOp::Div | ArithOp::Mod if r == 0 => return Err(process::Error::DivisionByZero),
+                ArithOp::Div => l.checked_div(r),
+                ArithOp::Mod => l.checked_rem(r),
+            };
+            return res.map(Value::IntValue).ok_or(process::Error::ArithmeticOverflow);
+        }
+
+        let (l, r) = (left.as_f64(), right.as_f64());
+        let res = match self {
+            ArithOp::Add => l + r,
+            ArithOp::Sub => l - r,
+            ArithOp::Mul => l * r,
+            ArithOp::Div | ArithOp::Mod if r == 0.0 => return Err(process::Error::DivisionByZero),
+            ArithOp::Div => l / r,
+            ArithOp::Mod => l % r,
+        };
+        match res.is_finite() {
+            true => Ok(Value::FloatValue(res.into())),
+            false => Err(process::Error::ArithmeticOverflow),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SplitOp {
+    source: Expression,
+    format: Option<PayloadFormat>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(from = "PayloadFormatConfig")]
 pub enum PayloadFormat {
     Yaml,
     Json,
+    LineProtocol,
+    Xml(XmlOptions),
+    Msgpack,
+    Csv { has_header: bool },
+}
+
+// Formats are written as a plain name, or as a map when they take options, e.g. `xml: { root_element: order }`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PayloadFormatConfig {
+    Named(PayloadFormatName),
+    Xml { xml: XmlOptions },
+    Csv { csv: CsvOptions },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PayloadFormatName {
+    Yaml,
+    Json,
+    #[serde(rename = "line_protocol")]
+    LineProtocol,
+    Xml,
+    Msgpack,
+    Csv,
+}
+
+impl From<PayloadFormatConfig> for PayloadFormat {
+    fn from(config: PayloadFormatConfig) -> Self {
+        match config {
+            PayloadFormatConfig::Named(PayloadFormatName::Yaml) => PayloadFormat::Yaml,
+            PayloadFormatConfig::Named(PayloadFormatName::Json) => PayloadFormat::Json,
+            PayloadFormatConfig::Named(PayloadFormatName::LineProtocol) => PayloadFormat::LineProtocol,
+            PayloadFormatConfig::Named(PayloadFormatName::Xml) => PayloadFormat::Xml(XmlOptions::default()),
+            PayloadFormatConfig::Named(PayloadFormatName::Msgpack) => PayloadFormat::Msgpack,
+            PayloadFormatConfig::Named(PayloadFormatName::Csv) => PayloadFormat::Csv { has_header: true },
+            PayloadFormatConfig::Xml { xml } => PayloadFormat::Xml(xml),
+            PayloadFormatConfig::Csv { csv } => PayloadFormat::Csv { has_header: csv.has_header },
+        }
+    }
 }
 
 impl PayloadFormat {
@@ -316,25 +3453,57 @@ impl PayloadFormat {
         Ok(match self {
             PayloadFormat::Yaml => serde_yaml::to_vec(&i)?,
             PayloadFormat::Json => serde_json::to_vec(&i)?,
+            PayloadFormat::LineProtocol => super::line_protocol::to_vec(i)?,
+            PayloadFormat::Xml(options) => super::xml::to_vec(i, options)?,
+            PayloadFormat::Msgpack => rmp_serde::to_vec(&i).map_err(|e| super::Error::SerializationError {
+                format: "msgpack".into(),
+                reason: e.to_string(),
+            })?,
+            PayloadFormat::Csv { has_header } => super::csv::to_vec(i, *has_header)?,
         })
     }
 
+    pub fn content_type(&self) -> &str {
+        match self {
+            PayloadFormat::Yaml => "application/yaml",
+            PayloadFormat::Json => "application/json",
+            PayloadFormat::LineProtocol => "text/plain; charset=utf-8",
+            PayloadFormat::Xml(_) => "application/xml",
+            PayloadFormat::Msgpack => "application/msgpack",
+            PayloadFormat::Csv { .. } => "text/csv",
+        }
+    }
+
     pub fn parse_payload(&self, payload: &Payload) -> super::Result<Item> {
-        Ok(match self {
-            PayloadFormat::Yaml => serde_yaml::from_slice(payload.content.as_slice().clone())?,
-            PayloadFormat::Json => serde_json::from_slice(payload.content.as_slice().clone())?,
-        })
+        let serialization_error = |format: &str, reason: String| super::Error::SerializationError {
+            format: format.into(),
+            reason,
+        };
+
+        match self {
+            PayloadFormat::Yaml => serde_yaml::from_slice(payload.content.as_slice())
+                .map_err(|e| serialization_error("yaml", e.to_string())),
+            PayloadFormat::Json => serde_json::from_slice(payload.content.as_slice())
+                .map_err(|e| serialization_error("json", e.to_string())),
+            PayloadFormat::LineProtocol => Err(super::Error::InvalidLineProtocol {
+                reason: "parsing line protocol payload is not supported".into(),
+            }),
+            PayloadFormat::Xml(_) => super::xml::parse(payload.content.as_slice()),
+            PayloadFormat::Msgpack => rmp_serde::from_slice(payload.content.as_slice())
+                .map_err(|e| serialization_error("msgpack", e.to_string())),
+            PayloadFormat::Csv { has_header } => super::csv::parse(payload.content.as_slice(), *has_header),
+        }
     }
 }
 
 impl From<serde_json::Error> for super::Error {
-    fn from(_: serde_json::Error) -> Self {
-        unimplemented!()
+    fn from(e: serde_json::Error) -> Self {
+        super::Error::JsonDeserialize(e.to_string())
     }
 }
 
 impl From<serde_yaml::Error> for super::Error {
-    fn from(_: serde_yaml::Error) -> Self {
-        unimplemented!()
+    fn from(e: serde_yaml::Error) -> Self {
+        super::Error::YamlDeserialize(e.to_string())
     }
 }
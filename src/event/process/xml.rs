@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use serde::Deserialize;
+
+use crate::event::process::{Error, Item, Result, Value};
+
+const TEXT_KEY: &str = "#text";
+const ATTRIBUTE_PREFIX: char = '@';
+
+fn default_root_element() -> String {
+    String::from("root")
+}
+
+fn default_include_declaration() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct XmlOptions {
+    #[serde(default = "default_root_element")]
+    root_element: String,
+    #[serde(default = "default_include_declaration")]
+    include_declaration: bool,
+}
+
+impl Default for XmlOptions {
+    fn default() -> Self {
+        XmlOptions {
+            root_element: default_root_element(),
+            include_declaration: default_include_declaration(),
+        }
+    }
+}
+
+fn xml_error(e: impl std::fmt::Display) -> Error {
+    Error::XmlError(e.to_string())
+}
+
+fn utf8(bytes: &[u8]) -> Result<String> {
+    String::from_utf8(bytes.to_vec()).map_err(xml_error)
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: HashMap<String, Item>,
+    text: String,
+}
+
+impl Element {
+    fn new(start: &BytesStart) -> Result<Self> {
+        let attributes = start.attributes()
+            .map(|attr| {
+                let attr = attr.map_err(xml_error)?;
+                let value = attr.unescape_value().map_err(xml_error)?;
+                Ok((utf8(attr.key.as_ref())?, value.into_owned()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Element {
+            name: utf8(start.name().as_ref())?,
+            attributes,
+            children: HashMap::new(),
+            text: String::new(),
+        })
+    }
+
+    fn add_child(&mut self, name: String, item: Item) {
+        match self.children.remove(&name) {
+            None => self.children.insert(name, item),
+            Some(Item::Vec(mut items)) => {
+                items.push(item);
+                self.children.insert(name, Item::Vec(items))
+            }
+            Some(existing) => self.children.insert(name, Item::Vec(vec!(existing, item))),
+        };
+    }
+
+    // A plain element becomes its text, anything with attributes or children becomes a Map.
+    fn into_item(self) -> Item {
+        if self.attributes.is_empty() && self.children.is_empty() {
+            return match self.text.is_empty() {
+                true => Item::Value(Value::None),
+                false => Item::Value(Value::StringValue(self.text)),
+            };
+        }
+
+        let mut map = self.children;
+        self.attributes.into_iter().for_each(|(k, v)| {
+            map.insert(format!("{}{}", ATTRIBUTE_PREFIX, k), Item::Value(Value::StringValue(v)));
+        });
+        if !self.text.is_empty() {
+            map.insert(TEXT_KEY.into(), Item::Value(Value::StringValue(self.text)));
+        }
+        Item::Map(map)
+    }
+}
+
+/// Parses an XML document into the content of its root element.
+pub fn parse(content: &[u8]) -> Result<Item> {
+    let mut reader = Reader::from_reader(content);
+    reader.config_mut().trim_text(true);
+
+    let mut stack: Vec<Element> = vec!();
+    let mut root = None;
+
+    loop {
+        let event = reader.read_event().map_err(xml_error)?;
+        if root.is_some() && matches!(event, Event::Start(_) | Event::Empty(_) | Event::Text(_) | Event::CData(_)) {
+            return Err(xml_error("unexpected content after the root element"));
+        }
+
+        let finished = match event {
+            Event::Start(start) => {
+                stack.push(Element::new(&start)?);
+                None
+            }
+            Event::Empty(start) => Some(Element::new(&start)?),
+            Event::End(_) => stack.pop(),
+            Event::Text(text) => {
+                let text = text.unescape().map_err(xml_error)?;
+                match stack.last_mut() {
+                    Some(element) => element.text.push_str(text.as_ref()),
+                    None => return Err(xml_error("text outside of the root element")),
+                }
+                None
+            }
+            Event::CData(data) => {
+                let text = utf8(data.into_inner().as_ref())?;
+                match stack.last_mut() {
+                    Some(element) => element.text.push_str(text.as_str()),
+                    None => return Err(xml_error("text outside of the root element")),
+                }
+                None
+            }
+            Event::Eof => break,
+            _ => None,
+        };
+
+        if let Some(element) = finished {
+            match stack.last_mut() {
+                Some(parent) => {
+                    let name = element.name.clone();
+                    parent.add_child(name, element.into_item());
+                }
+                None => root = Some(element.into_item()),
+            }
+        }
+    }
+
+    match (root, stack.last()) {
+        (_, Some(element)) => Err(xml_error(format!("unclosed element <{}>", element.name))),
+        (None, None) => Err(xml_error("missing root element")),
+        (Some(root), None) => Ok(root),
+    }
+}
+
+fn sorted(map: &HashMap<String, Item>) -> Vec<(&String, &Item)> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(k, _)| *k);
+    entries
+}
+
+fn write_element(writer: &mut Writer<Vec<u8>>, name: &str, item: &Item) -> Result<()> {
+    let mut start = BytesStart::new(name);
+    let (text, children) = match item {
+        Item::Value(Value::None) => (None, vec!()),
+        Item::Value(v) => (Some(v.to_string()), vec!()),
+        Item::Vec(_) => return Err(xml_error(format!("element <{}> cannot be an Array", name))),
+        Item::Map(map) => {
+            let mut text = None;
+            let mut children = vec!();
+
+            for (key, value) in sorted(map) {
+                match (key.strip_prefix(ATTRIBUTE_PREFIX), value) {
+                    (Some(_), Item::Value(Value::None)) => {}
+                    (Some(attr), Item::Value(v)) => start.push_attribute((attr, v.to_string().as_str())),
+                    (Some(attr), i) => return Err(xml_error(format!(
+                        "attribute {} of <{}> must be a Value, found {}", attr, name, i.type_name(),
+                    ))),
+                    (None, Item::Value(v)) if key == TEXT_KEY => text = Some(v.to_string()),
+                    (None, Item::Vec(items)) => items.iter().for_each(|i| children.push((key, i))),
+                    (None, i) => children.push((key, i)),
+                }
+            }
+
+            (text, children)
+        }
+    };
+
+    if text.is_none() && children.is_empty() {
+        return writer.write_event(Event::Empty(start)).map_err(xml_error);
+    }
+
+    writer.write_event(Event::Start(start)).map_err(xml_error)?;
+    if let Some(text) = text {
+        writer.write_event(Event::Text(BytesText::new(text.as_str()))).map_err(xml_error)?;
+    }
+    for (key, child) in children {
+        write_element(writer, key, child)?;
+    }
+    writer.write_event(Event::End(BytesEnd::new(name))).map_err(xml_error)
+}
+
+/// Serializes an item as the content of `root_element`. Map keys are written in sorted order.
+pub fn to_vec(item: &Item, options: &XmlOptions) -> Result<Vec<u8>> {
+    let mut writer = Writer::new(Vec::new());
+    if options.include_declaration {
+        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None))).map_err(xml_error)?;
+    }
+
+    write_element(&mut writer, options.root_element.as_str(), item)?;
+    Ok(writer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOAP_REQUEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/" xmlns:m="http://example.com/orders">
+  <soap:Header>
+    <m:Trace id="abc-123"/>
+  </soap:Header>
+  <soap:Body>
+    <m:CreateOrder>
+      <m:Customer type="business">Otter &amp; Co</m:Customer>
+      <m:Item sku="A1">2</m:Item>
+      <m:Item sku="B2">1</m:Item>
+      <m:Note><![CDATA[fragile <handle with care>]]></m:Note>
+      <m:Gift/>
+    </m:CreateOrder>
+  </soap:Body>
+</soap:Envelope>"#;
+
+    fn options(root_element: &str, include_declaration: bool) -> XmlOptions {
+        XmlOptions {
+            root_element: root_element.into(),
+            include_declaration,
+        }
+    }
+
+    #[test]
+    fn test_parse_ok() {
+        let item = parse(SOAP_REQUEST.as_bytes()).unwrap();
+
+        let expected: Item = serde_json::from_str(r##"{
+            "@xmlns:soap": "http://schemas.xmlsoap.org/soap/envelope/",
+            "@xmlns:m": "http://example.com/orders",
+            "soap:Header": {"m:Trace": {"@id": "abc-123"}},
+            "soap:Body": {
+                "m:CreateOrder": {
+                    "m:Customer": {"@type": "business", "#text": "Otter & Co"},
+                    "m:Item": [{"@sku": "A1", "#text": "2"}, {"@sku": "B2", "#text": "1"}],
+                    "m:Note": "fragile <handle with care>",
+                    "m:Gift": null
+                }
+            }
+        }"##).unwrap();
+        assert_eq!(item, expected);
+    }
+
+    #[test]
+    fn test_round_trip_ok() {
+        let item = parse(SOAP_REQUEST.as_bytes()).unwrap();
+
+        let content = to_vec(&item, &options("soap:Envelope", true)).unwrap();
+        assert!(content.starts_with(br#"<?xml version="1.0" encoding="UTF-8"?><soap:Envelope "#));
+        assert_eq!(parse(content.as_slice()).unwrap(), item);
+    }
+
+    #[test]
+    fn test_to_vec_ok() {
+        let item: Item = serde_json::from_str(r#"{"@id": 1, "name": "a < b", "tags": ["x", "y"], "empty": null}"#).unwrap();
+
+        let content = to_vec(&item, &options("event", false)).unwrap();
+        assert_eq!(
+            String::from_utf8(content).unwrap(),
+            r#"<event id="1"><empty/><name>a &lt; b</name><tags>x</tags><tags>y</tags></event>"#,
+        );
+
+        let content = to_vec(&Item::Value(Value::IntValue(1)), &XmlOptions::default()).unwrap();
+        assert_eq!(String::from_utf8(content).unwrap(), r#"<?xml version="1.0" encoding="UTF-8"?><root>1</root>"#);
+    }
+
+    #[test]
+    fn test_to_vec_err() {
+        let item: Item = serde_json::from_str(r#"{"@id": {"a": 1}}"#).unwrap();
+        assert!(matches!(to_vec(&item, &XmlOptions::default()), Err(Error::XmlError(_))));
+
+        let item: Item = serde_json::from_str(r#"[1, 2]"#).unwrap();
+        assert!(matches!(to_vec(&item, &XmlOptions::default()), Err(Error::XmlError(_))));
+
+        let item: Item = serde_json::from_str(r#"{"a": [[1]]}"#).unwrap();
+        assert!(matches!(to_vec(&item, &XmlOptions::default()), Err(Error::XmlError(_))));
+    }
+
+    #[test]
+    fn test_parse_err() {
+        for content in ["", "<a>", "<a></b>", "<a/><b/>", "text", "<a x=\"1></a>"] {
+            assert!(matches!(parse(content.as_bytes()), Err(Error::XmlError(_))), "{}", content);
+        }
+    }
+}
@@ -1,23 +1,43 @@
-pub fn new_queue<T>(buffer: Option<usize>) -> (QueuePusher<T>, QueuePuller<T>) {
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("queue channel is closed")]
+    ChannelClosed,
+
+    #[error("unable to send message to the queue: {0}")]
+    SendFailed(String),
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// `depth`, when given, is kept at the number of entries waiting in the queue.
+pub fn new_queue<T>(
+    buffer: Option<usize>,
+    depth: Option<prometheus::IntGauge>,
+) -> (QueuePusher<T>, QueuePuller<T>) {
     let (s, r) = match buffer {
         None => crossbeam_channel::unbounded(),
         Some(x) => crossbeam_channel::bounded(x),
     };
 
-    (QueuePusher{s}, QueuePuller{r})
+    (QueuePusher{s, depth: depth.clone()}, QueuePuller{r, depth})
 }
 
 #[derive(Debug)]
 pub struct QueuePusher<T> {
-    s: crossbeam_channel::Sender<T>
+    s: crossbeam_channel::Sender<T>,
+    depth: Option<prometheus::IntGauge>,
 }
 
 impl<T> QueuePusher<T> {
-    pub fn send(&self, o: T) {
+    pub fn send(&self, o: T) -> Result<()> {
         log::trace!("sending an entry to the queue");
-
-        // todo: error handling
-        self.s.send(o).expect("unable to send message");
+        self.s.send(o).map_err(|e| Error::SendFailed(e.to_string()))?;
+        if let Some(depth) = &self.depth {
+            depth.set(self.s.len() as i64);
+        }
+        Ok(())
     }
 }
 
@@ -25,28 +45,118 @@ impl<T> Clone for QueuePusher<T> {
     fn clone(&self) -> Self {
         QueuePusher{
             s: self.s.clone(),
+            depth: self.depth.clone(),
         }
     }
 }
 
 #[derive(Debug)]
 pub struct QueuePuller<T> {
-    r: crossbeam_channel::Receiver<T>
+    r: crossbeam_channel::Receiver<T>,
+    depth: Option<prometheus::IntGauge>,
 }
 
 impl<T> Clone for QueuePuller<T> {
     fn clone(&self) -> Self {
         QueuePuller{
             r: self.r.clone(),
+            depth: self.depth.clone(),
         }
     }
 }
 
 impl<T> QueuePuller<T> {
-    pub fn recv(&self) -> T {
+    pub fn recv(&self) -> Result<T> {
         log::trace!("receiving an entry in the queue");
-        // todo: error handling
-        // todo: closed queue
-        self.r.recv().expect("unable to get message")
+        let o = self.r.recv().map_err(|_| Error::ChannelClosed)?;
+        if let Some(depth) = &self.depth {
+            depth.set(self.r.len() as i64);
+        }
+        Ok(o)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_recv_ok() {
+        let (s, r) = new_queue(None, None);
+        s.send(1).unwrap();
+        s.send(2).unwrap();
+
+        assert_eq!(r.recv().unwrap(), 1);
+        assert_eq!(r.recv().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_depth_ok() {
+        let depth = prometheus::IntGauge::new("depth", "depth").unwrap();
+        let (s, r) = new_queue(Some(4), Some(depth.clone()));
+        s.send(1).unwrap();
+        s.send(2).unwrap();
+        assert_eq!(depth.get(), 2);
+
+        r.recv().unwrap();
+        assert_eq!(depth.get(), 1);
+    }
+
+    #[test]
+    fn test_recv_closed_err() {
+        let (s, r) = new_queue::<i32>(None, None);
+        drop(s);
+
+        assert!(matches!(r.recv(), Err(Error::ChannelClosed)));
+    }
+
+    #[test]
+    fn test_send_closed_err() {
+        let (s, r) = new_queue(None, None);
+        drop(r);
+
+        assert!(matches!(s.send(1), Err(Error::SendFailed(_))));
+    }
+
+    // Bursty producer against a consumer that takes 1ms per message. Run with
+    // `cargo test --release bench_ -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_throughput_by_capacity() {
+        const BURSTS: usize = 20;
+        const BURST_SIZE: usize = 50;
+
+        for capacity in [Some(0), Some(1), Some(16), Some(256), None] {
+            let (s, r) = new_queue(capacity, None);
+            let start = std::time::Instant::now();
+
+            let producer = std::thread::spawn(move || {
+                let mut blocked = std::time::Duration::ZERO;
+                for _ in 0..BURSTS {
+                    for i in 0..BURST_SIZE {
+                        let t = std::time::Instant::now();
+                        s.send(i).unwrap();
+                        blocked += t.elapsed();
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+                blocked
+            });
+
+            let mut received = 0;
+            while r.recv().is_ok() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                received += 1;
+            }
+            let blocked = producer.join().unwrap();
+            let elapsed = start.elapsed();
+
+            println!(
+                "capacity {:>9}: {:>6.0} msg/s, producer blocked for {:?}",
+                format!("{:?}", capacity),
+                received as f64 / elapsed.as_secs_f64(),
+                blocked,
+            );
+        }
     }
 }
\ No newline at end of file
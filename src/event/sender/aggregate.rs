@@ -0,0 +1,218 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::event::process::State;
+use crate::event::sender::{Error, Payload, Result, Sender, SenderConfig};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AggregateSenderConfig {
+    inner: Box<SenderConfig>,
+    max_batch_size: usize,
+    max_wait_ms: u64,
+}
+
+struct Batch {
+    payloads: Vec<Payload>,
+    state: State,
+}
+
+pub struct AggregateSender {
+    inner: Arc<dyn Sender>,
+    batch: Arc<Mutex<Batch>>,
+    max_batch_size: usize,
+    ticker: tokio::task::JoinHandle<()>,
+}
+
+impl AggregateSender {
+    pub fn new(config: &AggregateSenderConfig) -> Result<Self> {
+        let inner = super::new_sender(&config.inner)?;
+        Ok(Self::with_inner(inner.into(), config.max_batch_size, config.max_wait_ms))
+    }
+
+    fn with_inner(inner: Arc<dyn Sender>, max_batch_size: usize, max_wait_ms: u64) -> Self {
+        let batch = Arc::new(Mutex::new(Batch { payloads: vec!(), state: State::new() }));
+
+        let ticker = {
+            let inner = inner.clone();
+            let batch = batch.clone();
+            let period = tokio::time::Duration::from_millis(max_wait_ms.max(1));
+            crate::event::utils::logger::spawn(async move {
+                let mut interval = tokio::time::interval(period);
+                // the first tick completes immediately
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    log::trace!("aggregate sender time-based flush");
+                    if let Err(e) = Self::flush_batch(inner.as_ref(), &batch).await {
+                        log::error!("error flushing aggregated payloads: {}", e);
+                    }
+                }
+            })
+        };
+
+        AggregateSender {
+            inner,
+            batch,
+            max_batch_size,
+            ticker,
+        }
+    }
+
+    /// Sends the buffered payloads as one JSON array. When the send fails the payloads go back to
+    /// the front of the batch, since they were acknowledged when they were buffered.
+    async fn flush_batch(inner: &dyn Sender, batch: &Mutex<Batch>) -> Result<()> {
+        let (payloads, mut state) = {
+            let mut batch = batch.lock().expect("aggregate batch lock poisoned");
+            (std::mem::take(&mut batch.payloads), batch.state.clone())
+        };
+
+        if payloads.is_empty() {
+            return Ok(());
+        }
+
+        log::debug!("flushing {} aggregated payloads", payloads.len());
+        let res = match Self::encode(&payloads) {
+            Ok(content) => inner.send(Payload::new(content), &mut state).await,
+            Err(e) => Err(e),
+        };
+
+        if res.is_err() {
+            log::warn!("keeping {} aggregated payloads for the next flush", payloads.len());
+            let mut batch = batch.lock().expect("aggregate batch lock poisoned");
+            let newer = std::mem::replace(&mut batch.payloads, payloads);
+            batch.payloads.extend(newer);
+        }
+        res
+    }
+
+    fn encode(payloads: &[Payload]) -> Result<Vec<u8>> {
+        let items = payloads.iter()
+            .map(|p| {
+                serde_json::from_slice(p.content.as_slice())
+                    .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&p.content).into()))
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_vec(&items)
+            .map_err(|e| Error::Serialization(e.to_string()))
+    }
+}
+
+impl Drop for AggregateSender {
+    fn drop(&mut self) {
+        self.ticker.abort();
+    }
+}
+
+#[async_trait]
+impl Sender for AggregateSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        let full = {
+            let mut batch = self.batch.lock().expect("aggregate batch lock poisoned");
+            batch.payloads.push(payload);
+            batch.state = state.clone();
+            batch.payloads.len() >= self.max_batch_size
+        };
+
+        if full {
+            log::trace!("aggregate sender size-based flush");
+            Self::flush_batch(self.inner.as_ref(), &self.batch).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        Self::flush_batch(self.inner.as_ref(), &self.batch).await?;
+        self.inner.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct MockSender {
+        received: Mutex<Vec<Payload>>,
+        failures: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl Sender for MockSender {
+        async fn send(&self, payload: Payload, _: &mut State) -> Result<()> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(Error::IoError("mock failure".into()));
+            }
+            self.received.lock().unwrap().push(payload);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_batch_size() {
+        let mock = Arc::new(MockSender::default());
+        let sender = AggregateSender::with_inner(mock.clone(), 2, 60_000);
+
+        let res = sender.send(Payload::new("1".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+        assert!(mock.received.lock().unwrap().is_empty());
+
+        let res = sender.send(Payload::new("{\"a\": 2}".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+
+        let received = mock.received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].content, "[1,{\"a\":2}]".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_max_wait() {
+        let mock = Arc::new(MockSender::default());
+        let sender = AggregateSender::with_inner(mock.clone(), 100, 50);
+
+        let res = sender.send(Payload::new("not json".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+        assert!(mock.received.lock().unwrap().is_empty());
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+
+        let received = mock.received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].content, "[\"not json\"]".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_shutdown() {
+        let mock = Arc::new(MockSender::default());
+        let sender = AggregateSender::with_inner(mock.clone(), 100, 60_000);
+
+        let _ = sender.send(Payload::new("1".into()), &mut State::new()).await;
+        let res = sender.flush().await;
+        assert!(res.is_ok());
+
+        let received = mock.received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].content, "[1]".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_keeps_payloads() {
+        let mock = Arc::new(MockSender { failures: Mutex::new(1), ..Default::default() });
+        let sender = AggregateSender::with_inner(mock.clone(), 2, 60_000);
+
+        let _ = sender.send(Payload::new("1".into()), &mut State::new()).await;
+        let res = sender.send(Payload::new("2".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::IoError(_))));
+        assert!(mock.received.lock().unwrap().is_empty());
+
+        let _ = sender.send(Payload::new("3".into()), &mut State::new()).await;
+
+        let received = mock.received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].content, "[1,2,3]".as_bytes());
+    }
+}
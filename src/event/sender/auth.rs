@@ -0,0 +1,38 @@
+use serde::Deserialize;
+
+use crate::event::process::State;
+use crate::event::sender::{EnvString, Error, Result};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum HttpAuth {
+    Bearer { token: EnvString },
+    Basic { username: EnvString, password: Option<EnvString> },
+    ApiKey { header: String, value: EnvString },
+}
+
+impl HttpAuth {
+    pub fn apply(&self, request: reqwest::RequestBuilder, state: &State) -> Result<reqwest::RequestBuilder> {
+        Ok(match self {
+            HttpAuth::Bearer { token } => {
+                let token = token.to_string(state)
+                    .ok_or_else(|| Error::MissingValue("token".into()))?;
+                request.bearer_auth(token)
+            }
+            HttpAuth::Basic { username, password } => {
+                let username = username.to_string(state)
+                    .ok_or_else(|| Error::MissingValue("username".into()))?;
+                let password = match password {
+                    None => None,
+                    Some(p) => Some(p.to_string(state).ok_or_else(|| Error::MissingValue("password".into()))?),
+                };
+                request.basic_auth(username, password)
+            }
+            HttpAuth::ApiKey { header, value } => {
+                let value = value.to_string(state)
+                    .ok_or_else(|| Error::MissingValue("value".into()))?;
+                request.header(header.as_str(), value)
+            }
+        })
+    }
+}
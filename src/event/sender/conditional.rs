@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+
+use crate::event::process::operation::Expression;
+use crate::event::process::State;
+use crate::event::sender::{Error, Payload, Result, Sender};
+
+pub struct ConditionalSender {
+    condition: Expression,
+    inner: Box<dyn Sender>,
+}
+
+impl ConditionalSender {
+    pub fn new(condition: Expression, inner: Box<dyn Sender>) -> Self {
+        ConditionalSender { condition, inner }
+    }
+}
+
+#[async_trait]
+impl Sender for ConditionalSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        // The condition is evaluated against copies so it cannot change what the sender sees.
+        let (item, _, _) = self.condition.evaluate(payload.clone(), state.clone())
+            .map_err(|e| Error::Condition(e.to_string()))?;
+
+        if !item.is_truthy() {
+            log::debug!("sender condition evaluated to {:?}, skipping", item);
+            return Ok(());
+        }
+
+        self.inner.send(payload, state).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+
+    fn captures_response(&self) -> bool {
+        self.inner.captures_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::sender::mock;
+
+    fn sender(name: &str, condition: &str) -> ConditionalSender {
+        let config: mock::MockSenderConfig = serde_yaml::from_str(format!("name: {}", name).as_str()).unwrap();
+        ConditionalSender::new(
+            serde_yaml::from_str(condition).unwrap(),
+            Box::new(mock::MockSender::new(&config)),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_truthy_condition_sends() {
+        let sender = sender("conditional_truthy", "true");
+
+        sender.send(Payload::new(b"a".to_vec()), &mut State::new()).await.unwrap();
+        assert_eq!(mock::sent("conditional_truthy"), vec!(b"a".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_falsy_condition_skips() {
+        let sender = sender("conditional_falsy", "from_state: missing");
+
+        sender.send(Payload::new(b"a".to_vec()), &mut State::new()).await.unwrap();
+        assert!(mock::sent("conditional_falsy").is_empty());
+    }
+}
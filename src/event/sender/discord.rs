@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::event::process::State;
+use crate::event::sender::{EnvString, Error, Payload, Result, Sender};
+
+const MAX_ATTEMPTS: usize = 3;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct DiscordSenderConfig {
+    webhook_url: EnvString,
+    username: Option<EnvString>,
+    avatar_url: Option<EnvString>,
+    #[serde(default)]
+    embed: bool,
+}
+
+pub struct DiscordSender {
+    config: DiscordSenderConfig,
+    client: reqwest::Client,
+}
+
+impl DiscordSender {
+    pub fn new(config: &DiscordSenderConfig) -> Self {
+        DiscordSender {
+            config: config.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn body(&self, payload: &Payload, state: &State) -> serde_json::Value {
+        let text = String::from_utf8_lossy(&payload.content).to_string();
+
+        let mut body = serde_json::Map::new();
+        if self.config.embed {
+            body.insert("embeds".into(), serde_json::json!([{ "description": text }]));
+        } else {
+            body.insert("content".into(), serde_json::Value::String(text));
+        }
+
+        if let Some(username) = self.config.username.as_ref().and_then(|u| u.to_string(state)) {
+            body.insert("username".into(), serde_json::Value::String(username));
+        }
+        if let Some(avatar_url) = self.config.avatar_url.as_ref().and_then(|u| u.to_string(state)) {
+            body.insert("avatar_url".into(), serde_json::Value::String(avatar_url));
+        }
+
+        serde_json::Value::Object(body)
+    }
+
+    async fn retry_after(resp: reqwest::Response) -> tokio::time::Duration {
+        let header = resp.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.parse::<f64>().ok());
+
+        let body = resp.bytes().await.ok()
+            .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).ok())
+            .and_then(|b| b.get("retry_after").and_then(|r| r.as_f64()));
+
+        tokio::time::Duration::from_secs_f64(body.or(header).unwrap_or(1.0))
+    }
+}
+
+#[async_trait]
+impl Sender for DiscordSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        let url = self.config.webhook_url.to_string(state)
+            .ok_or_else(|| Error::MissingValue("webhook_url".into()))?;
+        let body = serde_json::to_vec(&self.body(&payload, state))
+            .map_err(|e| Error::Serialization(e.to_string()))?;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            log::debug!("sending discord webhook (attempt {})", attempt);
+            let resp = self.client
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone())
+                .send()
+                .await?;
+
+            if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                let wait = Self::retry_after(resp).await;
+                log::warn!("discord webhook rate limited, retrying in {:?}", wait);
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+
+            if !resp.status().is_success() {
+                return Err(Error::HttpStatus { url, status: resp.status().as_u16() });
+            }
+
+            return Ok(());
+        }
+
+        Err(Error::HttpError(format!("discord webhook still rate limited after {} attempts", MAX_ATTEMPTS)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_json, header, method, path};
+
+    use super::*;
+
+    fn sender(server: &MockServer, embed: bool) -> DiscordSender {
+        DiscordSender::new(&DiscordSenderConfig {
+            webhook_url: EnvString::String(format!("{}/api/webhooks/1/abc", server.uri())),
+            username: Some(EnvString::String("webhook".into())),
+            avatar_url: None,
+            embed,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_send_content_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/webhooks/1/abc"))
+            .and(header("content-type", "application/json"))
+            .and(body_json(serde_json::json!({"content": "hello", "username": "webhook"})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let res = sender(&server, false).send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_embed_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_json(serde_json::json!({"embeds": [{"description": "hello"}], "username": "webhook"})))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let res = sender(&server, true).send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_rate_limited_retry() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(serde_json::json!({"retry_after": 0.05})))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let res = sender(&server, false).send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_failed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&server)
+            .await;
+
+        let res = sender(&server, false).send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::HttpStatus { status: 400, .. })));
+    }
+}
@@ -0,0 +1,167 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::event::process::State;
+use crate::event::sender::{EnvString, Error, Payload, Result, Sender};
+use crate::event::sender::auth::HttpAuth;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ElasticsearchSenderConfig {
+    endpoint: EnvString,
+    index: EnvString,
+    auth: Option<HttpAuth>,
+    doc_id: Option<EnvString>,
+}
+
+pub struct ElasticsearchSender {
+    config: ElasticsearchSenderConfig,
+    client: reqwest::Client,
+}
+
+impl ElasticsearchSender {
+    pub fn new(config: &ElasticsearchSenderConfig) -> Self {
+        ElasticsearchSender {
+            config: config.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn url(&self, state: &State) -> Result<String> {
+        let endpoint = self.config.endpoint.to_string(state)
+            .ok_or_else(|| Error::MissingValue("endpoint".into()))?;
+        let index = self.config.index.to_string(state)
+            .ok_or_else(|| Error::MissingValue("index".into()))?;
+
+        let endpoint = endpoint.trim_end_matches('/');
+        Ok(match &self.config.doc_id {
+            None => format!("{}/{}/_doc", endpoint, index),
+            Some(doc_id) => {
+                let doc_id = doc_id.to_string(state)
+                    .ok_or_else(|| Error::MissingValue("doc_id".into()))?;
+                format!("{}/{}/_doc/{}", endpoint, index, doc_id)
+            }
+        })
+    }
+
+    fn error_reason(body: &[u8]) -> String {
+        serde_json::from_slice::<serde_json::Value>(body).ok()
+            .and_then(|b| b.get("error").cloned())
+            .map(|e| e.to_string())
+            .unwrap_or_else(|| String::from_utf8_lossy(body).to_string())
+    }
+}
+
+#[async_trait]
+impl Sender for ElasticsearchSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        serde_json::from_slice::<serde_json::Value>(&payload.content)
+            .map_err(|e| Error::InvalidPayload(format!("elasticsearch document must be valid json: {}", e)))?;
+
+        let url = self.url(state)?;
+        log::debug!("indexing document to elasticsearch at \"{}\"", url);
+
+        let request = self.client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.content);
+        let request = match &self.config.auth {
+            None => request,
+            Some(auth) => auth.apply(request, state)?,
+        };
+
+        let resp = request.send().await?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.bytes().await.unwrap_or_default();
+            return Err(Error::HttpError(format!(
+                "elasticsearch indexing failed with code {}: {}",
+                status,
+                Self::error_reason(&body),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_json, header, method, path};
+
+    use crate::event::process::{Item, Value};
+
+    use super::*;
+
+    fn config(server: &MockServer, doc_id: Option<EnvString>) -> ElasticsearchSenderConfig {
+        ElasticsearchSenderConfig {
+            endpoint: EnvString::String(server.uri()),
+            index: EnvString::String("events".into()),
+            auth: Some(HttpAuth::Bearer { token: EnvString::String("secret".into()) }),
+            doc_id,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_index_with_id_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/events/_doc/abc"))
+            .and(header("authorization", "Bearer secret"))
+            .and(body_json(serde_json::json!({"a": 1})))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut state = State::new();
+        let _ = state.set("id".into(), Item::Value(Value::StringValue("abc".into())));
+        let sender = ElasticsearchSender::new(&config(&server, Some(EnvString::FromEnv { from_env: "id".into(), sensitive: false })));
+
+        let res = sender.send(Payload::new("{\"a\": 1}".into()), &mut state).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_index_auto_id_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/events/_doc"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let sender = ElasticsearchSender::new(&config(&server, None));
+
+        let res = sender.send(Payload::new("{\"a\": 1}".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_index_invalid_payload() {
+        let server = MockServer::start().await;
+        let sender = ElasticsearchSender::new(&config(&server, None));
+
+        let res = sender.send(Payload::new("not json".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::InvalidPayload(_))));
+    }
+
+    #[tokio::test]
+    async fn test_index_error_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": {"type": "mapper_parsing_exception", "reason": "failed to parse"},
+                "status": 400,
+            })))
+            .mount(&server)
+            .await;
+
+        let sender = ElasticsearchSender::new(&config(&server, None));
+
+        let res = sender.send(Payload::new("{\"a\": 1}".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::HttpError(e)) if e.contains("mapper_parsing_exception")));
+    }
+}
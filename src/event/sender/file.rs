@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::event::process::State;
+use crate::event::sender::{EnvString, Error, Payload, Result, Sender};
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    #[default]
+    Append,
+    Overwrite,
+    CreateNew,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct FileSenderConfig {
+    path: EnvString,
+    #[serde(default)]
+    mode: WriteMode,
+    // A path taken from state (templated or from_env) is written under this directory, so values from the message cannot escape it.
+    #[serde(default = "default_root")]
+    root: PathBuf,
+}
+
+fn default_root() -> PathBuf {
+    PathBuf::from(".")
+}
+
+pub struct FileSender {
+    config: FileSenderConfig,
+}
+
+impl FileSender {
+    pub fn new(config: &FileSenderConfig) -> Result<Self> {
+        let empty = match &config.path {
+            EnvString::String(s) | EnvString::Template { template: s } => s.trim().is_empty(),
+            EnvString::FromEnv { .. } => false,
+        };
+        if empty {
+            return Err(Error::IoError("file sender path must not be empty".into()));
+        }
+
+        Ok(FileSender {
+            config: config.clone(),
+        })
+    }
+
+    fn options(&self) -> tokio::fs::OpenOptions {
+        let mut options = tokio::fs::OpenOptions::new();
+        match self.config.mode {
+            WriteMode::Append => options.append(true).create(true),
+            WriteMode::Overwrite => options.write(true).truncate(true).create(true),
+            WriteMode::CreateNew => options.write(true).create_new(true),
+        };
+        options
+    }
+}
+
+#[async_trait]
+impl Sender for FileSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        let path = self.config.path.to_string(state)
+            .ok_or_else(|| Error::MissingValue("path".into()))?;
+        let path = self.config.path.confine_path(&self.config.root, path)
+            .map_err(Error::PathOutsideRoot)?;
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await
+                .map_err(|e| Error::IoError(format!("unable to create {}: {}", parent.display(), e)))?;
+        }
+
+        log::debug!("writing {} bytes to {} ({:?})", payload.content.len(), path.display(), self.config.mode);
+        let mut file = self.options().open(&path).await
+            .map_err(|e| Error::IoError(format!("unable to open {}: {}", path.display(), e)))?;
+        file.write_all(&payload.content).await
+            .map_err(|e| Error::IoError(format!("unable to write {}: {}", path.display(), e)))?;
+        file.flush().await
+            .map_err(|e| Error::IoError(format!("unable to write {}: {}", path.display(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event::process::{Item, Value};
+
+    use super::*;
+
+    fn sender(dir: &tempfile::TempDir, mode: &str) -> FileSender {
+        let config = serde_yaml::from_str(format!(
+            "path:\n  template: \"archive/{{{{ name }}}}/{{{{ id }}}}.json\"\nmode: {}\nroot: \"{}\"",
+            mode, dir.path().display(),
+        ).as_str()).unwrap();
+        FileSender::new(&config).unwrap()
+    }
+
+    fn state() -> State {
+        let mut state = State::new();
+        let _ = state.set("name".into(), Item::Value(Value::StringValue("orders".into())));
+        let _ = state.set("id".into(), Item::Value(Value::IntValue(42)));
+        state
+    }
+
+    fn read(dir: &tempfile::TempDir) -> String {
+        std::fs::read_to_string(dir.path().join("archive/orders/42.json")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_append_ok() {
+        let dir = tempfile::tempdir().unwrap();
+        let sender = sender(&dir, "append");
+
+        assert!(sender.send(Payload::new("a\n".into()), &mut state()).await.is_ok());
+        assert!(sender.send(Payload::new("b\n".into()), &mut state()).await.is_ok());
+        assert_eq!(read(&dir), "a\nb\n");
+    }
+
+    #[tokio::test]
+    async fn test_send_overwrite_ok() {
+        let dir = tempfile::tempdir().unwrap();
+        let sender = sender(&dir, "overwrite");
+
+        assert!(sender.send(Payload::new("first".into()), &mut state()).await.is_ok());
+        assert!(sender.send(Payload::new("b".into()), &mut state()).await.is_ok());
+        assert_eq!(read(&dir), "b");
+    }
+
+    #[tokio::test]
+    async fn test_send_create_new_err() {
+        let dir = tempfile::tempdir().unwrap();
+        let sender = sender(&dir, "create_new");
+
+        assert!(sender.send(Payload::new("a".into()), &mut state()).await.is_ok());
+        let res = sender.send(Payload::new("b".into()), &mut state()).await;
+        assert!(matches!(res, Err(Error::IoError(_))));
+        assert_eq!(read(&dir), "a");
+    }
+
+    #[tokio::test]
+    async fn test_send_missing_value_err() {
+        let dir = tempfile::tempdir().unwrap();
+        let res = sender(&dir, "append").send(Payload::new("a".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::MissingValue(_))));
+    }
+
+    #[tokio::test]
+    async fn test_send_outside_root_err() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = state();
+        let _ = state.set("name".into(), Item::Value(Value::StringValue("../..".into())));
+        let res = sender(&dir, "append").send(Payload::new("a".into()), &mut state).await;
+        assert!(matches!(res, Err(Error::PathOutsideRoot(_))));
+
+        let config = serde_yaml::from_str("path: { template: \"{{ name }}\" }").unwrap();
+        let mut state = State::new();
+        let name = dir.path().join("a.json").display().to_string();
+        let _ = state.set("name".into(), Item::Value(Value::StringValue(name)));
+        let res = FileSender::new(&config).unwrap().send(Payload::new("a".into()), &mut state).await;
+        assert!(matches!(res, Err(Error::PathOutsideRoot(_))));
+        assert!(!dir.path().join("a.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_send_from_env_outside_root_err() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = serde_yaml::from_str(format!(
+            "path: {{ from_env: target }}\nroot: \"{}\"", dir.path().display(),
+        ).as_str()).unwrap();
+        let sender = FileSender::new(&config).unwrap();
+
+        for target in ["../escaped.json".to_string(), dir.path().join("abs.json").display().to_string()] {
+            let mut state = State::new();
+            let _ = state.set("target".into(), Item::Value(Value::StringValue(target.clone())));
+            let res = sender.send(Payload::new("a".into()), &mut state).await;
+            assert!(matches!(res, Err(Error::PathOutsideRoot(_))), "{}", target);
+        }
+        assert!(!dir.path().join("abs.json").exists());
+
+        let mut state = State::new();
+        let _ = state.set("target".into(), Item::Value(Value::StringValue("inside.json".into())));
+        assert!(sender.send(Payload::new("a".into()), &mut state).await.is_ok());
+        assert_eq!(std::fs::read_to_string(dir.path().join("inside.json")).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_new_empty_path_err() {
+        let config: FileSenderConfig = serde_yaml::from_str("path: \"\"").unwrap();
+        assert!(matches!(FileSender::new(&config), Err(Error::IoError(_))));
+        assert_eq!(config.mode, WriteMode::Append);
+        assert_eq!(config.root, PathBuf::from("."));
+    }
+}
@@ -1,21 +1,92 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
-use crate::event::sender::{Sender, Payload, Result};
+use crate::event::process::{Identifier, State};
+use crate::event::process::operation::PayloadFormat;
+use crate::event::sender::auth::HttpAuth;
+use crate::event::sender::{Sender, Payload, Result, Error};
+use rand::Rng;
 use serde::Deserialize;
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct HttpSenderConfig {
-    http: Vec<HttpSenderType>
+    http: Vec<HttpSenderType>,
+    timeout_seconds: Option<u64>,
+    connect_timeout_seconds: Option<u64>,
+    max_body_bytes: Option<usize>,
+    #[serde(default = "default_auto_content_type")]
+    auto_content_type: bool,
+}
+
+fn default_auto_content_type() -> bool {
+    true
+}
+
+fn detect_content_type(content: &[u8]) -> Option<&'static str> {
+    let start = content.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(content.len());
+    match &content[start..] {
+        [b'{', ..] | [b'[', ..] => Some("application/json"),
+        [b'-', b'-', b'-', ..] => Some("application/yaml"),
+        _ => None,
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
 #[serde(untagged)]
 enum HttpSenderType {
     Post { post: HttpSenderUrlConfig },
+    Put { put: HttpSenderUrlConfig },
+    Patch { patch: HttpSenderUrlConfig },
+    Delete { delete: HttpSenderUrlConfig },
+    PostForm { post_form: FormSenderConfig },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct FormSenderConfig {
+    url: super::EnvString,
+    fields: HashMap<String, super::EnvString>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 struct HttpSenderUrlConfig {
-    url: super::EnvString
+    url: super::EnvString,
+    headers: Option<HashMap<String, super::EnvString>>,
+    auth: Option<HttpAuth>,
+    retry: Option<RetryConfig>,
+    capture_response: Option<CaptureConfig>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct CaptureConfig {
+    key: Identifier,
+    format: PayloadFormat,
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct RetryConfig {
+    max_attempts: usize,
+    initial_backoff_ms: u64,
+    #[serde(default = "default_multiplier")]
+    multiplier: f64,
+    #[serde(default)]
+    jitter_factor: f64,
+}
+
+impl RetryConfig {
+    fn backoff(&self, attempt: usize) -> tokio::time::Duration {
+        let base = self.initial_backoff_ms as f64 * self.multiplier.powi(attempt as i32 - 1);
+        let jitter = if self.jitter_factor > 0.0 {
+            rand::thread_rng().gen_range(-self.jitter_factor..=self.jitter_factor)
+        } else {
+            0.0
+        };
+
+        tokio::time::Duration::from_millis((base * (1.0 + jitter)).max(0.0) as u64)
+    }
 }
 
 pub struct HttpSender {
@@ -24,47 +95,665 @@ pub struct HttpSender {
 }
 
 impl HttpSender {
-    pub fn new(config: &HttpSenderConfig) -> Self {
-        HttpSender{
+    pub fn new(config: &HttpSenderConfig) -> Result<Self> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = config.timeout_seconds {
+            builder = builder.timeout(tokio::time::Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = config.connect_timeout_seconds {
+            builder = builder.connect_timeout(tokio::time::Duration::from_secs(timeout));
+        }
+
+        Ok(HttpSender{
             config: config.clone(),
-            client: reqwest::Client::new(),
+            client: builder.build()?,
+        })
+    }
+
+    async fn request(&self, method: reqwest::Method, config: &HttpSenderUrlConfig, payload: &Payload, state: &mut State) -> Result<()> {
+        let url = config.url.to_string(state)
+            .ok_or_else(|| Error::MissingValue("url".into()))?;
+        let headers = config.headers.iter()
+            .flatten()
+            .filter_map(|(k, v)| match v.to_string(state) {
+                Some(v) => Some((k.as_str(), v)),
+                None => {
+                    log::warn!("skipping header \"{}\" for \"{}\": value not found", k, url);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let content_type = self.content_type(config, payload);
+        let max_attempts = config.retry.as_ref().map(|r| r.max_attempts.max(1)).unwrap_or(1);
+
+        let mut status = None;
+        let mut timed_out = false;
+        for attempt in 1..=max_attempts {
+            if attempt > 1 {
+                if let Some(retry) = &config.retry {
+                    let wait = retry.backoff(attempt - 1);
+                    log::warn!("retrying HTTP {} to \"{}\" in {:?} (attempt {})", method, url, wait, attempt);
+                    tokio::time::sleep(wait).await;
+                }
+            }
+
+            log::debug!("sending HTTP {} to \"{}\" with {} bytes", method, url, payload.content.len());
+            let req = headers.iter()
+                .fold(self.client.request(method.clone(), &url), |req, (k, v)| req.header(*k, v.as_str()));
+            let req = match &content_type {
+                Some(content_type) => req.header(reqwest::header::CONTENT_TYPE, content_type.as_str()),
+                None => req,
+            };
+            let req = match &config.auth {
+                Some(auth) => auth.apply(req, state)?,
+                None => req,
+            };
+            let res = req
+                .body(payload.content.clone())
+                .send()
+                .await;
+
+            match res {
+                Ok(resp) if resp.status().is_success() => {
+                    return match &config.capture_response {
+                        Some(capture) => Self::capture(capture, resp, state).await,
+                        None => Ok(()),
+                    };
+                }
+                Ok(resp) => {
+                    log::error!("http call to {} failed with code {}", resp.url(), resp.status());
+                    if config.retry.is_none() {
+                        return Err(Error::HttpStatus { url, status: resp.status().as_u16() });
+                    }
+                    status = Some(resp.status().as_u16());
+                    timed_out = false;
+                }
+                Err(e) => {
+                    log::error!("http call to {} failed: {}", url, e);
+                    if config.retry.is_none() {
+                        return Err(e.into());
+                    }
+                    status = e.status().map(|s| s.as_u16());
+                    timed_out = e.is_timeout();
+                }
+            }
+        }
+
+        if timed_out {
+            return Err(Error::Timeout { url });
+        }
+        Err(Error::RequestFailed { url, status, attempts: max_attempts })
+    }
+
+    // Headers configured explicitly always win over the detected content type.
+    fn content_type(&self, config: &HttpSenderUrlConfig, payload: &Payload) -> Option<String> {
+        let has_header = config.headers.iter()
+            .flatten()
+            .any(|(k, _)| k.eq_ignore_ascii_case("content-type"));
+        if !self.config.auto_content_type || has_header {
+            return None;
         }
+
+        payload.content_type.clone()
+            .or_else(|| detect_content_type(&payload.content).map(String::from))
+    }
+
+    async fn post_form(&self, config: &FormSenderConfig, state: &State) -> Result<()> {
+        let url = config.url.to_string(state)
+            .ok_or_else(|| Error::MissingValue("url".into()))?;
+        let fields = config.fields.iter()
+            .map(|(k, v)| v.to_string(state)
+                .map(|v| (k.as_str(), v))
+                .ok_or_else(|| Error::MissingValue(format!("form field \"{}\"", k))))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        // Form fields typically carry credentials, so only their names are logged.
+        let mut names = fields.keys().collect::<Vec<_>>();
+        names.sort();
+        log::debug!("sending HTTP form POST to \"{}\" with fields {:?}", url, names);
+        let resp = self.client.post(&url)
+            .form(&fields)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            log::error!("http call to {} failed with code {}", resp.url(), resp.status());
+            return Err(Error::HttpStatus { url, status: resp.status().as_u16() });
+        }
+        Ok(())
+    }
+
+    async fn capture(capture: &CaptureConfig, resp: reqwest::Response, state: &mut State) -> Result<()> {
+        let body = Payload::new(resp.bytes().await?.to_vec());
+        let item = capture.format.parse_payload(&body)
+            .map_err(|e| Error::InvalidPayload(format!("unable to parse response body: {}", e)))?;
+
+        log::debug!("capturing http response into env with key {}", capture.key);
+        state.set(capture.key.clone(), item)
+            .map_err(|e| Error::InvalidPayload(format!("unable to capture response body: {}", e)))?;
+        Ok(())
     }
 }
 
 #[async_trait]
 impl Sender for HttpSender {
-    async fn send(&self, payload: Payload, state: &crate::event::process::State) -> Result<()> {
-        let ps = self.config.http.iter()
-            .map(|s| {
-                match s {
-                    HttpSenderType::Post { post } => {
-                        // todo: handle missing url
-                        let url = post.url.to_string(state).unwrap_or(String::from("missing url"));
-
-                        log::debug!("sending HTTP POST to \"{}\" with body {:?}", url, payload.content);
-
-                        // todo: handle error
-                        let request = self.client
-                            .post(&url)
-                            .body(payload.content.clone())
-                            .build()
-                            .expect("unable to build request");
-
-                        self.client.execute(request)
-                    } }
-            });
-
-        futures::future::join_all(ps).await
-            .drain(0..)
-            .for_each(|p| {
-                // todo: handle error
-                let resp = p.expect("http request failed");
-                if !http::StatusCode::from(resp.status()).is_success() {
-                    log::error!("http call to {} failed with code {}", resp.url(), resp.status())
-                }
-            });
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        if let Some(max) = self.config.max_body_bytes {
+            if payload.content.len() > max {
+                return Err(Error::PayloadTooLarge { max, actual: payload.content.len() });
+            }
+        }
+
+        for s in self.config.http.iter() {
+            match s {
+                HttpSenderType::Post { post } => self.request(reqwest::Method::POST, post, &payload, state).await?,
+                HttpSenderType::Put { put } => self.request(reqwest::Method::PUT, put, &payload, state).await?,
+                HttpSenderType::Patch { patch } => self.request(reqwest::Method::PATCH, patch, &payload, state).await?,
+                HttpSenderType::Delete { delete } => self.request(reqwest::Method::DELETE, delete, &payload, state).await?,
+                HttpSenderType::PostForm { post_form } => self.post_form(post_form, state).await?,
+            }
+        }
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn captures_response(&self) -> bool {
+        self.config.http.iter().any(|s| match s {
+            HttpSenderType::Post { post: c }
+            | HttpSenderType::Put { put: c }
+            | HttpSenderType::Patch { patch: c }
+            | HttpSenderType::Delete { delete: c } => c.capture_response.is_some(),
+            HttpSenderType::PostForm { .. } => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_string, body_string_contains, header, header_exists, method, path};
+
+    use crate::event::process::{Item, Value};
+
+    use super::*;
+
+    fn config(yaml: String) -> HttpSenderConfig {
+        serde_yaml::from_str(yaml.as_str()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_post_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(body_string("hello"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let sender = HttpSender::new(&config(format!("http:\n  - post:\n      url: {}/hook", server.uri()))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_methods_ok() {
+        let server = MockServer::start().await;
+        for (m, p) in [("POST", "/post"), ("PUT", "/put"), ("PATCH", "/patch"), ("DELETE", "/delete")] {
+            Mock::given(method(m))
+                .and(path(p))
+                .and(body_string("hello"))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let sender = HttpSender::new(&config(format!(r#"
+http:
+  - post:
+      url: {0}/post
+  - put:
+      url: {0}/put
+  - patch:
+      url: {0}/patch
+  - delete:
+      url: {0}/delete
+"#, server.uri()))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_auto_content_type_ok() {
+        let server = MockServer::start().await;
+        for (p, content_type) in [("/json", "application/json"), ("/yaml", "application/yaml"), ("/csv", "text/csv")] {
+            Mock::given(method("POST"))
+                .and(path(p))
+                .and(header("content-type", content_type))
+                .respond_with(ResponseTemplate::new(200))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let sender = |p: &str| HttpSender::new(&config(format!("http:\n  - post:\n      url: {}{}", server.uri(), p))).unwrap();
+
+        let res = sender("/json").send(Payload::new(br#" {"a": 1}"#.to_vec()), &mut State::new()).await;
+        assert!(res.is_ok());
+        let res = sender("/yaml").send(Payload::new(b"---\na: 1".to_vec()), &mut State::new()).await;
+        assert!(res.is_ok());
+        let payload = Payload::new(b"a\n1".to_vec()).with_content_type("text/csv");
+        let res = sender("/csv").send(payload, &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_auto_content_type_disabled_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header_exists("content-type"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let sender = HttpSender::new(&config(format!(
+            "auto_content_type: false\nhttp:\n  - post:\n      url: {}/hook",
+            server.uri(),
+        ))).unwrap();
+
+        let res = sender.send(Payload::new(b"{}".to_vec()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_post_form_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(header("content-type", "application/x-www-form-urlencoded"))
+            .and(body_string_contains("grant_type=client_credentials"))
+            .and(body_string_contains("scope=read+write%26admin"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let sender = HttpSender::new(&config(format!(r#"
+http:
+  - post_form:
+      url: {}/token
+      fields:
+        grant_type: client_credentials
+        scope:
+          from_env: scope
+"#, server.uri()))).unwrap();
+
+        crate::event::utils::logger::capture::init();
+        let mut state = State::new();
+        let _ = state.set("scope".into(), Item::Value(Value::StringValue("read write&admin".into())));
+        let res = sender.send(Payload::new("hello".into()), &mut state).await;
+        assert!(res.is_ok());
+
+        let logged = crate::event::utils::logger::capture::records(|r| r.message.starts_with(format!("sending HTTP form POST to \"{}/token", server.uri()).as_str()));
+        assert_eq!(logged.len(), 1);
+        assert!(logged[0].message.ends_with("with fields [\"grant_type\", \"scope\"]"));
+        assert!(!logged[0].message.contains("client_credentials"));
+    }
+
+    #[tokio::test]
+    async fn test_post_form_missing_field_err() {
+        let sender = HttpSender::new(&config(r#"
+http:
+  - post_form:
+      url: http://localhost/token
+      fields:
+        scope:
+          from_env: scope
+"#.into())).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::MissingValue(field)) if field.contains("scope")));
+    }
+
+    #[tokio::test]
+    async fn test_post_timeout_err() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(tokio::time::Duration::from_secs(3)))
+            .mount(&server)
+            .await;
+
+        let sender = HttpSender::new(&config(format!(
+            "timeout_seconds: 1\nconnect_timeout_seconds: 1\nhttp:\n  - post:\n      url: {}/hook",
+            server.uri(),
+        ))).unwrap();
+
+        let start = tokio::time::Instant::now();
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        let elapsed = start.elapsed();
+
+        assert!(matches!(res, Err(Error::Timeout { url }) if url.ends_with("/hook")));
+        assert!(elapsed >= tokio::time::Duration::from_secs(1));
+        assert!(elapsed < tokio::time::Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn test_post_non_success_err() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/hook", server.uri());
+        let sender = HttpSender::new(&config(format!("http:\n  - post:\n      url: {}", url))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::HttpStatus { url: u, status: 500 }) if u == url));
+    }
+
+    #[tokio::test]
+    async fn test_post_url_from_state_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/from/state"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut state = State::new();
+        let _ = state.set("url".into(), Item::Value(Value::StringValue(format!("{}/from/state", server.uri()))));
+        let sender = HttpSender::new(&config(String::from("http:\n  - post:\n      url:\n        from_env: url"))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut state).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_post_headers_from_state_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header("authorization", "Bearer secret"))
+            .and(header("x-tenant-id", "otter"))
+            .and(header("content-type", "application/json"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(header_exists("x-missing"))
+            .respond_with(ResponseTemplate::new(400))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let mut state = State::new();
+        let _ = state.set("token".into(), Item::Value(Value::StringValue("secret".into())));
+        let _ = state.set("tenant".into(), Item::Value(Value::StringValue("otter".into())));
+        let sender = HttpSender::new(&config(format!(r#"
+http:
+  - post:
+      url: {}/hook
+      headers:
+        Authorization:
+          template: "Bearer {{{{ token }}}}"
+        X-Tenant-Id:
+          from_env: tenant
+        X-Missing:
+          from_env: missing
+        Content-Type: application/json
+"#, server.uri()))).unwrap();
+
+        let res = sender.send(Payload::new("{}".into()), &mut state).await;
+        assert!(res.is_ok());
+    }
+
+    async fn auth_server(matcher: impl wiremock::Match + 'static) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(matcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn auth_state() -> State {
+        let mut state = State::new();
+        let _ = state.set("token".into(), Item::Value(Value::StringValue("secret".into())));
+        state
+    }
+
+    #[tokio::test]
+    async fn test_post_bearer_auth_ok() {
+        let server = auth_server(header("authorization", "Bearer secret")).await;
+        let sender = HttpSender::new(&config(format!(
+            "http:\n  - post:\n      url: {}/hook\n      auth:\n        token:\n          from_env: token",
+            server.uri(),
+        ))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut auth_state()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_post_basic_auth_ok() {
+        // base64("otter:secret")
+        let server = auth_server(header("authorization", "Basic b3R0ZXI6c2VjcmV0")).await;
+        let sender = HttpSender::new(&config(format!(r#"
+http:
+  - post:
+      url: {}/hook
+      auth:
+        username: otter
+        password:
+          from_env: token
+"#, server.uri()))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut auth_state()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_post_api_key_auth_ok() {
+        let server = auth_server(header("x-api-key", "secret")).await;
+        let sender = HttpSender::new(&config(format!(r#"
+http:
+  - post:
+      url: {}/hook
+      auth:
+        header: X-Api-Key
+        value:
+          template: "{{{{ token }}}}"
+"#, server.uri()))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut auth_state()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_post_auth_missing_value_err() {
+        let sender = HttpSender::new(&config(String::from(
+            "http:\n  - post:\n      url: http://127.0.0.1:1/hook\n      auth:\n        token:\n          from_env: token",
+        ))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::MissingValue(_))));
+    }
+
+    #[tokio::test]
+    async fn test_capture_response_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/create"))
+            .respond_with(ResponseTemplate::new(201).set_body_string(r#"{"id": 42, "tags": ["a"]}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/items/42"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let create = HttpSender::new(&config(format!(r#"
+http:
+  - post:
+      url: {0}/create
+      capture_response: {{ key: created, format: json }}
+  - post:
+      url:
+        template: "{0}/items/{{{{ created.id }}}}"
+"#, server.uri()))).unwrap();
+        let next = HttpSender::new(&config(format!(r#"
+http:
+  - post:
+      url:
+        template: "{0}/items/{{{{ created.id }}}}"
+"#, server.uri()))).unwrap();
+
+        let mut state = State::new();
+        let res = create.send(Payload::new("{}".into()), &mut state).await;
+        assert!(res.is_ok());
+        assert_eq!(state.get(&"created.id".into()), Some(&Item::Value(Value::IntValue(42))));
+        assert_eq!(state.get(&"created.tags.0".into()), Some(&Item::Value(Value::StringValue("a".into()))));
+
+        let res = next.send(Payload::new("{}".into()), &mut state).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_capture_response_invalid_body_err() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+            .mount(&server)
+            .await;
+
+        let sender = HttpSender::new(&config(format!(
+            "http:\n  - post:\n      url: {}/hook\n      capture_response: {{ key: res, format: json }}",
+            server.uri(),
+        ))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::InvalidPayload(_))));
+    }
+
+    fn retry_config(server: &str, path: &str, max_attempts: usize) -> HttpSenderConfig {
+        config(format!(r#"
+http:
+  - post:
+      url: {}{}
+      retry:
+        max_attempts: {}
+        initial_backoff_ms: 10
+        multiplier: 2.0
+        jitter_factor: 0.1
+"#, server, path, max_attempts))
+    }
+
+    #[tokio::test]
+    async fn test_post_retry_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let sender = HttpSender::new(&retry_config(&server.uri(), "/hook", 3)).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_post_retry_exhausted_err() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let sender = HttpSender::new(&retry_config(&server.uri(), "/hook", 3)).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::RequestFailed { status: Some(503), attempts: 3, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_post_network_error_err() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let sender = HttpSender::new(&retry_config(&format!("http://{}", addr), "/hook", 2)).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::RequestFailed { status: None, attempts: 2, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_post_network_error_without_retry_err() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let sender = HttpSender::new(&config(format!("http:\n  - post:\n      url: http://{}/hook", addr))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::HttpNetwork { url, .. }) if url.ends_with("/hook")));
+    }
+
+    #[tokio::test]
+    async fn test_post_payload_too_large_err() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let sender = HttpSender::new(&config(format!(
+            "max_body_bytes: 4\nhttp:\n  - post:\n      url: {}/hook",
+            server.uri(),
+        ))).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::PayloadTooLarge { max: 4, actual: 5 })));
+    }
+
+    #[test]
+    fn test_backoff_ok() {
+        let retry: RetryConfig = serde_yaml::from_str("{ max_attempts: 3, initial_backoff_ms: 100, multiplier: 3.0 }").unwrap();
+        assert_eq!(retry.backoff(1).as_millis(), 100);
+        assert_eq!(retry.backoff(3).as_millis(), 900);
+
+        let retry = RetryConfig { jitter_factor: 0.5, ..retry };
+        let wait = retry.backoff(2).as_millis();
+        assert!((150..=450).contains(&wait));
+    }
+}
@@ -0,0 +1,110 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::event::process::State;
+use crate::event::sender::{EnvString, Error, Payload, Result, Sender};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct InfluxDBSenderConfig {
+    endpoint: EnvString,
+    token: EnvString,
+    org: String,
+    bucket: EnvString,
+}
+
+pub struct InfluxDBSender {
+    config: InfluxDBSenderConfig,
+    client: reqwest::Client,
+}
+
+impl InfluxDBSender {
+    pub fn new(config: &InfluxDBSenderConfig) -> Self {
+        InfluxDBSender {
+            config: config.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sender for InfluxDBSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        let endpoint = self.config.endpoint.to_string(state)
+            .ok_or_else(|| Error::MissingValue("endpoint".into()))?;
+        let token = self.config.token.to_string(state)
+            .ok_or_else(|| Error::MissingValue("token".into()))?;
+        let bucket = self.config.bucket.to_string(state)
+            .ok_or_else(|| Error::MissingValue("bucket".into()))?;
+
+        let url = format!("{}/api/v2/write", endpoint.trim_end_matches('/'));
+        log::debug!("writing line protocol to influxdb bucket \"{}\"", bucket);
+
+        let resp = self.client
+            .post(&url)
+            .query(&[("org", self.config.org.as_str()), ("bucket", bucket.as_str()), ("precision", "ns")])
+            .header(reqwest::header::AUTHORIZATION, format!("Token {}", token))
+            .header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(payload.content)
+            .send()
+            .await?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.bytes().await.unwrap_or_default();
+            return Err(Error::HttpError(format!(
+                "influxdb write failed with code {}: {}",
+                status,
+                String::from_utf8_lossy(&body),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_string, header, method, path, query_param};
+
+    use super::*;
+
+    fn sender(server: &MockServer) -> InfluxDBSender {
+        InfluxDBSender::new(&InfluxDBSenderConfig {
+            endpoint: EnvString::String(server.uri()),
+            token: EnvString::String("secret".into()),
+            org: "otter".into(),
+            bucket: EnvString::String("metrics".into()),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_write_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/write"))
+            .and(query_param("org", "otter"))
+            .and(query_param("bucket", "metrics"))
+            .and(header("authorization", "Token secret"))
+            .and(body_string("cpu value=1i"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let res = sender(&server).send(Payload::new("cpu value=1i".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_write_failed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("unable to parse"))
+            .mount(&server)
+            .await;
+
+        let res = sender(&server).send(Payload::new("cpu".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::HttpError(e)) if e.contains("unable to parse")));
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::event::sender::{Payload, Result, Sender};
+
+fn registry() -> &'static Mutex<HashMap<String, Vec<Vec<u8>>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, Vec<Vec<u8>>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn sent(name: &str) -> Vec<Vec<u8>> {
+    registry().lock().unwrap().get(name).cloned().unwrap_or_default()
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MockSenderConfig {
+    name: String,
+    delay_ms: Option<u64>,
+}
+
+pub struct MockSender {
+    name: String,
+    delay_ms: Option<u64>,
+}
+
+impl MockSender {
+    pub fn new(config: &MockSenderConfig) -> Self {
+        MockSender {
+            name: config.name.clone(),
+            delay_ms: config.delay_ms,
+        }
+    }
+}
+
+#[async_trait]
+impl Sender for MockSender {
+    async fn send(&self, payload: Payload, _: &mut crate::event::process::State) -> Result<()> {
+        if let Some(delay_ms) = self.delay_ms {
+            tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
+        }
+        registry().lock().unwrap()
+            .entry(self.name.clone())
+            .or_default()
+            .push(payload.content);
+        Ok(())
+    }
+}
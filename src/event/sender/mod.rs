@@ -1,4 +1,17 @@
+mod aggregate;
+mod auth;
+mod conditional;
+mod discord;
+mod elasticsearch;
+mod file;
 mod http;
+mod influxdb;
+mod nats;
+mod pubsub;
+mod syslog;
+mod telegram;
+#[cfg(test)]
+pub mod mock;
 
 use thiserror::Error;
 use async_trait::async_trait;
@@ -6,63 +19,354 @@ use serde::Deserialize;
 use crate::event::process::Identifier;
 
 #[async_trait]
-pub trait Sender {
-    async fn send(&self, payload: Payload, state: &crate::event::process::State) -> Result<()>;
+pub trait Sender: Send + Sync {
+    async fn send(&self, payload: Payload, state: &mut crate::event::process::State) -> Result<()>;
+
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether `send` writes into the state, in which case the senders after it wait for it.
+    fn captures_response(&self) -> bool {
+        false
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Payload {
-    pub content: Vec<u8>
+    pub content: Vec<u8>,
+    pub content_type: Option<String>,
 }
 
 impl Payload {
     pub fn new(content: Vec<u8>) -> Self {
-        Payload{ content }
+        Payload{ content, content_type: None }
+    }
+
+    pub fn with_content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    pub fn to_json_value(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.content)
+            .unwrap_or_else(|_| serde_json::Value::String(base64::encode(&self.content)))
+    }
+
+    // A string is treated as base64 first, so a JSON string payload that happens
+    // to be valid base64 is decoded as binary.
+    pub fn from_json_value(v: &serde_json::Value) -> Payload {
+        let content = match v {
+            serde_json::Value::String(s) => base64::decode(s)
+                .unwrap_or_else(|_| serde_json::to_vec(v).unwrap_or_default()),
+            v => serde_json::to_vec(v).unwrap_or_default(),
+        };
+        Payload::new(content)
+    }
+}
+
+#[cfg(test)]
+mod payload_tests {
+    use super::*;
+
+    #[test]
+    fn to_json_value_json_payload() {
+        let payload = Payload::new(br#"{"a":[1,"b"]}"#.to_vec());
+
+        assert_eq!(payload.to_json_value(), serde_json::json!({"a": [1, "b"]}));
+    }
+
+    #[test]
+    fn to_json_value_binary_payload() {
+        let payload = Payload::new(vec!(0, 159, 146, 150));
+
+        assert_eq!(payload.to_json_value(), serde_json::Value::String("AJ+Slg==".into()));
+    }
+
+    #[test]
+    fn from_json_value_base64_round_trip() {
+        let content = vec!(0, 159, 146, 150);
+        let value = Payload::new(content.clone()).to_json_value();
+
+        assert_eq!(Payload::from_json_value(&value).content, content);
+    }
+
+    #[test]
+    fn from_json_value_json_round_trip() {
+        let value = serde_json::json!({"a": [1, "b"]});
+        let payload = Payload::from_json_value(&value);
+
+        assert_eq!(payload.to_json_value(), value);
     }
 }
 
+#[derive(Deserialize, Clone, Debug)]
+pub struct SenderConfig {
+    condition: Option<crate::event::process::operation::Expression>,
+    #[serde(flatten)]
+    sender: SenderType,
+}
+
 #[derive(Deserialize, Clone, Debug)]
 #[serde(untagged)]
-pub enum SenderConfig {
-    Http(http::HttpSenderConfig)
+pub enum SenderType {
+    Http(http::HttpSenderConfig),
+    Aggregate { aggregate: aggregate::AggregateSenderConfig },
+    Discord { discord: discord::DiscordSenderConfig },
+    Telegram { telegram: telegram::TelegramSenderConfig },
+    Elasticsearch { elasticsearch: elasticsearch::ElasticsearchSenderConfig },
+    InfluxDB { influxdb: influxdb::InfluxDBSenderConfig },
+    Syslog { syslog: syslog::SyslogSenderConfig },
+    Nats { nats: nats::NatsSenderConfig },
+    PubSub { pubsub: pubsub::PubSubSenderConfig },
+    File { file: file::FileSenderConfig },
+    #[cfg(test)]
+    Mock { mock: mock::MockSenderConfig },
 }
 
 #[derive(Error, Debug)]
 pub enum Error {
+    #[error("missing value for {0}")]
+    MissingValue(String),
+
+    #[error("http error: {0}")]
+    HttpError(String),
+
+    #[error("invalid payload: {0}")]
+    InvalidPayload(String),
+
+    #[error("syslog error: {0}")]
+    SyslogError(String),
+
+    #[error("io error: {0}")]
+    IoError(String),
+
+    #[error("path {0} is outside of the file sender root")]
+    PathOutsideRoot(String),
+
+    #[error("connection failed: {0}")]
+    ConnectionFailed(String),
+
+    #[error("unable to build http request: {0}")]
+    HttpRequestBuild(String),
+
+    #[error("request to {url} failed: {reason}")]
+    HttpNetwork {
+        url: String,
+        reason: String,
+    },
+
+    #[error("request to {url} failed with status {status}")]
+    HttpStatus {
+        url: String,
+        status: u16,
+    },
+
+    #[error("request to {url} timed out")]
+    Timeout {
+        url: String,
+    },
+
+    #[error("payload of {actual} bytes exceeds the limit of {max} bytes")]
+    PayloadTooLarge {
+        max: usize,
+        actual: usize,
+    },
+
+    #[error("serialization error: {0}")]
+    Serialization(String),
+
+    #[error("unable to evaluate sender condition: {0}")]
+    Condition(String),
+
+    #[error("request to {url} failed after {attempts} attempts (status: {status:?})")]
+    RequestFailed {
+        url: String,
+        status: Option<u16>,
+        attempts: usize,
+    },
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        let url = e.url().map(|u| u.to_string()).unwrap_or_default();
+        if e.is_timeout() {
+            Error::Timeout { url }
+        } else if e.is_builder() {
+            Error::HttpRequestBuild(e.to_string())
+        } else if let Some(status) = e.status() {
+            Error::HttpStatus { url, status: status.as_u16() }
+        } else {
+            Error::HttpNetwork { url, reason: e.to_string() }
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+impl SenderConfig {
+    pub fn kind(&self) -> &'static str {
+        match &self.sender {
+            SenderType::Http(_) => "http",
+            SenderType::Aggregate { .. } => "aggregate",
+            SenderType::Discord { .. } => "discord",
+            SenderType::Telegram { .. } => "telegram",
+            SenderType::Elasticsearch { .. } => "elasticsearch",
+            SenderType::InfluxDB { .. } => "influxdb",
+            SenderType::Syslog { .. } => "syslog",
+            SenderType::Nats { .. } => "nats",
+            SenderType::PubSub { .. } => "pubsub",
+            SenderType::File { .. } => "file",
+            #[cfg(test)]
+            SenderType::Mock { .. } => "mock",
+        }
+    }
+}
+
 pub fn new_sender(config: &SenderConfig) -> Result<Box<dyn Sender>> {
-    Ok(
-        match config {
-            SenderConfig::Http(c) => { Box::new(http::HttpSender::new(c)) }
+    let sender: Box<dyn Sender> = match &config.sender {
+        SenderType::Http(c) => { Box::new(http::HttpSender::new(c)?) }
+        SenderType::Aggregate { aggregate } => { Box::new(aggregate::AggregateSender::new(aggregate)?) }
+        SenderType::Discord { discord } => { Box::new(discord::DiscordSender::new(discord)) }
+        SenderType::Telegram { telegram } => { Box::new(telegram::TelegramSender::new(telegram)) }
+        SenderType::Elasticsearch { elasticsearch } => {
+            Box::new(elasticsearch::ElasticsearchSender::new(elasticsearch))
         }
-    )
+        SenderType::InfluxDB { influxdb } => { Box::new(influxdb::InfluxDBSender::new(influxdb)) }
+        SenderType::Syslog { syslog } => { Box::new(syslog::SyslogSender::new(syslog)?) }
+        SenderType::Nats { nats } => { Box::new(nats::NatsSender::new(nats)) }
+        SenderType::PubSub { pubsub } => { Box::new(pubsub::PubSubSender::new(pubsub)?) }
+        SenderType::File { file } => { Box::new(file::FileSender::new(file)?) }
+        #[cfg(test)]
+        SenderType::Mock { mock } => { Box::new(mock::MockSender::new(mock)) }
+    };
+
+    Ok(match &config.condition {
+        Some(condition) => Box::new(conditional::ConditionalSender::new(condition.clone(), sender)),
+        None => sender,
+    })
 }
 
 #[derive(Deserialize, Clone, Debug)]
 #[serde(untagged)]
-enum EnvString {
-    FromEnv { from_env: Identifier },
+pub enum EnvString {
+    FromEnv {
+        from_env: Identifier,
+        #[serde(default)]
+        sensitive: bool,
+    },
+    Template { template: String },
     String(String),
 }
 
 impl EnvString {
-    fn to_string(&self, state: &crate::event::process::State) -> Option<String> {
+    pub fn to_string(&self, state: &crate::event::process::State) -> Option<String> {
         match self {
-            EnvString::FromEnv { from_env: key } => {
+            EnvString::FromEnv { from_env: key, sensitive } => {
                 log::debug!("getting string from env with key: {}", key);
                 let val = state.get(key);
                 match val {
                     Some(crate::event::process::Item::Value(crate::event::process::Value::StringValue(s))) => {
-                        log::debug!("string from env with key \"{}\" found: {}", key, s);
+                        let shown = if *sensitive || state.is_sensitive(key) { crate::event::process::REDACTED } else { s.as_str() };
+                        log::debug!("string from env with key \"{}\" found: {}", key, shown);
                         Some(s.clone())
                     },
                     _ => None,
                 }
             },
+            EnvString::Template { template } => { Self::render(template, state, chrono::Utc::now()) },
             EnvString::String(s) => { Some(s.clone()) },
         }
     }
+
+    /// Places `path`, resolved from this value, under `root`. A literal path is used as configured,
+    /// while a path taken from the state must be relative and must not leave `root`, so values
+    /// from the message cannot reach other files. Returns the rejected path on error.
+    pub fn confine_path(&self, root: &std::path::Path, path: String) -> std::result::Result<std::path::PathBuf, String> {
+        use std::path::{Component, PathBuf};
+
+        if matches!(self, EnvString::String(_)) {
+            return Ok(PathBuf::from(path));
+        }
+
+        let relative = PathBuf::from(&path);
+        if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(path);
+        }
+        Ok(root.join(relative))
+    }
+
+    fn render(
+        template: &str,
+        state: &crate::event::process::State,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<String> {
+        use chrono::{Datelike, Timelike};
+        use crate::event::process::{Item, Value};
+
+        let re = regex::Regex::new(r"\{\{\s*([^}\s]+)\s*\}\}").expect("invalid template regex");
+
+        let mut missing = false;
+        let rendered = re.replace_all(template, |c: &regex::Captures| {
+            match &c[1] {
+                "year" => format!("{:04}", now.year()),
+                "month" => format!("{:02}", now.month()),
+                "day" => format!("{:02}", now.day()),
+                "hour" => format!("{:02}", now.hour()),
+                key => match state.get(&Identifier::from(key)) {
+                    Some(Item::Value(v)) if *v != Value::None => v.to_string(),
+                    _ => {
+                        log::debug!("template key \"{}\" not found in env", key);
+                        missing = true;
+                        String::new()
+                    }
+                },
+            }
+        }).to_string();
+
+        if missing { None } else { Some(rendered) }
+    }
+}
+
+#[cfg(test)]
+mod env_string_tests {
+    use chrono::TimeZone;
+
+    use crate::event::process::{Item, State, Value};
+
+    use super::*;
+
+    #[test]
+    fn test_render_template_ok() {
+        let mut state = State::new();
+        let _ = state.set("app.name".into(), Item::Value(Value::StringValue("otter".into())));
+        let now = chrono::Utc.with_ymd_and_hms(2021, 9, 5, 7, 0, 0).unwrap();
+
+        let res = EnvString::render("logs-{{app.name}}-{{year}}.{{month}}.{{ day }}", &state, now);
+        assert_eq!(res, Some(String::from("logs-otter-2021.09.05")));
+    }
+
+    #[test]
+    fn test_from_env_sensitive_ok() {
+        crate::event::utils::logger::capture::init();
+        let mut state = State::new();
+        let _ = state.set("sensitive_token".into(), Item::Value(Value::StringValue("from-env-secret".into())));
+
+        let value: EnvString = serde_yaml::from_str("{ from_env: sensitive_token, sensitive: true }").unwrap();
+        assert!(matches!(value, EnvString::FromEnv { sensitive: true, .. }));
+        assert_eq!(value.to_string(&state), Some("from-env-secret".into()));
+
+        let records = crate::event::utils::logger::capture::records(|r| r.message.contains("\"sensitive_token\" found"));
+        assert_eq!(records.len(), 1);
+        assert!(records[0].message.ends_with(crate::event::process::REDACTED));
+        assert!(crate::event::utils::logger::capture::records(|r| r.message.contains("from-env-secret")).is_empty());
+    }
+
+    #[test]
+    fn test_render_template_missing_key() {
+        let now = chrono::Utc.with_ymd_and_hms(2021, 9, 5, 7, 0, 0).unwrap();
+
+        let res = EnvString::render("logs-{{app.name}}", &State::new(), now);
+        assert_eq!(res, None);
+    }
 }
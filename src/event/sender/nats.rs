@@ -0,0 +1,143 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+use crate::event::process::State;
+use crate::event::sender::{EnvString, Error, Payload, Result, Sender};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct NatsSenderConfig {
+    url: String,
+    subject: EnvString,
+}
+
+pub struct NatsSender {
+    config: NatsSenderConfig,
+    client: OnceCell<async_nats::Client>,
+}
+
+impl NatsSender {
+    pub fn new(config: &NatsSenderConfig) -> Self {
+        NatsSender {
+            config: config.clone(),
+            client: OnceCell::new(),
+        }
+    }
+
+    async fn client(&self) -> Result<&async_nats::Client> {
+        self.client.get_or_try_init(|| async {
+            log::debug!("connecting to nats server {}", self.config.url);
+            async_nats::connect(self.config.url.as_str())
+                .await
+                .map_err(|e| Error::ConnectionFailed(format!("{}: {}", self.config.url, e)))
+        }).await
+    }
+}
+
+#[async_trait]
+impl Sender for NatsSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        let subject = self.config.subject.to_string(state)
+            .ok_or_else(|| Error::MissingValue("subject".into()))?;
+        let client = self.client().await?;
+
+        log::debug!("publishing {} bytes to nats subject \"{}\"", payload.content.len(), subject);
+        client.publish(subject, payload.content.into())
+            .await
+            .map_err(|e| Error::ConnectionFailed(format!("{}", e)))?;
+        client.flush()
+            .await
+            .map_err(|e| Error::ConnectionFailed(format!("{}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use crate::event::process::{Item, Value};
+
+    use super::*;
+
+    fn sender(url: String) -> NatsSender {
+        NatsSender::new(&serde_yaml::from_str(format!("url: {}\nsubject:\n  from_env: subject", url).as_str()).unwrap())
+    }
+
+    // Accepts a single client and returns the subject and payload of the first PUB.
+    async fn fake_server() -> (String, tokio::task::JoinHandle<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("nats://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut read = BufReader::new(read);
+            write.write_all(b"INFO {\"server_id\":\"test\",\"version\":\"2.10.0\",\"proto\":1,\"max_payload\":1048576}\r\n").await.unwrap();
+
+            let mut published = None;
+            loop {
+                let mut line = String::new();
+                if read.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+
+                let parts = line.split_whitespace().collect::<Vec<_>>();
+                match parts.first().copied() {
+                    Some("PING") => {
+                        write.write_all(b"PONG\r\n").await.unwrap();
+                        if published.is_some() {
+                            break;
+                        }
+                    }
+                    Some("PUB") => {
+                        let len = parts.last().unwrap().parse::<usize>().unwrap();
+                        let mut content = vec![0; len + 2];
+                        read.read_exact(&mut content).await.unwrap();
+                        content.truncate(len);
+                        published = Some((parts[1].to_string(), content));
+                    }
+                    _ => {}
+                }
+            }
+
+            published.unwrap()
+        });
+
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_send_ok() {
+        let (url, server) = fake_server().await;
+
+        let mut state = State::new();
+        let _ = state.set("subject".into(), Item::Value(Value::StringValue("events.created".into())));
+
+        let res = sender(url).send(Payload::new("hello".into()), &mut state).await;
+        assert!(res.is_ok());
+
+        let (subject, content) = server.await.unwrap();
+        assert_eq!(subject, "events.created");
+        assert_eq!(content, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_send_missing_subject_err() {
+        let res = sender("nats://127.0.0.1:1".into()).send(Payload::new(vec!()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::MissingValue(_))));
+    }
+
+    #[tokio::test]
+    async fn test_send_connection_failed_err() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("nats://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let mut state = State::new();
+        let _ = state.set("subject".into(), Item::Value(Value::StringValue("a".into())));
+
+        let res = sender(url).send(Payload::new(vec!()), &mut state).await;
+        assert!(matches!(res, Err(Error::ConnectionFailed(_))));
+    }
+}
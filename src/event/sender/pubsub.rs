@@ -0,0 +1,137 @@
+use async_trait::async_trait;
+use google_pubsub1::Pubsub;
+use google_pubsub1::api::{PublishRequest, PubsubMessage};
+use serde::Deserialize;
+
+use crate::event::process::State;
+use crate::event::sender::{Error, Payload, Result, Sender};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PubSubSenderConfig {
+    credential: String,
+    topic_name: String,
+}
+
+pub struct PubSubSender {
+    pubsub: Pubsub,
+    topic_name: String,
+}
+
+impl PubSubSender {
+    pub fn new(config: &PubSubSenderConfig) -> Result<Self> {
+        let secret: yup_oauth2::ServiceAccountKey = serde_json::from_str(config.credential.as_str())
+            .map_err(|e| Error::ConnectionFailed(format!("invalid pubsub credential: {}", e)))?;
+
+        log::debug!("initializing pubsub sender for topic \"{}\"", config.topic_name);
+
+        Ok(PubSubSender {
+            pubsub: futures::executor::block_on(Self::new_hub(secret))?,
+            topic_name: config.topic_name.clone(),
+        })
+    }
+
+    async fn new_hub(secret: yup_oauth2::ServiceAccountKey) -> Result<Pubsub> {
+        let auth = yup_oauth2::ServiceAccountAuthenticator::builder(secret)
+            .build()
+            .await
+            .map_err(|e| Error::ConnectionFailed(format!("unable to create pubsub authenticator: {}", e)))?;
+
+        Ok(Pubsub::new(hyper::Client::builder().build(hyper_rustls::HttpsConnector::with_native_roots()), auth))
+    }
+}
+
+#[async_trait]
+impl Sender for PubSubSender {
+    async fn send(&self, payload: Payload, _: &mut State) -> Result<()> {
+        log::debug!("publishing {} bytes to pubsub topic \"{}\"", payload.content.len(), self.topic_name);
+
+        let request = PublishRequest {
+            messages: Some(vec!(PubsubMessage {
+                data: Some(base64::encode(&payload.content)),
+                ..Default::default()
+            })),
+        };
+
+        self.pubsub.projects()
+            .topics_publish(request, self.topic_name.as_str())
+            .doit()
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::ConnectionFailed(format!("unable to publish to {}: {}", self.topic_name, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_json, method, path};
+
+    use crate::event::trigger::TEST_PRIVATE_KEY;
+
+    use super::*;
+
+    async fn sender(server: &MockServer) -> PubSubSender {
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"access_token": "token", "token_type": "Bearer", "expires_in": 3600}"#,
+            ))
+            .mount(server)
+            .await;
+
+        let credential = serde_json::json!({
+            "type": "service_account",
+            "private_key": TEST_PRIVATE_KEY,
+            "client_email": "webhook@project.iam.gserviceaccount.com",
+            "token_uri": format!("{}/token", server.uri()),
+        }).to_string();
+        let config: PubSubSenderConfig = serde_yaml::from_value(serde_yaml::to_value(serde_json::json!({
+            "credential": credential,
+            "topic_name": "projects/project/topics/topic",
+        })).unwrap()).unwrap();
+
+        let mut sender = PubSubSender::new(&config).unwrap();
+        sender.pubsub.base_url(format!("{}/", server.uri()));
+        sender
+    }
+
+    #[tokio::test]
+    async fn test_send_ok() {
+        let server = MockServer::start().await;
+        let sender = sender(&server).await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/projects/project/topics/topic:publish"))
+            .and(body_json(serde_json::json!({"messages": [{"data": "aGVsbG8="}]})))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"messageIds": ["1"]}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_err() {
+        let server = MockServer::start().await;
+        let sender = sender(&server).await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/projects/project/topics/topic:publish"))
+            .respond_with(ResponseTemplate::new(404).set_body_string(
+                r#"{"error": {"code": 404, "message": "Resource not found", "status": "NOT_FOUND"}}"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::ConnectionFailed(_))));
+    }
+
+    #[test]
+    fn test_new_invalid_credential_err() {
+        let config: PubSubSenderConfig = serde_yaml::from_str("credential: \"{}\"\ntopic_name: t").unwrap();
+        assert!(matches!(PubSubSender::new(&config), Err(Error::ConnectionFailed(_))));
+    }
+}
@@ -0,0 +1,183 @@
+use std::net::ToSocketAddrs;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use syslog::{Facility, Formatter5424, Logger, LoggerBackend};
+
+use crate::event::process::State;
+use crate::event::sender::{EnvString, Error, Payload, Result, Sender};
+
+fn default_severity() -> EnvString {
+    EnvString::String("info".into())
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SyslogSenderConfig {
+    address: String,
+    facility: u8,
+    #[serde(default = "default_severity")]
+    severity: EnvString,
+}
+
+pub struct SyslogSender {
+    severity: EnvString,
+    logger: Mutex<Logger<LoggerBackend, Formatter5424>>,
+}
+
+impl SyslogSender {
+    pub fn new(config: &SyslogSenderConfig) -> Result<Self> {
+        let formatter = Formatter5424 {
+            facility: facility(config.facility)?,
+            process: "webhook".into(),
+            ..Default::default()
+        };
+
+        let logger = if let Some(address) = config.address.strip_prefix("udp://") {
+            let server = address.to_socket_addrs()
+                .map_err(|e| Error::SyslogError(format!("invalid address \"{}\": {}", address, e)))?
+                .next()
+                .ok_or_else(|| Error::SyslogError(format!("invalid address \"{}\"", address)))?;
+            let local = if server.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+            syslog::udp(formatter, local, server)
+        } else if let Some(path) = config.address.strip_prefix("unix://") {
+            syslog::unix_custom(formatter, path)
+        } else {
+            return Err(Error::SyslogError(format!(
+                "unsupported address \"{}\", expected udp:// or unix://",
+                config.address,
+            )));
+        }.map_err(|e| Error::SyslogError(format!("{}", e)))?;
+
+        Ok(SyslogSender {
+            severity: config.severity.clone(),
+            logger: Mutex::new(logger),
+        })
+    }
+}
+
+fn facility(code: u8) -> Result<Facility> {
+    Ok(match code {
+        0 => Facility::LOG_KERN,
+        1 => Facility::LOG_USER,
+        2 => Facility::LOG_MAIL,
+        3 => Facility::LOG_DAEMON,
+        4 => Facility::LOG_AUTH,
+        5 => Facility::LOG_SYSLOG,
+        6 => Facility::LOG_LPR,
+        7 => Facility::LOG_NEWS,
+        8 => Facility::LOG_UUCP,
+        9 => Facility::LOG_CRON,
+        10 => Facility::LOG_AUTHPRIV,
+        11 => Facility::LOG_FTP,
+        12 => Facility::LOG_NTP,
+        13 => Facility::LOG_AUDIT,
+        14 => Facility::LOG_ALERT,
+        15 => Facility::LOG_CLOCK_DAEMON,
+        16 => Facility::LOG_LOCAL0,
+        17 => Facility::LOG_LOCAL1,
+        18 => Facility::LOG_LOCAL2,
+        19 => Facility::LOG_LOCAL3,
+        20 => Facility::LOG_LOCAL4,
+        21 => Facility::LOG_LOCAL5,
+        22 => Facility::LOG_LOCAL6,
+        23 => Facility::LOG_LOCAL7,
+        c => return Err(Error::SyslogError(format!("invalid facility code {}, expected 0 to 23", c))),
+    })
+}
+
+#[async_trait]
+impl Sender for SyslogSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        let severity = self.severity.to_string(state)
+            .ok_or_else(|| Error::MissingValue("severity".into()))?;
+        let message = (0, Default::default(), String::from_utf8_lossy(&payload.content).into_owned());
+
+        let mut logger = self.logger.lock().expect("syslog logger lock poisoned");
+        match severity.to_lowercase().as_str() {
+            "emerg" | "emergency" => logger.emerg(message),
+            "alert" => logger.alert(message),
+            "crit" | "critical" => logger.crit(message),
+            "err" | "error" => logger.err(message),
+            "warning" | "warn" => logger.warning(message),
+            "notice" => logger.notice(message),
+            "info" => logger.info(message),
+            "debug" => logger.debug(message),
+            s => return Err(Error::SyslogError(format!("unknown severity \"{}\"", s))),
+        }.map_err(|e| Error::SyslogError(format!("{}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event::process::{Item, Value};
+
+    use super::*;
+
+    fn assert_rfc5424(message: &str, priority: u8, msg: &str) {
+        let re = regex::Regex::new(r"^<(\d+)>1 (\S+) (\S+) webhook (\d+) 0 - (.*)$").unwrap();
+        let captures = re.captures(message).unwrap_or_else(|| panic!("not an RFC 5424 message: {}", message));
+
+        assert_eq!(&captures[1], priority.to_string());
+        assert!(chrono::DateTime::parse_from_rfc3339(&captures[2]).is_ok());
+        assert_eq!(&captures[5], msg);
+    }
+
+    #[tokio::test]
+    async fn test_send_udp_ok() {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+
+        let sender = SyslogSender::new(&SyslogSenderConfig {
+            address: format!("udp://{}", socket.local_addr().unwrap()),
+            facility: 16,
+            severity: EnvString::FromEnv { from_env: "severity".into(), sensitive: false },
+        }).unwrap();
+
+        let mut state = State::new();
+        let _ = state.set("severity".into(), Item::Value(Value::StringValue("warning".into())));
+
+        let res = sender.send(Payload::new("disk almost full".into()), &mut state).await;
+        assert!(res.is_ok());
+
+        let mut buf = [0; 1024];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_rfc5424(std::str::from_utf8(&buf[..len]).unwrap(), 16 * 8 + 4, "disk almost full");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_send_unix_ok() {
+        let path = std::env::temp_dir().join(format!("webhook_syslog_test_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+        socket.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+
+        let sender = SyslogSender::new(&SyslogSenderConfig {
+            address: format!("unix://{}", path.display()),
+            facility: 1,
+            severity: default_severity(),
+        }).unwrap();
+
+        let res = sender.send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+
+        let mut buf = [0; 1024];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_rfc5424(std::str::from_utf8(&buf[..len]).unwrap(), 8 + 6, "hello");
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_new_invalid_config_err() {
+        let config = |address: &str, facility: u8| SyslogSenderConfig {
+            address: address.into(),
+            facility,
+            severity: default_severity(),
+        };
+
+        assert!(matches!(SyslogSender::new(&config("tcp://localhost:514", 1)), Err(Error::SyslogError(_))));
+        assert!(matches!(SyslogSender::new(&config("udp://127.0.0.1:514", 24)), Err(Error::SyslogError(_))));
+    }
+}
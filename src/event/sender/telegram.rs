@@ -0,0 +1,160 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::event::process::State;
+use crate::event::sender::{EnvString, Error, Payload, Result, Sender};
+
+const DEFAULT_API_URL: &str = "https://api.telegram.org";
+const MAX_MESSAGE_LENGTH: usize = 4096;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TelegramSenderConfig {
+    bot_token: EnvString,
+    chat_id: EnvString,
+    parse_mode: Option<String>,
+    api_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TelegramResponse {
+    ok: bool,
+    description: Option<String>,
+}
+
+pub struct TelegramSender {
+    config: TelegramSenderConfig,
+    client: reqwest::Client,
+}
+
+impl TelegramSender {
+    pub fn new(config: &TelegramSenderConfig) -> Self {
+        TelegramSender {
+            config: config.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn split_message(text: &str) -> Vec<String> {
+        let chars = text.chars().collect::<Vec<_>>();
+        if chars.is_empty() {
+            return vec!(String::new());
+        }
+
+        chars.chunks(MAX_MESSAGE_LENGTH)
+            .map(|c| c.iter().collect())
+            .collect()
+    }
+
+    async fn send_message(&self, url: &str, chat_id: &str, text: String) -> Result<()> {
+        let mut body = serde_json::json!({
+            "chat_id": chat_id,
+            "text": text,
+        });
+        if let Some(parse_mode) = &self.config.parse_mode {
+            body["parse_mode"] = serde_json::Value::String(parse_mode.clone());
+        }
+
+        let resp = self.client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        let status = resp.status();
+        let resp = resp.bytes().await?;
+        let resp: TelegramResponse = serde_json::from_slice(&resp)
+            .map_err(|e| Error::HttpError(format!("invalid telegram response with code {}: {}", status, e)))?;
+
+        if !resp.ok {
+            return Err(Error::HttpError(format!(
+                "telegram api error: {}",
+                resp.description.unwrap_or_else(|| status.to_string()),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sender for TelegramSender {
+    async fn send(&self, payload: Payload, state: &mut State) -> Result<()> {
+        let token = self.config.bot_token.to_string(state)
+            .ok_or_else(|| Error::MissingValue("bot_token".into()))?;
+        let chat_id = self.config.chat_id.to_string(state)
+            .ok_or_else(|| Error::MissingValue("chat_id".into()))?;
+
+        let api_url = self.config.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+        let url = format!("{}/bot{}/sendMessage", api_url, token);
+
+        let text = String::from_utf8_lossy(&payload.content);
+        let messages = Self::split_message(&text);
+        log::debug!("sending {} telegram message(s) to chat {}", messages.len(), chat_id);
+
+        for message in messages {
+            self.send_message(&url, &chat_id, message).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_json, method, path};
+
+    use super::*;
+
+    fn sender(server: &MockServer) -> TelegramSender {
+        TelegramSender::new(&TelegramSenderConfig {
+            bot_token: EnvString::String("123:abc".into()),
+            chat_id: EnvString::String("42".into()),
+            parse_mode: Some("HTML".into()),
+            api_url: Some(server.uri()),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_send_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/bot123:abc/sendMessage"))
+            .and(body_json(serde_json::json!({"chat_id": "42", "text": "hello", "parse_mode": "HTML"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"ok": true})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let res = sender(&server).send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_long_message_split() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"ok": true})))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let text = "a".repeat(MAX_MESSAGE_LENGTH * 2 + 1);
+        let res = sender(&server).send(Payload::new(text.into()), &mut State::new()).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_api_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400)
+                .set_body_json(serde_json::json!({"ok": false, "description": "Bad Request: chat not found"})))
+            .mount(&server)
+            .await;
+
+        let res = sender(&server).send(Payload::new("hello".into()), &mut State::new()).await;
+        assert!(matches!(res, Err(Error::HttpError(e)) if e.contains("chat not found")));
+    }
+}
@@ -0,0 +1,185 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tokio::sync::{mpsc, Mutex, OnceCell};
+use tokio_cron_scheduler::{Job, JobScheduler};
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use super::{Error, Result};
+
+#[derive(Deserialize)]
+struct CronConfig {
+    schedule: String,
+    payload: Option<serde_yaml::Value>,
+}
+
+pub struct Receiver {
+    schedule: String,
+    payload: serde_json::Map<String, serde_json::Value>,
+    job: Job,
+    scheduler: OnceCell<JobScheduler>,
+    ticks: Mutex<mpsc::Receiver<DateTime<Utc>>>,
+}
+
+impl Receiver {
+    fn parse_config(trigger: &Trigger) -> Result<(String, serde_json::Map<String, serde_json::Value>)> {
+        let config: CronConfig = trigger.config.clone()
+            .map(serde_yaml::from_value)
+            .ok_or(Error::InvalidConfig("missing config".to_string()))?
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        let payload = match config.payload.map(serde_json::to_value).transpose() {
+            Ok(None) => serde_json::Map::new(),
+            Ok(Some(serde_json::Value::Object(payload))) => payload,
+            Ok(Some(_)) => return Err(Error::InvalidConfig("cron payload must be a map".into())),
+            Err(e) => return Err(Error::InvalidConfig(format!("invalid cron payload: {}", e))),
+        };
+
+        Ok((with_seconds(&config.schedule), payload))
+    }
+
+    pub fn validate(trigger: &Trigger) -> Result<()> {
+        let (schedule, _) = Self::parse_config(trigger)?;
+        Self::new_job(&schedule, mpsc::channel(1).0).map(|_| ())
+    }
+
+    fn new_job(schedule: &str, ticks: mpsc::Sender<DateTime<Utc>>) -> Result<Job> {
+        let job_schedule = schedule.to_string();
+        Job::new_async(schedule, move |_, _| {
+            let ticks = ticks.clone();
+            let schedule = job_schedule.clone();
+            Box::pin(async move {
+                if ticks.try_send(Utc::now()).is_err() {
+                    log::warn!("cron trigger ({}) is still busy, skipping tick", schedule);
+                }
+            })
+        }).map_err(|e| Error::InvalidConfig(format!("invalid cron schedule \"{}\": {}", schedule, e)))
+    }
+
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let (schedule, payload) = Self::parse_config(trigger)?;
+
+        log::debug!("initializing cron trigger with schedule \"{}\"", schedule);
+
+        let (s, r) = mpsc::channel(1);
+        let job = Self::new_job(&schedule, s)?;
+
+        Ok(Receiver {
+            schedule,
+            payload,
+            job,
+            scheduler: OnceCell::new(),
+            ticks: Mutex::new(r),
+        })
+    }
+
+    // The scheduler can only be created inside the runtime, so it is started on the first pull.
+    async fn start_scheduler(&self) -> Result<&JobScheduler> {
+        self.scheduler.get_or_try_init(|| async {
+            let scheduler = JobScheduler::new().await
+                .map_err(|e| Error::PullError(format!("unable to create cron scheduler: {}", e)))?;
+            scheduler.add(self.job.clone()).await
+                .map_err(|e| Error::PullError(format!("unable to schedule cron job: {}", e)))?;
+            scheduler.start().await
+                .map_err(|e| Error::PullError(format!("unable to start cron scheduler: {}", e)))?;
+
+            Ok(scheduler)
+        }).await
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        if let (Some(mut scheduler), Ok(handle)) = (self.scheduler.take(), tokio::runtime::Handle::try_current()) {
+            handle.spawn(async move {
+                if let Err(e) = scheduler.shutdown().await {
+                    log::error!("error stopping cron scheduler: {}", e);
+                }
+            });
+        }
+    }
+}
+
+// Standard 5-field expressions have no seconds field, which the scheduler requires.
+fn with_seconds(schedule: &str) -> String {
+    if schedule.split_whitespace().count() == 5 {
+        format!("0 {}", schedule.trim())
+    } else {
+        schedule.trim().to_string()
+    }
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        self.start_scheduler().await?;
+
+        let scheduled_at = self.ticks.lock().await
+            .recv()
+            .await
+            .ok_or_else(|| Error::PullError(format!("cron trigger ({}) stopped", self.schedule)))?;
+        log::trace!("cron trigger ({}) fired at {}", self.schedule, scheduled_at);
+
+        let mut payload = self.payload.clone();
+        payload.insert("_scheduled_at".into(), serde_json::Value::String(scheduled_at.to_rfc3339()));
+
+        Ok(Box::new(Event {
+            content: serde_json::to_vec(&payload).expect("unable to serialize cron payload"),
+        }))
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(config: &str) -> Trigger {
+        serde_yaml::from_str(format!(r#"
+type: cron
+config:
+  {}
+"#, config).as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_with_seconds_ok() {
+        assert_eq!(with_seconds("*/5 * * * *"), "0 */5 * * * *");
+        assert_eq!(with_seconds("30 */5 * * * *"), "30 */5 * * * *");
+    }
+
+    #[test]
+    fn test_validate_err() {
+        assert!(Receiver::validate(&trigger("schedule: \"0 0 * * *\"")).is_ok());
+        assert!(matches!(Receiver::validate(&trigger("schedule: every minute")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::new(&trigger("schedule: \"61 * * * *\"")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::validate(&trigger("schedule: \"* * * * *\"\n  payload: [1]")), Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_one_ok() {
+        let receiver = Receiver::new(&trigger("schedule: \"* * * * * *\"\n  payload: { job: cleanup }")).unwrap();
+
+        let event = tokio::time::timeout(tokio::time::Duration::from_secs(5), receiver.get_one())
+            .await
+            .expect("cron trigger did not fire")
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(event.bytes()).unwrap();
+        event.done().await;
+
+        assert_eq!(body["job"], "cleanup");
+        assert!(DateTime::parse_from_rfc3339(body["_scheduled_at"].as_str().unwrap()).is_ok());
+    }
+}
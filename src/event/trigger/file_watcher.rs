@@ -0,0 +1,218 @@
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use super::{Error, Result};
+
+const EVENT_KINDS: &[&str] = &["create", "modify", "remove", "access"];
+
+fn default_events() -> Vec<String> {
+    vec!(String::from("create"), String::from("modify"), String::from("remove"))
+}
+
+#[derive(Deserialize)]
+struct FileWatcherConfig {
+    path: String,
+    #[serde(default = "default_events")]
+    events: Vec<String>,
+    #[serde(default)]
+    recursive: bool,
+}
+
+#[derive(Serialize)]
+struct FileEvent {
+    path: String,
+    kind: String,
+    timestamp: String,
+}
+
+pub struct Receiver {
+    path: String,
+    events: crossbeam_channel::Receiver<Vec<u8>>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl Receiver {
+    fn parse_config(trigger: &Trigger) -> Result<(FileWatcherConfig, PathBuf, Option<glob::Pattern>)> {
+        let config: FileWatcherConfig = trigger.config.clone()
+            .map(serde_yaml::from_value)
+            .ok_or(Error::InvalidConfig("missing config".to_string()))?
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        if let Some(kind) = config.events.iter().find(|k| !EVENT_KINDS.contains(&k.as_str())) {
+            return Err(Error::InvalidConfig(format!("unknown file event \"{}\", expected one of {:?}", kind, EVENT_KINDS)));
+        }
+
+        let (root, pattern) = split_glob(&config.path)?;
+        Ok((config, root, pattern))
+    }
+
+    pub fn validate(trigger: &Trigger) -> Result<()> {
+        Self::parse_config(trigger).map(|_| ())
+    }
+
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let (config, root, pattern) = Self::parse_config(trigger)?;
+
+        log::debug!("initializing file watcher on \"{}\"", config.path);
+
+        let (s, r) = crossbeam_channel::unbounded();
+        let kinds = config.events.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    log::error!("file watcher error: {}", e);
+                    return;
+                }
+            };
+
+            let kind = match event_kind(&event.kind) {
+                Some(kind) if kinds.iter().any(|k| k == kind) => kind,
+                _ => return,
+            };
+
+            event.paths.iter()
+                .filter(|p| pattern.as_ref().map(|pattern| pattern.matches_path(p)).unwrap_or(true))
+                .for_each(|p| {
+                    let content = serde_json::to_vec(&FileEvent {
+                        path: p.to_string_lossy().to_string(),
+                        kind: kind.to_string(),
+                        timestamp: chrono::Utc::now().to_rfc3339(),
+                    }).expect("unable to serialize file event");
+
+                    if s.send(content).is_err() {
+                        log::warn!("file watcher receiver dropped, ignoring event for {:?}", p);
+                    }
+                });
+        }).map_err(|e| Error::InvalidConfig(format!("unable to create file watcher: {}", e)))?;
+
+        let mode = if config.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        watcher.watch(&root, mode)
+            .map_err(|e| Error::InvalidConfig(format!("unable to watch {:?}: {}", root, e)))?;
+
+        Ok(Receiver {
+            path: config.path,
+            events: r,
+            _watcher: watcher,
+        })
+    }
+}
+
+fn event_kind(kind: &EventKind) -> Option<&'static str> {
+    match kind {
+        EventKind::Create(_) => Some("create"),
+        EventKind::Modify(_) => Some("modify"),
+        EventKind::Remove(_) => Some("remove"),
+        EventKind::Access(_) => Some("access"),
+        _ => None,
+    }
+}
+
+// Splits a glob into the deepest directory without wildcards, which is watched, and the pattern
+// used to filter the reported paths.
+fn split_glob(path: &str) -> Result<(PathBuf, Option<glob::Pattern>)> {
+    let is_glob = |s: &str| s.contains(['*', '?', '[']);
+    if !is_glob(path) {
+        return Ok((PathBuf::from(path), None));
+    }
+
+    let pattern = glob::Pattern::new(path)
+        .map_err(|e| Error::InvalidConfig(format!("invalid glob \"{}\": {}", path, e)))?;
+    let root = Path::new(path).components()
+        .take_while(|c| !matches!(c, Component::Normal(s) if is_glob(&s.to_string_lossy())))
+        .collect::<PathBuf>();
+    let root = if root.as_os_str().is_empty() { PathBuf::from(".") } else { root };
+
+    Ok((root, Some(pattern)))
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        let events = self.events.clone();
+        let content = tokio::task::spawn_blocking(move || events.recv())
+            .await
+            .map_err(|e| Error::PullError(format!("{}", e)))?
+            .map_err(|_| Error::PullError(format!("file watcher on {} stopped", self.path)))?;
+
+        Ok(Box::new(Event { content }))
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(config: &str) -> Trigger {
+        serde_yaml::from_str(format!(r#"
+type: file-watcher
+config:
+  {}
+"#, config).as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_split_glob_ok() {
+        let (root, pattern) = split_glob("/tmp/events").unwrap();
+        assert_eq!(root, PathBuf::from("/tmp/events"));
+        assert!(pattern.is_none());
+
+        let (root, pattern) = split_glob("/tmp/events/*/in/*.json").unwrap();
+        assert_eq!(root, PathBuf::from("/tmp/events"));
+        assert!(pattern.unwrap().matches_path(Path::new("/tmp/events/a/in/b.json")));
+
+        let (root, _) = split_glob("*.json").unwrap();
+        assert_eq!(root, PathBuf::from("."));
+    }
+
+    #[test]
+    fn test_validate_err() {
+        assert!(matches!(Receiver::validate(&trigger("path: /tmp\n  events: [rename]")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::validate(&trigger("path: /tmp/[")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::new(&trigger("path: /webhook/missing/directory")), Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_one_create_ok() {
+        let dir = std::env::temp_dir().join(format!("webhook_file_watcher_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let receiver = Receiver::new(&trigger(format!(
+            "path: {}/*.json\n  events: [create]",
+            dir.to_string_lossy(),
+        ).as_str())).unwrap();
+
+        std::fs::write(dir.join("ignored.txt"), "ignored").unwrap();
+        std::fs::write(dir.join("event.json"), "{}").unwrap();
+
+        let event = tokio::time::timeout(tokio::time::Duration::from_secs(5), receiver.get_one())
+            .await
+            .expect("file watcher did not fire")
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(event.bytes()).unwrap();
+        event.done().await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(body["kind"], "create");
+        assert_eq!(body["path"], dir.join("event.json").to_string_lossy().as_ref());
+        assert!(body["timestamp"].is_string());
+    }
+}
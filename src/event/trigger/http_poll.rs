@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use super::{Error, Result};
+
+fn default_method() -> String {
+    String::from("GET")
+}
+
+#[derive(Deserialize)]
+struct HttpPollConfig {
+    url: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    interval_seconds: u64,
+    #[serde(default)]
+    dedup_by_hash: bool,
+}
+
+pub struct Receiver {
+    config: HttpPollConfig,
+    method: reqwest::Method,
+    client: reqwest::Client,
+    interval: Mutex<tokio::time::Interval>,
+    last_hash: Mutex<Option<Vec<u8>>>,
+}
+
+impl Receiver {
+    fn parse_config(trigger: &Trigger) -> Result<(HttpPollConfig, reqwest::Method)> {
+        let config: HttpPollConfig = trigger.config.clone()
+            .map(serde_yaml::from_value)
+            .ok_or(Error::InvalidConfig("missing config".to_string()))?
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        if config.interval_seconds == 0 {
+            return Err(Error::InvalidConfig("interval_seconds must be greater than 0".into()));
+        }
+        let method = reqwest::Method::from_bytes(config.method.to_uppercase().as_bytes())
+            .map_err(|e| Error::InvalidConfig(format!("invalid method \"{}\": {}", config.method, e)))?;
+
+        Ok((config, method))
+    }
+
+    pub fn validate(trigger: &Trigger) -> Result<()> {
+        Self::parse_config(trigger).map(|_| ())
+    }
+
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let (config, method) = Self::parse_config(trigger)?;
+
+        log::debug!("initializing http poll trigger for {} \"{}\" every {}s", method, config.url, config.interval_seconds);
+
+        let interval = tokio::time::interval(tokio::time::Duration::from_secs(config.interval_seconds));
+        Ok(Receiver {
+            config,
+            method,
+            client: reqwest::Client::new(),
+            interval: Mutex::new(interval),
+            last_hash: Mutex::new(None),
+        })
+    }
+
+    async fn poll(&self) -> Result<Vec<u8>> {
+        let req = self.config.headers.iter()
+            .fold(self.client.request(self.method.clone(), &self.config.url), |req, (k, v)| req.header(k, v));
+        let resp = req.send().await
+            .map_err(|e| Error::PullError(format!("unable to poll \"{}\": {}", self.config.url, e)))?;
+
+        if !resp.status().is_success() {
+            return Err(Error::PullError(format!("polling \"{}\" failed with status {}", self.config.url, resp.status())));
+        }
+
+        let body = resp.bytes().await
+            .map_err(|e| Error::PullError(format!("unable to read response from \"{}\": {}", self.config.url, e)))?;
+        Ok(body.to_vec())
+    }
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        let mut interval = self.interval.lock().await;
+        loop {
+            interval.tick().await;
+
+            // Polling errors are transient, the next tick tries again.
+            let content = match self.poll().await {
+                Ok(content) => content,
+                Err(e) => {
+                    log::warn!("{}", e);
+                    continue;
+                }
+            };
+
+            if self.config.dedup_by_hash {
+                let hash = Sha256::digest(&content).to_vec();
+                let mut last_hash = self.last_hash.lock().await;
+                if last_hash.as_ref() == Some(&hash) {
+                    log::trace!("response from \"{}\" did not change", self.config.url);
+                    continue;
+                }
+                *last_hash = Some(hash);
+            }
+
+            return Ok(Box::new(Event { content }));
+        }
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{header, method, path};
+
+    use super::*;
+
+    fn trigger(config: &str) -> Trigger {
+        serde_yaml::from_str(format!(r#"
+type: http-poll
+config:
+  {}
+"#, config).as_str()).unwrap()
+    }
+
+    async fn next(receiver: &Receiver) -> Option<Vec<u8>> {
+        tokio::time::timeout(tokio::time::Duration::from_millis(3500), receiver.get_one())
+            .await
+            .ok()
+            .map(|e| e.unwrap().bytes().clone())
+    }
+
+    #[test]
+    fn test_validate_err() {
+        assert!(Receiver::validate(&trigger("url: http://localhost\n  interval_seconds: 5")).is_ok());
+        assert!(matches!(Receiver::validate(&trigger("url: http://localhost\n  interval_seconds: 0")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::validate(&trigger("url: http://localhost\n  interval_seconds: 5\n  method: \"G T\"")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::validate(&trigger("interval_seconds: 5")), Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_one_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/status"))
+            .and(header("x-token", "secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("up"))
+            .mount(&server)
+            .await;
+
+        let receiver = Receiver::new(&trigger(format!(
+            "url: {}/status\n  method: post\n  headers: {{ x-token: secret }}\n  interval_seconds: 1",
+            server.uri(),
+        ).as_str())).unwrap();
+
+        assert_eq!(next(&receiver).await, Some(b"up".to_vec()));
+        assert_eq!(next(&receiver).await, Some(b"up".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_get_one_dedup_by_hash() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("v1"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("v2"))
+            .mount(&server)
+            .await;
+
+        let receiver = Receiver::new(&trigger(format!(
+            "url: {}/status\n  interval_seconds: 1\n  dedup_by_hash: true",
+            server.uri(),
+        ).as_str())).unwrap();
+
+        assert_eq!(next(&receiver).await, Some(b"v1".to_vec()));
+        // the second poll returns the same body and is skipped, the third one has changed
+        assert_eq!(next(&receiver).await, Some(b"v2".to_vec()));
+        assert_eq!(next(&receiver).await, None);
+    }
+}
@@ -0,0 +1,279 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use serde::Deserialize;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use super::{Error, Result};
+
+fn default_bind() -> String {
+    String::from("0.0.0.0")
+}
+
+fn default_methods() -> Vec<String> {
+    vec!(String::from("POST"))
+}
+
+fn default_response_status() -> u16 {
+    200
+}
+
+#[derive(Deserialize)]
+struct HttpServerConfig {
+    port: u16,
+    path: String,
+    #[serde(default = "default_bind")]
+    bind: String,
+    #[serde(default = "default_methods")]
+    methods: Vec<String>,
+    #[serde(default = "default_response_status")]
+    response_status: u16,
+}
+
+struct PendingRequest {
+    content: Vec<u8>,
+    respond: oneshot::Sender<bool>,
+}
+
+pub struct Receiver {
+    addr: SocketAddr,
+    requests: tokio::sync::Mutex<mpsc::Receiver<PendingRequest>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+struct Route {
+    path: String,
+    methods: Vec<Method>,
+    response_status: StatusCode,
+    requests: mpsc::Sender<PendingRequest>,
+}
+
+impl Receiver {
+    fn parse_config(trigger: &Trigger) -> Result<(HttpServerConfig, SocketAddr, Vec<Method>, StatusCode)> {
+        let config: HttpServerConfig = trigger.config.clone()
+            .map(serde_yaml::from_value)
+            .ok_or(Error::InvalidConfig("missing config".to_string()))?
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        let addr = format!("{}:{}", config.bind, config.port).parse::<SocketAddr>()
+            .map_err(|e| Error::InvalidConfig(format!("invalid bind address \"{}\": {}", config.bind, e)))?;
+        let methods = config.methods.iter()
+            .map(|m| Method::from_bytes(m.to_uppercase().as_bytes())
+                .map_err(|e| Error::InvalidConfig(format!("invalid method \"{}\": {}", m, e))))
+            .collect::<Result<Vec<_>>>()?;
+        let response_status = StatusCode::from_u16(config.response_status)
+            .map_err(|e| Error::InvalidConfig(format!("invalid response_status {}: {}", config.response_status, e)))?;
+
+        Ok((config, addr, methods, response_status))
+    }
+
+    pub fn validate(trigger: &Trigger) -> Result<()> {
+        Self::parse_config(trigger).map(|_| ())
+    }
+
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let (config, addr, methods, response_status) = Self::parse_config(trigger)?;
+
+        let (s, r) = mpsc::channel(1);
+        let route = std::sync::Arc::new(Route {
+            path: config.path,
+            methods,
+            response_status,
+            requests: s,
+        });
+
+        let make_service = make_service_fn(move |_| {
+            let route = route.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let route = route.clone();
+                    async move { Ok::<_, Infallible>(route.handle(req).await) }
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&addr)
+            .map_err(|e| Error::InvalidConfig(format!("unable to bind {}: {}", addr, e)))?
+            .serve(make_service);
+        let addr = server.local_addr();
+
+        let (shutdown, stopped) = oneshot::channel();
+        crate::event::utils::logger::spawn(async move {
+            let server = server.with_graceful_shutdown(async move { let _ = stopped.await; });
+            if let Err(e) = server.await {
+                log::error!("http trigger server error: {}", e);
+            }
+        });
+        log::info!("http trigger listening on {}", addr);
+
+        Ok(Receiver {
+            addr,
+            requests: tokio::sync::Mutex::new(r),
+            shutdown: Some(shutdown),
+        })
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+impl Route {
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if req.uri().path() != self.path {
+            return status(StatusCode::NOT_FOUND);
+        }
+        if !self.methods.contains(req.method()) {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        let content = match hyper::body::to_bytes(req.into_body()).await {
+            Ok(content) => content.to_vec(),
+            Err(e) => {
+                log::warn!("unable to read http trigger request body: {}", e);
+                return status(StatusCode::BAD_REQUEST);
+            }
+        };
+
+        let (respond, response) = oneshot::channel();
+        if self.requests.send(PendingRequest { content, respond }).await.is_err() {
+            return status(StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        match response.await {
+            Ok(true) => status(self.response_status),
+            _ => status(StatusCode::SERVICE_UNAVAILABLE),
+        }
+    }
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut res = Response::new(Body::empty());
+    *res.status_mut() = code;
+    res
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        let request = self.requests.lock().await
+            .recv()
+            .await
+            .ok_or_else(|| Error::PullError(format!("http trigger on {} stopped", self.addr)))?;
+
+        Ok(Box::new(Event {
+            content: request.content,
+            respond: Mutex::new(Some(request.respond)),
+        }))
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+    respond: Mutex<Option<oneshot::Sender<bool>>>,
+}
+
+impl Event {
+    fn respond(&self, acked: bool) {
+        let respond = self.respond.lock().expect("http trigger event lock poisoned").take();
+        if let Some(respond) = respond {
+            if respond.send(acked).is_err() {
+                log::warn!("http trigger client disconnected before the response was sent");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {
+        self.respond(true);
+    }
+
+    async fn nack(&self) {
+        self.respond(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receiver(config: &str) -> Receiver {
+        let trigger: Trigger = serde_yaml::from_str(format!(r#"
+type: http
+config:
+  port: 0
+  bind: 127.0.0.1
+  {}
+"#, config).as_str()).unwrap();
+
+        Receiver::new(&trigger).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_one_ok() {
+        let receiver = receiver("path: /hook\n  response_status: 202");
+        let url = format!("http://{}/hook", receiver.addr);
+
+        let client = reqwest::Client::new();
+        let requests = (0..2).map(|i| {
+            let client = client.clone();
+            let url = url.clone();
+            tokio::spawn(async move { client.post(url.as_str()).body(format!("hello {}", i)).send().await })
+        }).collect::<Vec<_>>();
+
+        let mut received = vec!();
+        for _ in 0..2 {
+            let event = receiver.get_one().await.unwrap();
+            received.push(String::from_utf8(event.bytes().clone()).unwrap());
+            event.done().await;
+        }
+        received.sort();
+        assert_eq!(received, vec!("hello 0", "hello 1"));
+
+        for request in requests {
+            let res = request.await.unwrap().unwrap();
+            assert_eq!(res.status(), reqwest::StatusCode::ACCEPTED);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_not_found_and_method_not_allowed() {
+        let receiver = receiver("path: /hook");
+        let client = reqwest::Client::new();
+
+        let res = client.post(format!("http://{}/other", receiver.addr)).send().await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let res = client.get(format!("http://{}/hook", receiver.addr)).send().await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_nack_returns_unavailable() {
+        let receiver = receiver("path: /hook");
+        let url = format!("http://{}/hook", receiver.addr);
+
+        let request = tokio::spawn(async move { reqwest::Client::new().post(url.as_str()).send().await });
+
+        let event = receiver.get_one().await.unwrap();
+        event.nack().await;
+
+        let res = request.await.unwrap().unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use rdkafka::{ClientConfig, ClientContext, Message, Offset, TopicPartitionList};
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer, ConsumerContext, Rebalance, StreamConsumer};
+use serde::Deserialize;
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use super::{Error, Result};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum AutoOffsetReset {
+    Earliest,
+    Latest,
+}
+
+fn default_auto_offset_reset() -> AutoOffsetReset {
+    AutoOffsetReset::Latest
+}
+
+#[derive(Deserialize)]
+struct KafkaConfig {
+    bootstrap_servers: String,
+    topic: String,
+    group_id: String,
+    #[serde(default = "default_auto_offset_reset")]
+    auto_offset_reset: AutoOffsetReset,
+    sasl_username: Option<String>,
+    sasl_password: Option<String>,
+}
+
+struct KafkaContext;
+
+impl ClientContext for KafkaContext {}
+
+impl ConsumerContext for KafkaContext {
+    fn pre_rebalance(&self, _: &BaseConsumer<Self>, rebalance: &Rebalance<'_>) {
+        match rebalance {
+            Rebalance::Assign(partitions) => log::info!("kafka assigning {} partitions", partitions.count()),
+            Rebalance::Revoke(partitions) => log::info!("kafka revoking {} partitions", partitions.count()),
+            Rebalance::Error(e) => log::error!("kafka rebalance error: {}", e),
+        }
+    }
+}
+
+type KafkaConsumer = StreamConsumer<KafkaContext>;
+
+pub struct Receiver {
+    consumer: Arc<KafkaConsumer>,
+    topic: String,
+}
+
+impl Receiver {
+    fn parse_config(trigger: &Trigger) -> Result<KafkaConfig> {
+        let config: KafkaConfig = trigger.config.clone()
+            .map(serde_yaml::from_value)
+            .ok_or(Error::InvalidConfig("missing config".to_string()))?
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        if config.sasl_username.is_some() != config.sasl_password.is_some() {
+            return Err(Error::InvalidConfig("sasl_username and sasl_password must be set together".into()));
+        }
+
+        Ok(config)
+    }
+
+    fn client_config(config: &KafkaConfig) -> ClientConfig {
+        let mut client_config = ClientConfig::new();
+        client_config
+            .set("bootstrap.servers", config.bootstrap_servers.as_str())
+            .set("group.id", config.group_id.as_str())
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", match config.auto_offset_reset {
+                AutoOffsetReset::Earliest => "earliest",
+                AutoOffsetReset::Latest => "latest",
+            });
+
+        if let (Some(username), Some(password)) = (&config.sasl_username, &config.sasl_password) {
+            client_config
+                .set("security.protocol", "SASL_SSL")
+                .set("sasl.mechanisms", "PLAIN")
+                .set("sasl.username", username.as_str())
+                .set("sasl.password", password.as_str());
+        }
+
+        client_config
+    }
+
+    pub fn validate(trigger: &Trigger) -> Result<()> {
+        Self::parse_config(trigger).map(|_| ())
+    }
+
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let config = Self::parse_config(trigger)?;
+
+        log::debug!("initializing kafka receiver for topic \"{}\"", config.topic);
+
+        let consumer: KafkaConsumer = Self::client_config(&config)
+            .create_with_context(KafkaContext)
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+        consumer.subscribe(&[config.topic.as_str()])
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        Ok(Receiver {
+            consumer: Arc::new(consumer),
+            topic: config.topic,
+        })
+    }
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        let message = self.consumer.recv()
+            .await
+            .map_err(|e| Error::PullError(format!("{}", e)))?;
+
+        log::trace!("kafka ({}) received message at partition {} offset {}", self.topic, message.partition(), message.offset());
+
+        Ok(Box::new(Event {
+            content: message.payload().unwrap_or_default().to_vec(),
+            consumer: self.consumer.clone(),
+            topic: message.topic().to_string(),
+            partition: message.partition(),
+            offset: message.offset(),
+        }))
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+
+    consumer: Arc<KafkaConsumer>,
+    topic: String,
+    partition: i32,
+    offset: i64,
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {
+        log::trace!("committing kafka offset {} of {}/{}", self.offset, self.topic, self.partition);
+
+        let mut offsets = TopicPartitionList::new();
+        let res = offsets
+            .add_partition_offset(self.topic.as_str(), self.partition, Offset::Offset(self.offset + 1))
+            .and_then(|_| self.consumer.commit(&offsets, CommitMode::Async));
+
+        if let Err(e) = res {
+            log::error!("error committing kafka offset {} of {}/{}: {}", self.offset, self.topic, self.partition, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(config: &str) -> Trigger {
+        serde_yaml::from_str(format!(r#"
+type: kafka
+config:
+  bootstrap_servers: localhost:9092
+  topic: events
+  group_id: webhook
+  {}
+"#, config).as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_client_config_ok() {
+        let config = Receiver::parse_config(&trigger("auto_offset_reset: earliest")).unwrap();
+        let client_config = Receiver::client_config(&config);
+
+        assert_eq!(client_config.get("bootstrap.servers"), Some("localhost:9092"));
+        assert_eq!(client_config.get("group.id"), Some("webhook"));
+        assert_eq!(client_config.get("auto.offset.reset"), Some("earliest"));
+        assert_eq!(client_config.get("enable.auto.commit"), Some("false"));
+        assert_eq!(client_config.get("sasl.username"), None);
+    }
+
+    #[test]
+    fn test_client_config_sasl_ok() {
+        let config = Receiver::parse_config(&trigger("sasl_username: user\n  sasl_password: secret")).unwrap();
+        let client_config = Receiver::client_config(&config);
+
+        assert_eq!(client_config.get("auto.offset.reset"), Some("latest"));
+        assert_eq!(client_config.get("security.protocol"), Some("SASL_SSL"));
+        assert_eq!(client_config.get("sasl.username"), Some("user"));
+        assert_eq!(client_config.get("sasl.password"), Some("secret"));
+    }
+
+    #[test]
+    fn test_validate_err() {
+        assert!(matches!(Receiver::validate(&trigger("sasl_username: user")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::validate(&trigger("auto_offset_reset: never")), Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_new_without_broker_ok() {
+        let receiver = Receiver::new(&trigger(""));
+        assert!(receiver.is_ok());
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use super::{Error, Result};
+
+fn channels() -> &'static Mutex<HashMap<String, VecDeque<Vec<u8>>>> {
+    static CHANNELS: OnceLock<Mutex<HashMap<String, VecDeque<Vec<u8>>>>> = OnceLock::new();
+    CHANNELS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn push(channel: &str, message: &str) {
+    channels().lock().unwrap()
+        .entry(channel.to_string())
+        .or_default()
+        .push_back(message.as_bytes().to_vec());
+}
+
+pub struct Receiver {
+    messages: Mutex<VecDeque<Vec<u8>>>,
+    channel: Option<String>,
+    failures: Mutex<usize>,
+}
+
+#[derive(Deserialize)]
+struct MockConfig {
+    #[serde(default)]
+    messages: Vec<String>,
+    channel: Option<String>,
+    #[serde(default)]
+    failures: usize,
+}
+
+impl Receiver {
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let config = match trigger.config.clone() {
+            None => MockConfig { messages: vec!(), channel: None, failures: 0 },
+            Some(config) => serde_yaml::from_value(config)
+                .map_err(|e| Error::InvalidConfig(format!("{}", e)))?,
+        };
+
+        Ok(Receiver {
+            messages: Mutex::new(config.messages.into_iter().map(|m| m.into_bytes()).collect()),
+            channel: config.channel,
+            failures: Mutex::new(config.failures),
+        })
+    }
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(Error::PullError("mock failure".into()));
+            }
+        }
+
+        let message = self.messages.lock().unwrap().pop_front();
+        if let Some(content) = message {
+            return Ok(Box::new(Event { content }));
+        }
+
+        match &self.channel {
+            None => futures::future::pending().await,
+            Some(channel) => loop {
+                let message = channels().lock().unwrap()
+                    .get_mut(channel)
+                    .and_then(|c| c.pop_front());
+                if let Some(content) = message {
+                    return Ok(Box::new(Event { content }));
+                }
+                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            },
+        }
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {}
+}
@@ -1,6 +1,19 @@
+#[cfg(test)]
+pub mod mock;
+mod cron;
+mod file_watcher;
+mod http_poll;
+mod http_server;
+mod kafka;
+mod nats;
 mod pubsub;
+mod redis;
+mod sqs;
 
 use serde::{Deserialize};
+
+#[cfg(test)]
+pub(crate) use pubsub::tests::TEST_PRIVATE_KEY;
 use thiserror::Error;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -23,11 +36,33 @@ pub enum Error {
     InvalidCredential(String),
 
     #[error("failed to pull data: {0}")]
-    PullError(String)
+    PullError(String),
+
+    #[error("rate limited, retry after {retry_after_secs:?}s")]
+    RateLimited { retry_after_secs: Option<u64> },
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+impl Trigger {
+    pub fn validate(&self) -> Result<()> {
+        match self.trigger_type.as_str() {
+            "google-pubsub" => pubsub::Receiver::validate(self),
+            "http" => http_server::Receiver::validate(self),
+            "http-poll" => http_poll::Receiver::validate(self),
+            "kafka" => kafka::Receiver::validate(self),
+            "aws-sqs" => sqs::Receiver::validate(self),
+            "file-watcher" => file_watcher::Receiver::validate(self),
+            "cron" => cron::Receiver::validate(self),
+            "nats" => nats::Receiver::validate(self),
+            "redis-pubsub" => redis::Receiver::validate(self),
+            #[cfg(test)]
+            "mock" => mock::Receiver::new(self).map(|_| ()),
+            t => Err(Error::UnknownType(t.to_string())),
+        }
+    }
+}
+
 use async_trait::async_trait;
 
 #[async_trait]
@@ -39,11 +74,23 @@ pub trait SourceEventReceiver: Send + Sync {
 pub trait SourceEvent: Send + Sync {
     fn bytes(&self) -> &Vec<u8>;
     async fn done(&self);
+
+    async fn nack(&self) {}
 }
 
-pub fn new_source_event_receiver(trigger: &Trigger) -> Result<Box<dyn SourceEventReceiver>> {
+pub async fn new_source_event_receiver(trigger: &Trigger) -> Result<Box<dyn SourceEventReceiver>> {
     match trigger.trigger_type.as_str() {
-        "google-pubsub" => Ok(Box::new(pubsub::Receiver::new(trigger)?)),
+        "google-pubsub" => Ok(Box::new(pubsub::Receiver::new_async(trigger).await?)),
+        "http" => Ok(Box::new(http_server::Receiver::new(trigger)?)),
+        "http-poll" => Ok(Box::new(http_poll::Receiver::new(trigger)?)),
+        "kafka" => Ok(Box::new(kafka::Receiver::new(trigger)?)),
+        "aws-sqs" => Ok(Box::new(sqs::Receiver::new(trigger)?)),
+        "file-watcher" => Ok(Box::new(file_watcher::Receiver::new(trigger)?)),
+        "cron" => Ok(Box::new(cron::Receiver::new(trigger)?)),
+        "nats" => Ok(Box::new(nats::Receiver::new(trigger)?)),
+        "redis-pubsub" => Ok(Box::new(redis::Receiver::new(trigger)?)),
+        #[cfg(test)]
+        "mock" => Ok(Box::new(mock::Receiver::new(trigger)?)),
         t => Err(Error::UnknownType(t.to_string())),
     }
 }
@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use super::{Error, Result};
+
+#[derive(Deserialize)]
+struct NatsConfig {
+    url: String,
+    subject: String,
+    queue_group: Option<String>,
+    #[serde(default)]
+    jetstream: bool,
+}
+
+struct Subscription {
+    client: async_nats::Client,
+    subscriber: Mutex<async_nats::Subscriber>,
+}
+
+pub struct Receiver {
+    config: NatsConfig,
+    subscription: OnceCell<Subscription>,
+}
+
+impl Receiver {
+    fn parse_config(trigger: &Trigger) -> Result<NatsConfig> {
+        let config: NatsConfig = trigger.config.clone()
+            .map(serde_yaml::from_value)
+            .ok_or(Error::InvalidConfig("missing config".to_string()))?
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        if config.subject.is_empty() {
+            return Err(Error::InvalidConfig("subject must not be empty".into()));
+        }
+
+        Ok(config)
+    }
+
+    pub fn validate(trigger: &Trigger) -> Result<()> {
+        Self::parse_config(trigger).map(|_| ())
+    }
+
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let config = Self::parse_config(trigger)?;
+
+        log::debug!("initializing nats receiver for subject \"{}\"", config.subject);
+
+        Ok(Receiver {
+            config,
+            subscription: OnceCell::new(),
+        })
+    }
+
+    // Connecting is async, so the subscription is created on the first pull.
+    async fn subscription(&self) -> Result<&Subscription> {
+        self.subscription.get_or_try_init(|| async {
+            let client = async_nats::connect(self.config.url.as_str())
+                .await
+                .map_err(|e| Error::PullError(format!("unable to connect to {}: {}", self.config.url, e)))?;
+
+            let subject = self.config.subject.clone();
+            let subscriber = match &self.config.queue_group {
+                None => client.subscribe(subject).await,
+                Some(group) => client.queue_subscribe(subject, group.clone()).await,
+            }.map_err(|e| Error::PullError(format!("unable to subscribe to {}: {}", self.config.subject, e)))?;
+
+            Ok(Subscription {
+                client,
+                subscriber: Mutex::new(subscriber),
+            })
+        }).await
+    }
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        let subscription = self.subscription().await?;
+
+        let message = subscription.subscriber.lock().await
+            .next()
+            .await
+            .ok_or_else(|| Error::PullError(format!("nats subscription to {} closed", self.config.subject)))?;
+        log::trace!("nats ({}) received {} bytes", message.subject, message.payload.len());
+
+        let content = message.payload.to_vec();
+        let jetstream_message = if self.config.jetstream {
+            Some(async_nats::jetstream::Message {
+                message,
+                context: async_nats::jetstream::new(subscription.client.clone()),
+            })
+        } else {
+            None
+        };
+
+        Ok(Box::new(Event {
+            content,
+            jetstream_message,
+        }))
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+    jetstream_message: Option<async_nats::jetstream::Message>,
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {
+        if let Some(message) = &self.jetstream_message {
+            if let Err(e) = message.ack().await {
+                log::error!("error acking nats message from {}: {}", message.subject, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(config: &str) -> Trigger {
+        serde_yaml::from_str(format!(r#"
+type: nats
+config:
+  {}
+"#, config).as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let config = Receiver::parse_config(&trigger("url: nats://localhost:4222\n  subject: events.>\n  queue_group: webhook")).unwrap();
+        assert_eq!(config.queue_group.as_deref(), Some("webhook"));
+        assert!(!config.jetstream);
+
+        assert!(matches!(Receiver::validate(&trigger("url: nats://localhost:4222\n  subject: \"\"")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::validate(&trigger("subject: events")), Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_one_connection_err() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let receiver = Receiver::new(&trigger(format!("url: nats://{}\n  subject: events", addr).as_str())).unwrap();
+
+        let res = receiver.get_one().await;
+        assert!(matches!(res, Err(Error::PullError(_))));
+    }
+}
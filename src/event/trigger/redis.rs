@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use crate::event::utils::backoff::Backoff;
+use super::{Error, Result};
+
+#[derive(Deserialize)]
+struct RedisConfig {
+    url: String,
+    channel: String,
+}
+
+pub struct Receiver {
+    client: redis::Client,
+    channel: String,
+    pubsub: Mutex<Option<redis::aio::PubSub>>,
+    backoff: Backoff,
+}
+
+impl Receiver {
+    fn parse_config(trigger: &Trigger) -> Result<(RedisConfig, redis::Client)> {
+        let config: RedisConfig = trigger.config.clone()
+            .map(serde_yaml::from_value)
+            .ok_or(Error::InvalidConfig("missing config".to_string()))?
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        let client = redis::Client::open(config.url.as_str())
+            .map_err(|e| Error::InvalidConfig(format!("invalid redis url \"{}\": {}", config.url, e)))?;
+
+        Ok((config, client))
+    }
+
+    pub fn validate(trigger: &Trigger) -> Result<()> {
+        Self::parse_config(trigger).map(|_| ())
+    }
+
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let (config, client) = Self::parse_config(trigger)?;
+
+        log::debug!("initializing redis receiver for channel \"{}\"", config.channel);
+
+        Ok(Receiver {
+            client,
+            channel: config.channel,
+            pubsub: Mutex::new(None),
+            backoff: Backoff::default(),
+        })
+    }
+
+    async fn subscribe(&self) -> redis::RedisResult<redis::aio::PubSub> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(self.channel.as_str()).await?;
+        Ok(pubsub)
+    }
+
+    async fn connect(&self) -> Result<redis::aio::PubSub> {
+        let mut backoff = self.backoff.clone();
+
+        loop {
+            match self.subscribe().await {
+                Ok(pubsub) => return Ok(pubsub),
+                Err(e) => {
+                    log::warn!("unable to subscribe to redis channel {} (attempt {}): {}", self.channel, backoff.attempt() + 1, e);
+                    if !backoff.wait().await {
+                        return Err(Error::PullError(format!(
+                            "unable to subscribe to redis channel {} after {} attempts: {}",
+                            self.channel, backoff.attempt() + 1, e,
+                        )));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        let mut pubsub = self.pubsub.lock().await;
+
+        loop {
+            if pubsub.is_none() {
+                *pubsub = Some(self.connect().await?);
+            }
+
+            let message = match pubsub.as_mut() {
+                Some(current) => current.on_message().next().await,
+                None => None,
+            };
+
+            match message {
+                Some(message) => {
+                    log::trace!("redis ({}) received {} bytes", self.channel, message.get_payload_bytes().len());
+                    return Ok(Box::new(Event {
+                        content: message.get_payload_bytes().to_vec(),
+                    }));
+                }
+                None => {
+                    log::warn!("redis connection for channel {} lost, reconnecting", self.channel);
+                    *pubsub = None;
+                }
+            }
+        }
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use testcontainers_modules::redis::{Redis, REDIS_PORT};
+    use testcontainers_modules::testcontainers::runners::AsyncRunner;
+    use tokio::time::Duration;
+
+    use super::*;
+
+    fn trigger(url: &str) -> Trigger {
+        serde_yaml::from_str(format!(r#"
+type: redis-pubsub
+config:
+  url: {}
+  channel: events
+"#, url).as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_validate_err() {
+        assert!(Receiver::validate(&trigger("redis://localhost:6379")).is_ok());
+        assert!(matches!(Receiver::validate(&trigger("localhost:6379")), Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_one_reconnect_exhausted_err() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let mut receiver = Receiver::new(&trigger(format!("redis://{}", addr).as_str())).unwrap();
+        receiver.backoff = Backoff::new(Duration::from_millis(1), 1.0, Duration::from_millis(1), 2);
+
+        let res = receiver.get_one().await;
+        assert!(matches!(res, Err(Error::PullError(e)) if e.contains("after 3 attempts")));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires docker"]
+    async fn test_get_one_ok() {
+        let container = Redis::default().start().await.unwrap();
+        let url = format!("redis://127.0.0.1:{}", container.get_host_port_ipv4(REDIS_PORT).await.unwrap());
+
+        let receiver = Receiver::new(&trigger(url.as_str())).unwrap();
+        let event = tokio::spawn(async move {
+            let event = receiver.get_one().await.unwrap();
+            event.bytes().clone()
+        });
+
+        let mut connection = redis::Client::open(url.as_str()).unwrap()
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        for _ in 0..50 {
+            let receivers: i64 = redis::cmd("PUBLISH").arg("events").arg("hello")
+                .query_async(&mut connection)
+                .await
+                .unwrap();
+            if receivers > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        assert_eq!(event.await.unwrap(), b"hello");
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use aws_sdk_sqs::Client;
+use aws_sdk_sqs::config::Region;
+use aws_sdk_sqs::error::DisplayErrorContext;
+use aws_sdk_sqs::types::Message;
+use serde::Deserialize;
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::event::trigger::{SourceEvent, SourceEventReceiver, Trigger};
+use super::{Error, Result};
+
+fn default_max_messages() -> u32 {
+    1
+}
+
+fn default_wait_time_seconds() -> u32 {
+    20
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct SqsConfig {
+    queue_url: String,
+    region: String,
+    #[serde(default = "default_max_messages")]
+    max_messages: u32,
+    visibility_timeout_seconds: Option<u32>,
+    #[serde(default = "default_wait_time_seconds")]
+    wait_time_seconds: u32,
+    endpoint_url: Option<String>,
+}
+
+pub struct Receiver {
+    config: SqsConfig,
+    client: OnceCell<Client>,
+    buffer: Mutex<VecDeque<Message>>,
+}
+
+impl Receiver {
+    fn parse_config(trigger: &Trigger) -> Result<SqsConfig> {
+        let config: SqsConfig = trigger.config.clone()
+            .map(serde_yaml::from_value)
+            .ok_or(Error::InvalidConfig("missing config".to_string()))?
+            .map_err(|e| Error::InvalidConfig(format!("{}", e)))?;
+
+        if !(1..=10).contains(&config.max_messages) {
+            return Err(Error::InvalidConfig(format!("max_messages must be between 1 and 10, found {}", config.max_messages)));
+        }
+        if config.wait_time_seconds > 20 {
+            return Err(Error::InvalidConfig(format!("wait_time_seconds must be at most 20, found {}", config.wait_time_seconds)));
+        }
+
+        Ok(config)
+    }
+
+    pub fn validate(trigger: &Trigger) -> Result<()> {
+        Self::parse_config(trigger).map(|_| ())
+    }
+
+    pub fn new(trigger: &Trigger) -> Result<Self> {
+        let config = Self::parse_config(trigger)?;
+
+        log::debug!("initializing sqs receiver for queue \"{}\"", config.queue_url);
+
+        Ok(Receiver {
+            config,
+            client: OnceCell::new(),
+            buffer: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    // Credentials are resolved lazily on the first pull since loading the AWS config is async.
+    async fn client(&self) -> &Client {
+        self.client.get_or_init(|| async {
+            let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest())
+                .region(Region::new(self.config.region.clone()));
+            if let Some(endpoint_url) = &self.config.endpoint_url {
+                loader = loader.endpoint_url(endpoint_url.as_str());
+            }
+
+            Client::new(&loader.load().await)
+        }).await
+    }
+
+    async fn receive(&self) -> Result<Vec<Message>> {
+        let mut request = self.client().await
+            .receive_message()
+            .queue_url(self.config.queue_url.as_str())
+            .max_number_of_messages(self.config.max_messages as i32)
+            .wait_time_seconds(self.config.wait_time_seconds as i32);
+        if let Some(visibility_timeout) = self.config.visibility_timeout_seconds {
+            request = request.visibility_timeout(visibility_timeout as i32);
+        }
+
+        let resp = request.send()
+            .await
+            .map_err(|e| Error::PullError(format!("{}", DisplayErrorContext(e))))?;
+
+        Ok(resp.messages.unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl SourceEventReceiver for Receiver {
+    async fn get_one(&self) -> Result<Box<dyn SourceEvent>> {
+        let mut buffer = self.buffer.lock().await;
+
+        let message = loop {
+            if let Some(message) = buffer.pop_front() {
+                break message;
+            }
+
+            let messages = self.receive().await?;
+            log::trace!("sqs ({}) received {} messages", self.config.queue_url, messages.len());
+            buffer.extend(messages);
+        };
+
+        let receipt_handle = message.receipt_handle
+            .ok_or_else(|| Error::PullError("sqs message without receipt handle".into()))?;
+
+        Ok(Box::new(Event {
+            content: message.body.unwrap_or_default().into_bytes(),
+            client: self.client().await.clone(),
+            queue_url: self.config.queue_url.clone(),
+            receipt_handle,
+        }))
+    }
+}
+
+struct Event {
+    content: Vec<u8>,
+
+    client: Client,
+    queue_url: String,
+    receipt_handle: String,
+}
+
+#[async_trait]
+impl SourceEvent for Event {
+    fn bytes(&self) -> &Vec<u8> {
+        &self.content
+    }
+
+    async fn done(&self) {
+        log::trace!("deleting sqs message from {}", self.queue_url);
+
+        let res = self.client.delete_message()
+            .queue_url(self.queue_url.as_str())
+            .receipt_handle(self.receipt_handle.as_str())
+            .send()
+            .await;
+
+        if let Err(e) = res {
+            log::error!("error deleting sqs message from {}: {}", self.queue_url, DisplayErrorContext(e));
+        }
+    }
+
+    async fn nack(&self) {
+        log::trace!("returning sqs message to {}", self.queue_url);
+
+        let res = self.client.change_message_visibility()
+            .queue_url(self.queue_url.as_str())
+            .receipt_handle(self.receipt_handle.as_str())
+            .visibility_timeout(0)
+            .send()
+            .await;
+
+        if let Err(e) = res {
+            log::error!("error returning sqs message to {}: {}", self.queue_url, DisplayErrorContext(e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_sqs::config::{BehaviorVersion, Credentials};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+    use wiremock::matchers::{body_partial_json, header, method};
+
+    use super::*;
+
+    fn trigger(config: &str) -> Trigger {
+        serde_yaml::from_str(format!(r#"
+type: aws-sqs
+config:
+  queue_url: https://sqs.us-east-1.amazonaws.com/000000000000/events
+  region: us-east-1
+  {}
+"#, config).as_str()).unwrap()
+    }
+
+    fn receiver(server: &MockServer, config: &str) -> Receiver {
+        let receiver = Receiver::new(&trigger(config)).unwrap();
+
+        let sdk_config = aws_sdk_sqs::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .endpoint_url(server.uri())
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .build();
+        let _ = receiver.client.set(Client::from_conf(sdk_config));
+
+        receiver
+    }
+
+    fn target(operation: &str) -> wiremock::matchers::HeaderExactMatcher {
+        header("x-amz-target", format!("AmazonSQS.{}", operation).as_str())
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let config = Receiver::parse_config(&trigger("")).unwrap();
+        assert_eq!(config.max_messages, 1);
+        assert_eq!(config.wait_time_seconds, 20);
+        assert_eq!(config.visibility_timeout_seconds, None);
+
+        assert!(matches!(Receiver::validate(&trigger("max_messages: 11")), Err(Error::InvalidConfig(_))));
+        assert!(matches!(Receiver::validate(&trigger("wait_time_seconds: 21")), Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_one_and_done_ok() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(target("ReceiveMessage"))
+            .and(body_partial_json(serde_json::json!({"MaxNumberOfMessages": 2, "WaitTimeSeconds": 0})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Messages": [
+                    {"MessageId": "1", "ReceiptHandle": "handle-1", "Body": "hello"},
+                    {"MessageId": "2", "ReceiptHandle": "handle-2", "Body": "world"},
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(target("DeleteMessage"))
+            .and(body_partial_json(serde_json::json!({"ReceiptHandle": "handle-1"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(target("ChangeMessageVisibility"))
+            .and(body_partial_json(serde_json::json!({"ReceiptHandle": "handle-2", "VisibilityTimeout": 0})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let receiver = receiver(&server, "max_messages: 2\n  wait_time_seconds: 0");
+
+        let event = receiver.get_one().await.unwrap();
+        assert_eq!(event.bytes(), &"hello".as_bytes().to_vec());
+        event.done().await;
+
+        let event = receiver.get_one().await.unwrap();
+        assert_eq!(event.bytes(), &"world".as_bytes().to_vec());
+        event.nack().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_one_err() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(target("ReceiveMessage"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "__type": "com.amazonaws.sqs#QueueDoesNotExist",
+                "message": "The specified queue does not exist.",
+            })))
+            .mount(&server)
+            .await;
+
+        let receiver = receiver(&server, "wait_time_seconds: 0");
+
+        let res = receiver.get_one().await;
+        assert!(matches!(res, Err(Error::PullError(e)) if e.contains("QueueDoesNotExist")));
+    }
+}
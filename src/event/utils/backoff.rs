@@ -0,0 +1,78 @@
+use tokio::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    multiplier: f64,
+    max: Duration,
+    max_attempts: usize,
+    attempt: usize,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, multiplier: f64, max: Duration, max_attempts: usize) -> Self {
+        Backoff {
+            initial,
+            multiplier,
+            max,
+            max_attempts,
+            attempt: 0,
+        }
+    }
+
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// Returns the delay before the next attempt, or `None` once all attempts are used.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempt >= self.max_attempts {
+            return None;
+        }
+
+        let delay = self.initial.mul_f64(self.multiplier.powi(self.attempt as i32));
+        self.attempt += 1;
+        Some(delay.min(self.max))
+    }
+
+    pub async fn wait(&mut self) -> bool {
+        match self.next_delay() {
+            Some(delay) => {
+                tokio::time::sleep(delay).await;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_secs(1), 2.0, Duration::from_secs(30), 5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_delay_ok() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), 2.0, Duration::from_millis(300), 4);
+
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(100)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(200)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(300)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(300)));
+        assert_eq!(backoff.next_delay(), None);
+        assert_eq!(backoff.attempt(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_wait_ok() {
+        let mut backoff = Backoff::new(Duration::from_millis(1), 1.0, Duration::from_millis(1), 1);
+
+        assert!(backoff.wait().await);
+        assert!(!backoff.wait().await);
+    }
+}
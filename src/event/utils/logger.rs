@@ -0,0 +1,222 @@
+use std::io::Write;
+
+tokio::task_local! {
+    static CURRENT_PIPELINE: String;
+    static CORRELATION_ID: String;
+}
+
+pub async fn with_pipeline<F: std::future::Future>(name: String, f: F) -> F::Output {
+    CURRENT_PIPELINE.scope(name, f).await
+}
+
+pub async fn with_correlation_id<F: std::future::Future>(id: String, f: F) -> F::Output {
+    CORRELATION_ID.scope(id, f).await
+}
+
+/// `tokio::spawn` that keeps the pipeline and correlation id of the calling task, which a spawned
+/// task would otherwise lose.
+pub fn spawn<F>(f: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let pipeline = current_pipeline();
+    let correlation_id = CORRELATION_ID.try_with(|id| id.clone()).ok();
+
+    tokio::spawn(async move {
+        let f = async move {
+            match correlation_id {
+                Some(id) => CORRELATION_ID.scope(id, f).await,
+                None => f.await,
+            }
+        };
+        match pipeline {
+            Some(pipeline) => CURRENT_PIPELINE.scope(pipeline, f).await,
+            None => f.await,
+        }
+    })
+}
+
+/// `env_logger` format writing one JSON object per line, including the pipeline and correlation
+/// id of the task that emitted the record.
+pub fn format_json(buf: &mut env_logger::fmt::Formatter, record: &log::Record) -> std::io::Result<()> {
+    writeln!(buf, "{}", json_record(record, chrono::Utc::now()))
+}
+
+fn json_record(record: &log::Record, now: chrono::DateTime<chrono::Utc>) -> serde_json::Value {
+    let mut entry = serde_json::json!({
+        "timestamp": now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "level": record.level().to_string(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let Some(pipeline) = current_pipeline() {
+        entry["pipeline"] = serde_json::Value::String(pipeline);
+    }
+    if let Ok(id) = CORRELATION_ID.try_with(|id| id.clone()) {
+        entry["correlation_id"] = serde_json::Value::String(id);
+    }
+    entry
+}
+
+pub fn current_pipeline() -> Option<String> {
+    CURRENT_PIPELINE.try_with(|p| p.clone()).ok()
+}
+
+fn current_pipeline_matches(name: &str) -> Option<bool> {
+    CURRENT_PIPELINE.try_with(|p| p == name).ok()
+}
+
+pub struct PipelineLogFilter {
+    inner: Box<dyn log::Log>,
+    pipeline: String,
+}
+
+impl PipelineLogFilter {
+    pub fn new(inner: Box<dyn log::Log>, pipeline: String) -> Self {
+        PipelineLogFilter {
+            inner,
+            pipeline,
+        }
+    }
+}
+
+impl log::Log for PipelineLogFilter {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if current_pipeline_matches(self.pipeline.as_str()).unwrap_or(true) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Keeps every log record of the test binary. Tests run concurrently, so they pick their own
+/// records by content.
+#[cfg(test)]
+pub mod capture {
+    use std::sync::{Mutex, OnceLock};
+
+    #[derive(Clone, Debug)]
+    pub struct Record {
+        pub level: log::Level,
+        pub message: String,
+        pub correlation_id: Option<String>,
+    }
+
+    static RECORDS: Mutex<Vec<Record>> = Mutex::new(vec!());
+
+    struct CaptureLogger;
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            // Formatting may log as well, so it must happen before taking the lock.
+            let record = Record {
+                level: record.level(),
+                message: record.args().to_string(),
+                correlation_id: super::CORRELATION_ID.try_with(|id| id.clone()).ok(),
+            };
+            RECORDS.lock().unwrap().push(record);
+        }
+
+        fn flush(&self) {}
+    }
+
+    pub fn init() {
+        static INIT: OnceLock<()> = OnceLock::new();
+        INIT.get_or_init(|| {
+            log::set_logger(&CaptureLogger).expect("unable to set capture logger");
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    pub fn records(filter: impl Fn(&Record) -> bool) -> Vec<Record> {
+        RECORDS.lock().unwrap().iter().filter(|r| filter(r)).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use log::Log;
+
+    use super::*;
+
+    struct CaptureLogger(Arc<Mutex<Vec<String>>>);
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.0.lock().unwrap().push(format!("{}", record.args()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[tokio::test]
+    async fn test_filter_pipeline_logs() {
+        let captured = Arc::new(Mutex::new(vec!()));
+        let filter = PipelineLogFilter::new(Box::new(CaptureLogger(captured.clone())), "b".into());
+
+        with_pipeline("a".into(), async {
+            filter.log(&log::Record::builder().args(format_args!("from a")).build());
+        }).await;
+        with_pipeline("b".into(), async {
+            filter.log(&log::Record::builder().args(format_args!("from b")).build());
+        }).await;
+        filter.log(&log::Record::builder().args(format_args!("global")).build());
+
+        assert_eq!(*captured.lock().unwrap(), vec!(String::from("from b"), String::from("global")));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_keeps_pipeline() {
+        let (pipeline, correlation_id) = with_pipeline("a".into(), with_correlation_id("abc".into(), async {
+            spawn(async { (current_pipeline(), CORRELATION_ID.try_with(|id| id.clone()).ok()) }).await.unwrap()
+        })).await;
+        assert_eq!(pipeline, Some("a".to_string()));
+        assert_eq!(correlation_id, Some("abc".to_string()));
+
+        let pipeline = spawn(async { current_pipeline() }).await.unwrap();
+        assert_eq!(pipeline, None);
+    }
+
+    #[tokio::test]
+    async fn test_json_record() {
+        use chrono::TimeZone;
+
+        let now = chrono::Utc.with_ymd_and_hms(2021, 9, 1, 12, 30, 0).unwrap();
+        let record = |now| json_record(&log::Record::builder()
+            .level(log::Level::Warn)
+            .target("webhook::event")
+            .args(format_args!("retrying"))
+            .build(), now);
+
+        assert_eq!(record(now), serde_json::json!({
+            "timestamp": "2021-09-01T12:30:00.000Z",
+            "level": "WARN",
+            "target": "webhook::event",
+            "message": "retrying",
+        }));
+
+        let entry = with_pipeline("orders".into(), with_correlation_id("abc".into(), async {
+            record(now)
+        })).await;
+        assert_eq!(entry["pipeline"], "orders");
+        assert_eq!(entry["correlation_id"], "abc");
+    }
+}
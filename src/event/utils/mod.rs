@@ -1 +1,4 @@
-pub mod sync;
\ No newline at end of file
+pub mod backoff;
+pub mod logger;
+pub mod startup;
+pub mod sync;
@@ -0,0 +1,111 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+#[derive(Default)]
+pub struct Startup {
+    ready: AtomicBool,
+    state: Mutex<StartupState>,
+}
+
+#[derive(Default)]
+struct StartupState {
+    started: bool,
+    pending: BTreeSet<String>,
+}
+
+impl Startup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+
+    pub fn pending(&self) -> Vec<String> {
+        self.lock().pending.iter().cloned().collect()
+    }
+
+    pub fn register(&self, pipeline: &str) {
+        if !self.is_ready() {
+            self.lock().pending.insert(pipeline.to_string());
+        }
+    }
+
+    pub fn unregister(&self, pipeline: &str) {
+        let mut state = self.lock();
+        state.pending.remove(pipeline);
+        self.update(&state);
+    }
+
+    /// Marks `pipeline` ready once its receivers are built and connected, before its first pull.
+    pub fn pipeline_started(&self, pipeline: &str) {
+        let mut state = self.lock();
+        state.pending.remove(pipeline);
+        self.update(&state);
+    }
+
+    pub fn executor_started(&self) {
+        let mut state = self.lock();
+        state.started = true;
+        self.update(&state);
+    }
+
+    pub fn timed_out(&self) {
+        if !self.is_ready() {
+            log::warn!("startup timed out, pipelines still starting: {:?}", self.pending());
+            self.ready.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn update(&self, state: &StartupState) {
+        if state.started && state.pending.is_empty() {
+            self.ready.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StartupState> {
+        self.state.lock().expect("startup lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready_after_all_pipelines_started() {
+        let startup = Startup::new();
+        startup.register("a");
+        startup.register("b");
+        startup.pipeline_started("a");
+        startup.executor_started();
+
+        assert!(!startup.is_ready());
+        assert_eq!(startup.pending(), vec!("b".to_string()));
+
+        startup.pipeline_started("b");
+        assert!(startup.is_ready());
+    }
+
+    #[test]
+    fn test_ready_after_pending_pipeline_unregistered() {
+        let startup = Startup::new();
+        startup.register("a");
+        startup.executor_started();
+        startup.unregister("a");
+
+        assert!(startup.is_ready());
+    }
+
+    #[test]
+    fn test_ready_after_timeout() {
+        let startup = Startup::new();
+        startup.register("a");
+        startup.executor_started();
+        startup.timed_out();
+
+        assert!(startup.is_ready());
+    }
+}
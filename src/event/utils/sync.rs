@@ -1,38 +1,56 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
 pub trait GracefulSignalInvoker: Send {
     fn call(&self);
 }
 
 pub fn new_graceful_signal() -> (SingleGracefulSignalInvoker, GracefulSignal) {
-    let (s, r) = crossbeam_channel::unbounded();
-    (SingleGracefulSignalInvoker{s}, GracefulSignal{r})
+    let (s, r) = watch::channel(false);
+    (SingleGracefulSignalInvoker{s: Arc::new(s)}, GracefulSignal{r})
 }
 
+pub type GracefulFuture = Pin<Box<dyn Future<Output=()> + Send>>;
+
+#[derive(Clone)]
 pub struct GracefulSignal {
-    r: crossbeam_channel::Receiver<()>,
+    r: watch::Receiver<bool>,
 }
 
 impl GracefulSignal {
     pub async fn called(&self) {
-        let r = self.r.clone();
-        let res = tokio::task::spawn_blocking(move || {
-            if let Err(e) = r.recv() {
+        let mut r = self.r.clone();
+        while !*r.borrow_and_update() {
+            if let Err(e) = r.changed().await {
                 log::warn!("graceful signal is received with an channel error: {}", e);
+                return;
             }
-        }).await;
-
-        if let Err(e) = res {
-            log::error!("graceful signal thread join error: {}", e);
         }
     }
+
+    pub fn into_future(self) -> GracefulFuture {
+        Box::pin(async move { self.called().await })
+    }
+
+    // A dropped invoker counts as a stop, so the one backing this signal is kept alive forever.
+    #[cfg(test)]
+    pub fn never() -> Self {
+        static NEVER: std::sync::OnceLock<(SingleGracefulSignalInvoker, GracefulSignal)> = std::sync::OnceLock::new();
+        NEVER.get_or_init(new_graceful_signal).1.clone()
+    }
 }
 
+#[derive(Clone)]
 pub struct SingleGracefulSignalInvoker {
-    s: crossbeam_channel::Sender<()>,
+    s: Arc<watch::Sender<bool>>,
 }
 
 impl GracefulSignalInvoker for SingleGracefulSignalInvoker {
     fn call(&self) {
-        if let Err(e) = self.s.send(()) {
+        if let Err(e) = self.s.send(true) {
             log::error!("graceful signal is sent with an error: {}", e);
         };
     }
@@ -48,8 +66,87 @@ pub struct CombinedGracefulSignalInvoker {
     v: Vec<Box<dyn GracefulSignalInvoker>>
 }
 
+impl CombinedGracefulSignalInvoker {
+    pub fn push(&mut self, invoker: Box<dyn GracefulSignalInvoker>) {
+        self.v.push(invoker);
+    }
+
+    pub fn len(&self) -> usize {
+        self.v.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.v.is_empty()
+    }
+}
+
+impl std::fmt::Debug for CombinedGracefulSignalInvoker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CombinedGracefulSignalInvoker")
+            .field("len", &self.v.len())
+            .finish()
+    }
+}
+
 impl GracefulSignalInvoker for CombinedGracefulSignalInvoker {
     fn call(&self) {
         self.v.iter().for_each(|g| g.call());
     }
-}
\ No newline at end of file
+}
+impl GracefulSignalInvoker for std::sync::Arc<std::sync::Mutex<CombinedGracefulSignalInvoker>> {
+    fn call(&self) {
+        match self.lock() {
+            Ok(invoker) => invoker.call(),
+            Err(e) => log::error!("graceful signal invoker lock poisoned: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_graceful_signal_in_select() {
+        let (i, s) = new_graceful_signal();
+        let mut graceful_stop = s.into_future();
+
+        let mut ticks = 0;
+        loop {
+            tokio::select! {
+                _ = &mut graceful_stop => break,
+                _ = tokio::time::sleep(tokio::time::Duration::from_millis(5)) => {
+                    ticks += 1;
+                    if ticks == 3 {
+                        i.call();
+                    }
+                },
+            }
+        }
+
+        assert_eq!(ticks, 3);
+    }
+
+    #[tokio::test]
+    async fn test_graceful_signal_called_before_wait() {
+        let (i, s) = new_graceful_signal();
+        i.call();
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(1), s.called()).await;
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_combined_invoker_len_after_push() {
+        let mut combined = combine(vec!());
+        assert!(combined.is_empty());
+
+        let (i, _) = new_graceful_signal();
+        combined.push(Box::new(i.clone()));
+        combined.push(Box::new(i));
+
+        assert_eq!(combined.len(), 2);
+        assert!(!combined.is_empty());
+        assert_eq!(format!("{:?}", combined), "CombinedGracefulSignalInvoker { len: 2 }");
+    }
+}
@@ -0,0 +1,6 @@
+pub mod event;
+
+pub use event::{load_events, BuildError, Event, EventBuilder, Executor, Pipeline};
+pub use event::process::{Identifier, Item, State, Value};
+pub use event::sender::Sender;
+pub use event::trigger::{SourceEvent, SourceEventReceiver};
@@ -1,23 +1,116 @@
-mod event;
+mod probe;
 
-use crate::event::GracefulSignalInvoker;
+use webhook::event;
+use webhook::event::GracefulSignalInvoker;
 use serde::Deserialize;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 struct Config {
+    webhook_config_file: Option<String>,
     webhook_events_dir: Option<String>,
     webhook_log_level: Option<String>,
+    webhook_log_pipeline: Option<String>,
+    webhook_startup_probe: Option<u16>,
+    webhook_startup_timeout_secs: Option<u64>,
+    webhook_metrics_port: Option<u16>,
+    webhook_log_format: Option<LogFormat>,
+    webhook_health_port: Option<u16>,
+    webhook_drain_timeout_seconds: Option<u64>,
+    webhook_config_extensions: Option<String>,
+    webhook_require_tags: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct FileConfig {
+    events_dir: Option<String>,
+    log_level: Option<String>,
+    log_pipeline: Option<String>,
+    startup_probe: Option<u16>,
+    startup_timeout_secs: Option<u64>,
+    metrics_port: Option<u16>,
+    log_format: Option<LogFormat>,
+    health_port: Option<u16>,
+    drain_timeout_seconds: Option<u64>,
+    config_extensions: Option<String>,
+    require_tags: Option<String>,
+}
+
+impl Config {
+    fn load<I: IntoIterator<Item=(String, String)>>(vars: I) -> Self {
+        let config: Config = envy::from_iter(vars).expect("unable to load env");
+
+        match &config.webhook_config_file {
+            None => config,
+            Some(path) => {
+                let content = std::fs::read_to_string(path).expect("unable to read config file");
+                let file_config: FileConfig = serde_yaml::from_str(content.as_str())
+                    .expect("unable to parse config file");
+                config.merge(file_config)
+            }
+        }
+    }
+
+    fn config_extensions(&self) -> Vec<String> {
+        split_list(self.webhook_config_extensions.as_deref().unwrap_or("yaml,yml,toml,json"))
+            .into_iter()
+            .map(|e| e.trim_start_matches('.').to_string())
+            .filter(|e| !e.is_empty())
+            .collect()
+    }
+
+    fn required_tags(&self) -> Option<Vec<String>> {
+        self.webhook_require_tags.as_deref().map(split_list)
+    }
+
+    fn merge(self, file_config: FileConfig) -> Self {
+        Config {
+            webhook_config_file: self.webhook_config_file,
+            webhook_events_dir: self.webhook_events_dir.or(file_config.events_dir),
+            webhook_log_level: self.webhook_log_level.or(file_config.log_level),
+            webhook_log_pipeline: self.webhook_log_pipeline.or(file_config.log_pipeline),
+            webhook_startup_probe: self.webhook_startup_probe.or(file_config.startup_probe),
+            webhook_startup_timeout_secs: self.webhook_startup_timeout_secs.or(file_config.startup_timeout_secs),
+            webhook_metrics_port: self.webhook_metrics_port.or(file_config.metrics_port),
+            webhook_log_format: self.webhook_log_format.or(file_config.log_format),
+            webhook_health_port: self.webhook_health_port.or(file_config.health_port),
+            webhook_drain_timeout_seconds: self.webhook_drain_timeout_seconds.or(file_config.drain_timeout_seconds),
+            webhook_config_extensions: self.webhook_config_extensions.or(file_config.config_extensions),
+            webhook_require_tags: self.webhook_require_tags.or(file_config.require_tags),
+        }
+    }
+}
+
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+        .collect()
 }
 
 #[tokio::main]
 async fn main() {
-    let config: Config = envy::from_env().expect("unable to load env");
+    let config = Config::load(std::env::vars());
 
-    let logger = env_logger::Builder::new()
-        .filter_level(log::LevelFilter::Trace)
-        .build();
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(log::LevelFilter::Trace);
+    if config.webhook_log_format == Some(LogFormat::Json) {
+        builder.format(event::format_json);
+    }
+    let logger = builder.build();
 
-    log::set_boxed_logger(Box::new(logger)).expect("unable to set logger");
+    match &config.webhook_log_pipeline {
+        None => log::set_boxed_logger(Box::new(logger)),
+        Some(pipeline) => log::set_boxed_logger(Box::new(
+            event::PipelineLogFilter::new(Box::new(logger), pipeline.clone())
+        )),
+    }.expect("unable to set logger");
 
     let log_level = config
         .webhook_log_level
@@ -36,42 +129,281 @@ async fn main() {
 
     log::debug!("config: {:?}", config);
 
-    let events_dir = config.webhook_events_dir.unwrap_or("events".to_string());
-    let events = event::load_events(&events_dir);
+    let events = load_events(&config);
 
     log::debug!("events: {:?}", events);
 
-    let executor = event::Executor::new();
-    let (p, g) = executor.start(events);
+    let mut executor = event::Executor::new();
+    let (p, g) = executor.start(events).unwrap_or_else(|e| {
+        log::error!("unable to start pipelines: {}", e);
+        std::process::exit(1);
+    });
+    let executor = std::sync::Arc::new(executor);
+
+    if let Some(port) = config.webhook_startup_probe {
+        let startup = executor.startup();
+        let (addr, probe) = probe::startup_probe(port, startup.clone());
+        log::info!("startup probe listening on {}", addr);
+        tokio::spawn(probe);
+
+        if let Some(timeout) = config.webhook_startup_timeout_secs {
+            tokio::spawn(async move {
+                tokio::time::sleep(tokio::time::Duration::from_secs(timeout)).await;
+                startup.timed_out();
+            });
+        }
+    }
+
+    if let Some(port) = config.webhook_metrics_port {
+        let (addr, server) = probe::metrics_endpoint(port, executor.metrics());
+        log::info!("metrics endpoint listening on {}", addr);
+        tokio::spawn(server);
+    }
 
-    handle_signal(g);
+    if let Some(port) = config.webhook_health_port {
+        let liveness = {
+            let executor = executor.clone();
+            move || executor.liveness()
+        };
+        let (addr, server) = probe::health_endpoint(port, executor.startup(), liveness);
+        log::info!("health endpoint listening on {}", addr);
+        tokio::spawn(server);
+    }
 
-    p.await;
+    let reload = {
+        let executor = executor.clone();
+        let config = config.clone();
+        let runtime = tokio::runtime::Handle::current();
+        move || {
+            log::info!("reloading events");
+            let new_events = match try_load_events(&config) {
+                Ok(events) => events,
+                Err(e) => {
+                    log::error!("reload aborted, keeping the current pipelines: {}", e);
+                    return;
+                }
+            };
+            // Errors are logged by the executor, which keeps the current pipelines.
+            let _ = runtime.block_on(executor.reload(new_events));
+        }
+    };
+    let signal = handle_signal(g, reload);
+
+    tokio::pin!(p);
+    tokio::select! {
+        _ = &mut p => {}
+        _ = signal => match config.webhook_drain_timeout_seconds {
+            None => { p.await; }
+            Some(timeout) => {
+                if tokio::time::timeout(tokio::time::Duration::from_secs(timeout), &mut p).await.is_err() {
+                    let mut active = executor.liveness().into_iter()
+                        .filter(|(_, alive)| *alive)
+                        .map(|(name, _)| name)
+                        .collect::<Vec<_>>();
+                    active.sort();
+                    log::error!("pipelines still running after {}s drain timeout: {:?}", timeout, active);
+                    std::process::exit(1);
+                }
+            }
+        },
+    }
 
     log::info!("webhook turned off");
 }
 
-#[cfg(all(not(windows)))]
-fn handle_signal(g: Box<dyn GracefulSignalInvoker>) {
-    let mut signals = signal_hook::iterator::Signals::new(&[signal_hook::consts::SIGTERM])
-        .expect("unable to initialize signal handler");
+fn load_events(config: &Config) -> Vec<event::Event> {
+    let extensions = config.config_extensions();
+    let events = match &config.webhook_events_dir {
+        Some(events_dir) => event::load_events(events_dir, &extensions),
+        None => default_events(&extensions),
+    };
+    filter_required_tags(config, events)
+}
+
+/// Fails when any event file cannot be loaded, so that a broken file does not stop its pipelines.
+fn try_load_events(config: &Config) -> Result<Vec<event::Event>, event::LoadError> {
+    let extensions = config.config_extensions();
+    let events = match &config.webhook_events_dir {
+        Some(events_dir) => event::try_load_events(events_dir, &extensions)?,
+        None => try_default_events(&extensions)?,
+    };
+    Ok(filter_required_tags(config, events))
+}
 
+fn filter_required_tags(config: &Config, events: Vec<event::Event>) -> Vec<event::Event> {
+    match config.required_tags() {
+        None => events,
+        Some(tags) => events.into_iter()
+            .filter(|e| {
+                if !e.has_any_tag(&tags) {
+                    log::info!("event {} does not have any of the required tags {:?}, skipping", e.name(), tags);
+                }
+                e.has_any_tag(&tags)
+            })
+            .collect(),
+    }
+}
+
+#[cfg(feature = "compiled")]
+fn default_events(_: &[String]) -> Vec<event::Event> {
+    log::info!("loading compiled events");
+    event::load_compiled_events()
+}
+
+#[cfg(not(feature = "compiled"))]
+fn default_events(extensions: &[String]) -> Vec<event::Event> {
+    event::load_events(&"events".to_string(), extensions)
+}
+
+#[cfg(feature = "compiled")]
+fn try_default_events(_: &[String]) -> Result<Vec<event::Event>, event::LoadError> {
+    Ok(event::load_compiled_events())
+}
+
+#[cfg(not(feature = "compiled"))]
+fn try_default_events(extensions: &[String]) -> Result<Vec<event::Event>, event::LoadError> {
+    event::try_load_events(&"events".to_string(), extensions)
+}
+
+#[cfg(not(windows))]
+fn handle_signal(
+    g: Box<dyn GracefulSignalInvoker>,
+    mut reload: impl FnMut() + Send + 'static,
+) -> tokio::sync::oneshot::Receiver<()> {
+    let mut signals = signal_hook::iterator::Signals::new([
+        signal_hook::consts::SIGTERM,
+        signal_hook::consts::SIGINT,
+        signal_hook::consts::SIGHUP,
+    ]).expect("unable to initialize signal handler");
+
+    let (s, r) = tokio::sync::oneshot::channel();
     tokio::task::spawn_blocking(move || {
-        for _ in signals.forever() {
-            g.call();
-            break;
+        let mut stopped = Some(s);
+        for signal in signals.forever() {
+            if signal == signal_hook::consts::SIGHUP {
+                reload();
+                continue;
+            }
+
+            // A second stop signal skips the drain, e.g. pressing Ctrl+C twice.
+            match stopped.take() {
+                Some(s) => {
+                    log::info!("received signal {}, stopping pipelines", signal);
+                    g.call();
+                    let _ = s.send(());
+                }
+                None => {
+                    log::warn!("received signal {} while stopping, exiting immediately", signal);
+                    std::process::exit(1);
+                }
+            }
         }
     });
+    r
 }
 
 #[cfg(windows)]
-fn handle_signal(g: Box<dyn GracefulSignalInvoker>) {
+fn handle_signal(
+    g: Box<dyn GracefulSignalInvoker>,
+    _reload: impl FnMut() + Send + 'static,
+) -> tokio::sync::oneshot::Receiver<()> {
     log::warn!("signal is not yet handled in windows");
 
     let (s, r) = crossbeam_channel::unbounded();
+    let (done, signal) = tokio::sync::oneshot::channel();
     tokio::task::spawn_blocking(move || {
         r.recv().unwrap();
         s.send(()).unwrap();
         g.call();
+        let _ = done.send(());
     });
+    signal
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn test_load_log_format() {
+        let config = Config::load(vec!((String::from("WEBHOOK_LOG_FORMAT"), String::from("json"))));
+        assert_eq!(config.webhook_log_format, Some(LogFormat::Json));
+    }
+
+    #[test]
+    #[should_panic(expected = "unable to load env")]
+    fn test_load_unknown_log_format() {
+        Config::load(vec!((String::from("WEBHOOK_LOG_FORMAT"), String::from("xml"))));
+    }
+
+    #[test]
+    fn test_load_config_file_with_env_override() {
+        let path = std::env::temp_dir().join("webhook_config_file_test.yaml");
+        std::fs::write(&path, "events_dir: /etc/webhook/events\nlog_level: debug\nmetrics_port: 9100\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let config = Config::load(vec!(
+            (String::from("WEBHOOK_CONFIG_FILE"), path.clone()),
+            (String::from("WEBHOOK_LOG_LEVEL"), String::from("trace")),
+        ));
+
+        assert_eq!(config, Config {
+            webhook_config_file: Some(path),
+            webhook_events_dir: Some(String::from("/etc/webhook/events")),
+            webhook_log_level: Some(String::from("trace")),
+            webhook_log_pipeline: None,
+            webhook_startup_probe: None,
+            webhook_startup_timeout_secs: None,
+            webhook_metrics_port: Some(9100),
+            webhook_log_format: None,
+            webhook_health_port: None,
+            webhook_drain_timeout_seconds: None,
+            webhook_config_extensions: None,
+            webhook_require_tags: None,
+        });
+    }
+
+    #[test]
+    fn test_load_without_config_file() {
+        let config = Config::load(vec!(
+            (String::from("WEBHOOK_EVENTS_DIR"), String::from("events")),
+        ));
+
+        assert_eq!(config, Config {
+            webhook_config_file: None,
+            webhook_events_dir: Some(String::from("events")),
+            webhook_log_level: None,
+            webhook_log_pipeline: None,
+            webhook_startup_probe: None,
+            webhook_startup_timeout_secs: None,
+            webhook_metrics_port: None,
+            webhook_log_format: None,
+            webhook_health_port: None,
+            webhook_drain_timeout_seconds: None,
+            webhook_config_extensions: None,
+            webhook_require_tags: None,
+        });
+    }
+
+    #[test]
+    fn test_config_extensions() {
+        let config = Config::load(Vec::<(String, String)>::new());
+        assert_eq!(config.config_extensions(), vec!("yaml", "yml", "toml", "json"));
+
+        let config = Config::load(vec!(
+            (String::from("WEBHOOK_CONFIG_EXTENSIONS"), String::from("yaml, .json,")),
+        ));
+        assert_eq!(config.config_extensions(), vec!("yaml", "json"));
+    }
+
+    #[test]
+    fn test_required_tags() {
+        let config = Config::load(Vec::<(String, String)>::new());
+        assert_eq!(config.required_tags(), None);
+
+        let config = Config::load(vec!(
+            (String::from("WEBHOOK_REQUIRE_TAGS"), String::from("billing, eu")),
+        ));
+        assert_eq!(config.required_tags(), Some(vec!(String::from("billing"), String::from("eu"))));
+    }
 }
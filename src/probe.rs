@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use tokio::sync::Notify;
+
+use webhook::event::{PipelineMetrics, Startup};
+
+type Handler = Box<dyn Fn() -> Response<Body> + Send + Sync>;
+
+/// Serves `GET` requests for `routes`, where a `*` route matches any path, until `shutdown` resolves.
+fn serve(
+    name: &'static str,
+    port: u16,
+    routes: Vec<(&'static str, Handler)>,
+    shutdown: impl Future<Output=()> + Send + 'static,
+) -> (SocketAddr, impl Future<Output=()>) {
+    let routes = Arc::new(routes);
+    let make_service = make_service_fn(move |_| {
+        let routes = routes.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let res = route(&routes, &req);
+                async move { Ok::<_, Infallible>(res) }
+            }))
+        }
+    });
+
+    let server = Server::bind(&SocketAddr::from(([0, 0, 0, 0], port))).serve(make_service);
+    let addr = server.local_addr();
+    let server = server.with_graceful_shutdown(shutdown);
+
+    (addr, async move {
+        match server.await {
+            Ok(_) => log::info!("{} finished", name),
+            Err(e) => log::error!("{} server error: {}", name, e),
+        }
+    })
+}
+
+fn route(routes: &[(&'static str, Handler)], req: &Request<Body>) -> Response<Body> {
+    let handler = routes.iter()
+        .find(|(path, _)| *path == "*" || *path == req.uri().path())
+        .map(|(_, handler)| handler);
+
+    match handler {
+        Some(handler) if req.method() == Method::GET => handler(),
+        _ => {
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = StatusCode::NOT_FOUND;
+            res
+        }
+    }
+}
+
+fn json(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    let mut res = Response::new(Body::from(body.to_string()));
+    *res.status_mut() = status;
+    res.headers_mut().insert(hyper::header::CONTENT_TYPE, "application/json".parse().unwrap());
+    res
+}
+
+pub fn startup_probe(port: u16, startup: Arc<Startup>) -> (SocketAddr, impl Future<Output=()>) {
+    let done = Arc::new(Notify::new());
+
+    let handler: Handler = {
+        let done = done.clone();
+        Box::new(move || {
+            let res = respond_startup(&startup);
+            if res.status().is_success() {
+                done.notify_one();
+            }
+            res
+        })
+    };
+
+    serve("startup probe", port, vec!(("*", handler)), async move { done.notified().await })
+}
+
+fn respond_startup(startup: &Startup) -> Response<Body> {
+    if startup.is_ready() {
+        return Response::new(Body::from("ok"));
+    }
+
+    json(StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({ "pending": startup.pending() }))
+}
+
+pub fn metrics_endpoint(port: u16, metrics: Arc<PipelineMetrics>) -> (SocketAddr, impl Future<Output=()>) {
+    let handler: Handler = Box::new(move || {
+        let mut res = Response::new(Body::from(metrics.encode()));
+        res.headers_mut().insert(hyper::header::CONTENT_TYPE, "text/plain; version=0.0.4".parse().unwrap());
+        res
+    });
+
+    serve("metrics endpoint", port, vec!(("/metrics", handler)), futures::future::pending())
+}
+
+/// `liveness` reports whether each pipeline is still running, as `Executor::liveness` does.
+pub fn health_endpoint(
+    port: u16,
+    startup: Arc<Startup>,
+    liveness: impl Fn() -> HashMap<String, bool> + Send + Sync + 'static,
+) -> (SocketAddr, impl Future<Output=()>) {
+    let liveness = Arc::new(liveness);
+    let healthz: Handler = {
+        let liveness = liveness.clone();
+        Box::new(move || json(StatusCode::OK, serde_json::json!({ "status": "ok", "pipelines": liveness().len() })))
+    };
+    let readyz: Handler = Box::new(move || {
+        if !startup.is_ready() {
+            return json(StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({ "pending": startup.pending() }));
+        }
+
+        let mut stopped = liveness().into_iter()
+            .filter(|(_, alive)| !alive)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        stopped.sort();
+        match stopped.is_empty() {
+            true => json(StatusCode::OK, serde_json::json!({ "status": "ready" })),
+            false => json(StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({ "stopped": stopped })),
+        }
+    });
+
+    serve("health endpoint", port, vec!(("/healthz", healthz), ("/readyz", readyz)), futures::future::pending())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_startup_probe() {
+        let startup = Arc::new(Startup::new());
+        startup.register("a");
+        startup.executor_started();
+
+        let (addr, server) = startup_probe(0, startup.clone());
+        let server = tokio::spawn(server);
+        let url = format!("http://127.0.0.1:{}/", addr.port());
+
+        let res = reqwest::get(url.as_str()).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.text().await.unwrap(), r#"{"pending":["a"]}"#);
+
+        startup.pipeline_started("a");
+
+        let res = reqwest::get(url.as_str()).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+        let res = tokio::time::timeout(tokio::time::Duration::from_secs(5), server).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let metrics = Arc::new(PipelineMetrics::new());
+        metrics.event_received("orders");
+
+        let (addr, server) = metrics_endpoint(0, metrics.clone());
+        tokio::spawn(server);
+
+        let res = reqwest::get(format!("http://127.0.0.1:{}/metrics", addr.port())).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+        assert!(res.text().await.unwrap().contains("webhook_events_received_total{event_name=\"orders\"} 1"));
+
+        let res = reqwest::get(format!("http://127.0.0.1:{}/", addr.port())).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_health_endpoint() {
+        let startup = Arc::new(Startup::new());
+        startup.register("a");
+        startup.executor_started();
+        let alive = Arc::new(std::sync::atomic::AtomicBool::new(true));
+
+        let liveness = {
+            let alive = alive.clone();
+            move || HashMap::from([("a".to_string(), alive.load(std::sync::atomic::Ordering::SeqCst))])
+        };
+        let (addr, server) = health_endpoint(0, startup.clone(), liveness);
+        tokio::spawn(server);
+        let url = |path: &str| format!("http://127.0.0.1:{}{}", addr.port(), path);
+
+        let res = reqwest::get(url("/healthz")).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+        assert_eq!(res.text().await.unwrap(), r#"{"pipelines":1,"status":"ok"}"#);
+
+        let res = reqwest::get(url("/readyz")).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.text().await.unwrap(), r#"{"pending":["a"]}"#);
+
+        startup.pipeline_started("a");
+
+        let res = reqwest::get(url("/readyz")).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+        alive.store(false, std::sync::atomic::Ordering::SeqCst);
+
+        let res = reqwest::get(url("/readyz")).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.text().await.unwrap(), r#"{"stopped":["a"]}"#);
+    }
+}
@@ -0,0 +1,196 @@
+use hmac::{Hmac, Mac};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use wiremock::matchers::{body_json, body_string, header, method, path};
+
+use webhook::{Event, Executor};
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Runs `event` behind an http trigger, posts every body to it and stops the pipeline once each
+/// request is answered. The trigger answers after the targets are done with the message.
+async fn dispatch(event: &str, bodies: &[&str]) {
+    let port = free_port();
+    let event: Event = serde_yaml::from_str(format!(r#"
+name: http_sender
+trigger:
+  - type: http
+    config:
+      bind: 127.0.0.1
+      port: {}
+      path: /hook
+{}"#, port, event).as_str()).unwrap();
+
+    let mut executor = Executor::new();
+    let (pipeline, invoker) = executor.start(vec!(event)).unwrap();
+    let startup = executor.startup();
+
+    let client = async {
+        while !startup.is_ready() {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+
+        for body in bodies {
+            let res = reqwest::Client::new()
+                .post(format!("http://127.0.0.1:{}/hook", port))
+                .body(body.to_string())
+                .send()
+                .await
+                .unwrap();
+            assert!(res.status().is_success());
+        }
+        invoker.call();
+    };
+
+    let res = tokio::time::timeout(tokio::time::Duration::from_secs(10), async {
+        tokio::join!(pipeline, client)
+    }).await;
+    assert!(res.is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_dispatch_post_ok() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/target"))
+        .and(body_json(serde_json::json!({"greeting": "otter"})))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    dispatch(format!(r#"
+process:
+  - set_env: {{ target: body, value: {{ from_payload: json }} }}
+  - to_payload:
+      format: json
+      value:
+        as_map:
+          greeting:
+            get_env: body.name
+target:
+  - http:
+      - post:
+          url: {}/target
+"#, server.uri()).as_str(), &[r#"{"name": "otter"}"#]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_dispatch_retry_until_success() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_string("hello"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    dispatch(format!(r#"
+target:
+  - http:
+      - post:
+          url: {}/target
+          retry:
+            max_attempts: 3
+            initial_backoff_ms: 10
+"#, server.uri()).as_str(), &["hello"]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_dispatch_headers_from_state() {
+    let server = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/orders/42"))
+        .and(header("x-static", "static"))
+        .and(header("x-order", "42"))
+        .and(header("authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    dispatch(format!(r#"
+process:
+  - set_env: {{ target: order, value: {{ from_payload: json }} }}
+target:
+  - http:
+      - put:
+          url:
+            template: "{}/orders/{{{{ order.id }}}}"
+          headers:
+            x-static: static
+            x-order:
+              template: "{{{{ order.id }}}}"
+          auth:
+            token: secret
+"#, server.uri()).as_str(), &[r#"{"id": 42}"#]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_dispatch_hmac_signature_header() {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"signing-key").unwrap();
+    mac.update(b"order-42");
+    let signature = hex::encode(mac.finalize().into_bytes());
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(header("x-signature", signature.as_str()))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    dispatch(format!(r#"
+process:
+  - set_env: {{ target: order, value: {{ from_payload: json }} }}
+  - hmac:
+      value: {{ get_env: order.id }}
+      key: signing-key
+      algorithm: sha256
+      encoding: hex
+      target: signature
+target:
+  - http:
+      - post:
+          url: {}/target
+          headers:
+            x-signature:
+              from_env: signature
+"#, server.uri()).as_str(), &[r#"{"id": "order-42"}"#]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_dispatch_captured_response_feeds_next_target() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/create"))
+        .respond_with(ResponseTemplate::new(201).set_body_string(r#"{"id": 7}"#))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/items/7"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    dispatch(format!(r#"
+target:
+  - http:
+      - post:
+          url: {}/create
+          capture_response: {{ key: created, format: json }}
+  - http:
+      - post:
+          url:
+            template: "{}/items/{{{{ created.id }}}}"
+"#, server.uri(), server.uri()).as_str(), &["{}"]).await;
+}
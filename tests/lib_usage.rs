@@ -0,0 +1,76 @@
+use webhook::{Event, Executor, Identifier, Item, State, Value};
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_run_pipeline() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.json");
+    let port = free_port();
+
+    let event: Event = serde_yaml::from_str(format!(r#"
+name: lib_usage
+trigger:
+  - type: http
+    config:
+      bind: 127.0.0.1
+      port: {}
+      path: /hook
+process:
+  - set_env:
+      target: body
+      value:
+        from_payload: json
+  - to_payload:
+      format: json
+      value:
+        as_map:
+          greeting:
+            get_env: body.name
+target:
+  - file:
+      path: {}
+"#, port, output.display()).as_str()).unwrap();
+
+    let mut executor = Executor::new();
+    let (pipeline, invoker) = executor.start(vec!(event)).unwrap();
+    let startup = executor.startup();
+
+    let client = async {
+        while !startup.is_ready() {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+
+        let res = reqwest::Client::new()
+            .post(format!("http://127.0.0.1:{}/hook", port))
+            .body(r#"{"name": "otter"}"#)
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+
+        while !output.exists() {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        invoker.call();
+    };
+
+    let res = tokio::time::timeout(tokio::time::Duration::from_secs(10), async {
+        tokio::join!(pipeline, client)
+    }).await;
+    assert!(res.is_ok());
+
+    let written: serde_json::Value = serde_json::from_slice(&std::fs::read(&output).unwrap()).unwrap();
+    assert_eq!(written, serde_json::json!({"greeting": "otter"}));
+}
+
+#[test]
+fn test_state_api() {
+    let mut state = State::new();
+    state.set(Identifier::from("order.id"), Item::Value(Value::IntValue(42))).unwrap();
+
+    assert_eq!(state.get(&Identifier::from("order.id")), Some(&Item::Value(Value::IntValue(42))));
+    assert_eq!(state.keys(), vec!("order".to_string()));
+}